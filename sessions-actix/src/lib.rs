@@ -0,0 +1,148 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorInternalServerError,
+    http::header::{HeaderValue, COOKIE, SET_COOKIE},
+    Error, HttpMessage, HttpRequest,
+};
+use sessions_core::{
+    finish_session, join_cookie_headers, load_request_session, Config, Result, Session,
+};
+
+/// Loads the [`Session`] of each request into its extensions, then saves it
+/// and sets the cookie once the service responded
+///
+/// The session id is read from the `Cookie` header with the [`CookieOptions`] of
+/// the config, a new session is created without a valid one. Changed sessions are
+/// saved, unchanged ones [rolled](Session::roll), and the response gets the
+/// [`set_cookies`](Session::set_cookies) of the session status, see [`finish_session`].
+/// A session which can't be loaded or saved fails the request with
+/// `500 Internal Server Error`.
+///
+/// Handlers take the session as an argument or get it with [`SessionExt`].
+/// The futures of actix aren't `Send`, the storages are anyway.
+///
+/// [`CookieOptions`]: sessions_core::CookieOptions
+#[derive(Clone, Debug)]
+pub struct SessionMiddleware {
+    config: Arc<Config>,
+}
+
+impl SessionMiddleware {
+    /// Creates new `SessionMiddleware` with the `Config`
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SessionMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SessionMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionMiddlewareService {
+            service: Rc::new(service),
+            config: self.config.clone(),
+        }))
+    }
+}
+
+/// The service of the [`SessionMiddleware`]
+#[derive(Debug)]
+pub struct SessionMiddlewareService<S> {
+    service: Rc<S>,
+    config: Arc<Config>,
+}
+
+impl<S, B> Service<ServiceRequest> for SessionMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let config = self.config.clone();
+        let cookies = cookie_header(&req);
+
+        Box::pin(async move {
+            let session = load_request_session(&config, &cookies).await.map_err(|e| {
+                log::error!("failed to load session: {}", e);
+                ErrorInternalServerError(e)
+            })?;
+            req.extensions_mut().insert(session.clone());
+
+            let mut res = service.call(req).await?;
+
+            match finish(&session).await {
+                Ok(cookies) => {
+                    for cookie in cookies {
+                        res.headers_mut().append(SET_COOKIE, cookie);
+                    }
+                    Ok(res)
+                }
+                Err(e) => {
+                    log::error!("failed to save session: {}", e);
+                    Err(ErrorInternalServerError(e))
+                }
+            }
+        })
+    }
+}
+
+/// Gets the [`Session`] the [`SessionMiddleware`] loaded into a request
+pub trait SessionExt {
+    /// Gets the session, `None` on routes without the middleware
+    fn session(&self) -> Option<Session>;
+}
+
+impl SessionExt for HttpRequest {
+    fn session(&self) -> Option<Session> {
+        self.extensions().get::<Session>().cloned()
+    }
+}
+
+impl SessionExt for ServiceRequest {
+    fn session(&self) -> Option<Session> {
+        self.extensions().get::<Session>().cloned()
+    }
+}
+
+/// Joins the `Cookie` headers
+fn cookie_header(req: &ServiceRequest) -> String {
+    join_cookie_headers(
+        req.headers()
+            .get_all(COOKIE)
+            .filter_map(|val| val.to_str().ok()),
+    )
+}
+
+/// Finishes the session, getting the `Set-Cookie` values of its status
+async fn finish(session: &Session) -> Result<Vec<HeaderValue>> {
+    finish_session(session)
+        .await?
+        .iter()
+        .map(|cookie| Ok(HeaderValue::from_str(cookie)?))
+        .collect()
+}
@@ -0,0 +1,22 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload, error::ErrorInternalServerError, Error, FromRequest, HttpMessage, HttpRequest,
+};
+
+use crate::Session;
+
+/// Extracts the session the `SessionMiddleware` loaded into the request,
+/// failing with `500 Internal Server Error` on routes without the middleware
+impl FromRequest for Session {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<Session>().cloned().ok_or_else(|| {
+            ErrorInternalServerError(
+                "no session in the request, the `SessionMiddleware` isn't installed on this route",
+            )
+        }))
+    }
+}
@@ -0,0 +1,71 @@
+//! Binary values in the session data
+//!
+//! The data is made of JSON values, bytes are kept as an object with the single
+//! key [`KEY`] and their base64 as value:
+//!
+//! ```json
+//! { "$bytes": "aGVsbG8=" }
+//! ```
+//!
+//! JSON stores them so, serializers with byte strings write the bytes themselves
+//! and read them back as this object. [`Session::set_bytes`] and [`Session::get_bytes`]
+//! take and give the bytes, [`Session::get_raw`] and [`Session::iter_raw`] tell
+//! them apart from the other values.
+//!
+//! [`Session::set_bytes`]: crate::Session::set_bytes
+//! [`Session::get_bytes`]: crate::Session::get_bytes
+//! [`Session::get_raw`]: crate::Session::get_raw
+//! [`Session::iter_raw`]: crate::Session::iter_raw
+
+use crate::data::{Map, Value};
+
+/// The key of the object holding bytes
+pub const KEY: &str = "$bytes";
+
+/// A value of the session data, bytes apart
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawValue {
+    /// Bytes
+    Bytes(Vec<u8>),
+    /// Any other value
+    Value(Value),
+}
+
+impl From<Value> for RawValue {
+    fn from(val: Value) -> Self {
+        match from_value(&val) {
+            Some(bytes) => Self::Bytes(bytes),
+            None => Self::Value(val),
+        }
+    }
+}
+
+impl From<RawValue> for Value {
+    fn from(raw: RawValue) -> Self {
+        match raw {
+            RawValue::Bytes(bytes) => to_value(&bytes),
+            RawValue::Value(val) => val,
+        }
+    }
+}
+
+/// Wraps the bytes in a value
+pub fn to_value(bytes: &[u8]) -> Value {
+    let mut object = Map::new();
+    object.insert(KEY.into(), base64::encode(bytes).into());
+    Value::Object(object)
+}
+
+/// Unwraps the bytes of a value, `None` if it doesn't hold bytes
+pub fn from_value(val: &Value) -> Option<Vec<u8>> {
+    match val {
+        Value::Object(object) if object.len() == 1 => {
+            let encoded = object.get(KEY)?.as_str()?;
+            // Only the base64 written by `to_value`, to read back the same value
+            base64::decode(encoded)
+                .ok()
+                .filter(|bytes| base64::encode(bytes) == encoded)
+        }
+        _ => None,
+    }
+}
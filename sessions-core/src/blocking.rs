@@ -0,0 +1,142 @@
+use std::{future::Future, time::Duration};
+
+use crate::{
+    anyhow,
+    data::{DeserializeOwned, Serialize, Value},
+    Payload, Result, Session, Storage,
+};
+
+const ASYNC_CONTEXT: &str = "blocking sessions can't be used within an async context";
+
+/// Drives the future to completion on the current thread
+///
+/// Fails instead of deadlocking when the thread is already running an executor.
+fn block_on<F: Future>(fut: F) -> Result<F::Output> {
+    #[cfg(feature = "tokio")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(anyhow!(ASYNC_CONTEXT));
+    }
+    futures_executor::enter().map_err(|_| anyhow!(ASYNC_CONTEXT))?;
+    Ok(futures_executor::block_on(fut))
+}
+
+/// Exposes a [`Storage`] through synchronous methods, for CLI tools and tests
+///
+/// Each call drives the storage on a lightweight executor, so it must not be
+/// called from within an async context. Calls made on a thread that is running
+/// a `futures` executor return an error, as do the calls made within a tokio
+/// runtime, `spawn_blocking` included, with the `tokio` feature. Other runtimes
+/// aren't detected.
+#[derive(Debug, Clone)]
+pub struct BlockingStore<S> {
+    inner: S,
+}
+
+impl<S> BlockingStore<S> {
+    /// Creates new `BlockingStore` over the storage
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// Gets the async storage
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a data from storage by the key
+    pub fn get<P>(&self, key: &str) -> Result<Option<P>>
+    where
+        S: Storage<P>,
+        P: Payload,
+    {
+        block_on(self.inner.get(key))?
+    }
+
+    /// Set a session to storage
+    pub fn set<P>(&self, key: &str, val: P, exp: Duration) -> Result<()>
+    where
+        S: Storage<P>,
+        P: Payload,
+    {
+        block_on(self.inner.set(key, val, exp))?
+    }
+
+    /// Remove a data from storage by the key
+    pub fn remove<P>(&self, key: &str) -> Result<()>
+    where
+        S: Storage<P>,
+        P: Payload,
+    {
+        block_on(self.inner.remove(key))?
+    }
+
+    /// Purge expired data from storage, returns the number of removed keys
+    pub fn cleanup<P>(&self) -> Result<u64>
+    where
+        S: Storage<P>,
+        P: Payload,
+    {
+        block_on(self.inner.cleanup())?
+    }
+}
+
+/// A [`Session`] with synchronous saving, see [`BlockingStore`] for the caveats
+#[derive(Debug, Clone)]
+pub struct BlockingSession {
+    inner: Session,
+}
+
+impl BlockingSession {
+    /// Creates new `BlockingSession` over the session
+    pub fn new(inner: Session) -> Self {
+        Self { inner }
+    }
+
+    /// Gets the async session
+    pub fn inner(&self) -> &Session {
+        &self.inner
+    }
+
+    /// Unwraps the async session
+    pub fn into_inner(self) -> Session {
+        self.inner
+    }
+
+    /// Gets the session id
+    pub fn id(&self) -> Result<String> {
+        self.inner.id()
+    }
+
+    /// Gets a value by the key
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.inner.get(key)
+    }
+
+    /// Sets a value by the key, returns the previous one without deserializing it
+    pub fn set<T: Serialize>(&self, key: &str, val: T) -> Result<Option<Value>> {
+        self.inner.set(key, val)
+    }
+
+    /// Sets a value by the key, returns the previous one as a `T`
+    pub fn replace<T>(&self, key: &str, val: T) -> Result<Option<T>>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        self.inner.replace(key, val)
+    }
+
+    /// Removes a value
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.inner.remove(key)
+    }
+
+    /// Saves the current state to the store
+    pub fn save(&self) -> Result<()> {
+        block_on(self.inner.save())?
+    }
+
+    /// Destroys the current state from store
+    pub fn destroy(&self) -> Result<()> {
+        block_on(self.inner.destroy())?
+    }
+}
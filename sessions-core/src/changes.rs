@@ -0,0 +1,44 @@
+use std::{
+    collections::HashSet,
+    sync::{Mutex, MutexGuard},
+};
+
+/// The keys changed since the session was loaded or last saved
+#[derive(Debug, Default)]
+pub(crate) struct Changes(Mutex<HashSet<String>>);
+
+impl Changes {
+    fn lock(&self) -> MutexGuard<'_, HashSet<String>> {
+        // The set stays consistent even if a holder panicked
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn mark(&self, key: &str) {
+        self.lock().insert(key.into());
+    }
+
+    pub(crate) fn mark_all<'a, I: IntoIterator<Item = &'a String>>(&self, keys: I) {
+        self.lock().extend(keys.into_iter().cloned());
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    pub(crate) fn keys(&self) -> Vec<String> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Takes the changed keys, to be restored if they couldn't be saved
+    pub(crate) fn take(&self) -> HashSet<String> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    pub(crate) fn restore(&self, keys: HashSet<String>) {
+        self.lock().extend(keys);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use futures_executor::block_on;
+
+use crate::{Payload, Storage};
+
+/// Spawns a background task which purges expired sessions every `interval`
+///
+/// The task runs on its own thread, so it doesn't depend on any async runtime.
+/// It stops when the returned handle is stopped or dropped.
+pub fn spawn_cleanup<P: Payload>(
+    storage: Arc<dyn Storage<P>>,
+    interval: Duration,
+) -> CleanupHandle {
+    let (stop, rx) = mpsc::channel::<()>();
+
+    let thread = thread::Builder::new()
+        .name("sessions-cleanup".into())
+        .spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                match block_on(storage.cleanup()) {
+                    Ok(0) => {}
+                    Ok(n) => log::debug!("purged {} expired sessions", n),
+                    Err(e) => log::error!("failed to purge expired sessions: {}", e),
+                }
+            }
+        })
+        .expect("failed to spawn the sessions cleanup thread");
+
+    CleanupHandle {
+        inner: Mutex::new(Some((stop, thread))),
+    }
+}
+
+/// A handle to the background cleanup task
+#[derive(Debug)]
+pub struct CleanupHandle {
+    inner: Mutex<Option<(mpsc::Sender<()>, thread::JoinHandle<()>)>>,
+}
+
+impl CleanupHandle {
+    /// Stops the cleanup task and waits for it to finish, stopping twice is a no-op
+    pub fn stop(&self) {
+        let inner = match self.inner.lock() {
+            Ok(mut inner) => inner.take(),
+            Err(e) => e.into_inner().take(),
+        };
+        if let Some((stop, thread)) = inner {
+            let _ = stop.send(());
+            let _ = thread.join();
+        }
+    }
+}
@@ -1,8 +1,26 @@
-use std::{fmt, sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    fmt,
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use crate::{async_trait, CookieOptions, Data, Result, Storage};
+use crate::{
+    async_trait, cookie_options::is_value, data_schema_version, keys, session::data_size,
+    timeout::timeout, CleanupHandle, ConfigBuilder, ConfigOptions, CookieError, CookieOptions,
+    Data, Metadata, OpaqueIds, Result, Serializer, Session, SessionHooks, SessionStatus, Storage,
+};
+
+/// How long the storage keeps a session if neither the ttl nor the cookie's max_age is set
+const DEFAULT_TTL: Duration = Duration::from_secs(3600 * 24);
 
 /// Sessions Config
+///
+/// It implements [`Storage`], so apps holding it can work on sessions by id
+/// without a [`Session`], e.g. a background job invalidating one with
+/// [`remove`](Storage::remove). Its storage methods verify the ids, apply the
+/// [`key_prefix`](Config::key_prefix) and the timeouts.
 pub struct Config {
     /// Cookie Options
     pub cookie: CookieOptions,
@@ -12,9 +30,110 @@ pub struct Config {
     pub generate: Box<dyn GenerateFn>,
     /// Verifes session id
     pub verify: Box<dyn VerifyFn>,
+    /// Maximum time to wait for the storage to answer a ping
+    pub ping_timeout: Option<Duration>,
+    /// The background cleanup task, stopped on [`shutdown`](Config::shutdown)
+    pub cleanup: Option<CleanupHandle>,
+    /// Replaces the CSRF token after it was verified, so each token is used once
+    pub rotate_csrf: bool,
+    /// Maximum size of the serialized session data in bytes, larger data fails
+    /// to be set or saved with a [`DataTooLarge`](crate::DataTooLarge) error,
+    /// without writing to the storage. `0` disables the check too.
+    pub max_data_size: Option<usize>,
+    /// Tells the time of per-key TTLs, the system clock if `None`
+    pub clock: Option<Box<dyn ClockFn>>,
+    /// How long the storage keeps a session, see [`store_ttl`](Config::store_ttl)
+    pub ttl: Option<Duration>,
+    /// Expires a session which wasn't saved or touched for this long
+    pub idle_timeout: Option<Duration>,
+    /// Expires a session this long after it was created, however active it is
+    pub absolute_timeout: Option<Duration>,
+    /// Refreshes the expiry of unchanged sessions on every request, see
+    /// [`Session::roll`](crate::Session::roll)
+    pub rolling: bool,
+    /// Minimum time between two refreshes of a rolling session
+    pub rolling_interval: Option<Duration>,
+    /// Saves [fresh](crate::Session::is_fresh) sessions too, so every visitor
+    /// gets a stored session and a cookie
+    pub save_uninitialized: bool,
+    /// The serializer of the byte-oriented storage, measuring the data against
+    /// the `max_data_size`, JSON if `None`
+    pub serializer: Option<Arc<dyn Serializer>>,
+    /// Callbacks on the lifecycle of sessions
+    pub hooks: Option<Arc<dyn SessionHooks>>,
+    /// Fails the session method with the error of a hook, which is logged otherwise
+    pub strict_hooks: bool,
+    /// Prepended to the session ids to make the keys of the storage, so apps
+    /// sharing a storage get their own keyspaces while the cookies keep the bare ids
+    pub key_prefix: Option<String>,
+    /// The schema version of the data, stamped under [`keys::SCHEMA_VERSION`] on save,
+    /// `0` leaves the data unversioned
+    pub schema_version: u64,
+    /// Migrates the data stored under an older schema version when the session
+    /// is loaded, see [`ConfigBuilder::migration`]
+    pub migration: Option<Box<dyn MigrationFn>>,
+    /// Encrypts the generated session ids into opaque tokens, the ids the cookies
+    /// and the session API see, decrypted back before reaching the storage
+    ///
+    /// Tokens which can't be decrypted aren't verified, so they're answered as
+    /// missing sessions. A renewed session gets a fresh id, so its previous token
+    /// doesn't load it anymore.
+    pub opaque_ids: Option<OpaqueIds>,
 }
 
 impl Config {
+    /// Creates a [`ConfigBuilder`], the way to construct a config
+    ///
+    /// ```ignore
+    /// let config = Config::builder()
+    ///     .storage(Arc::new(MemoryStorage::new()))
+    ///     .cookie(CookieOptions::strict())
+    ///     .generate(|| nanoid::nanoid!(32))
+    ///     .verify(|sid: &str| sid.len() == 32)
+    ///     .build()?;
+    /// ```
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Creates a config from the options, with the storage and the session id functions
+    ///
+    /// Fails like [`validate`](Config::validate) if the options are misconfigured.
+    #[deprecated(
+        since = "0.2.0",
+        note = "use `Config::builder` with `ConfigBuilder::options`"
+    )]
+    pub fn from_options(
+        options: ConfigOptions,
+        storage: Arc<dyn Storage>,
+        generate: impl GenerateFn,
+        verify: impl VerifyFn,
+    ) -> Result<Self> {
+        Ok(Self::builder()
+            .options(options)
+            .storage(storage)
+            .generate(generate)
+            .verify(verify)
+            .build()?)
+    }
+
+    /// Checks the config, so a misconfiguration fails at startup
+    ///
+    /// Fails with a [`CookieError`](crate::CookieError) if the cookie options make
+    /// a cookie browsers would reject, or if a generated session id has characters
+    /// not allowed in a cookie value.
+    pub fn validate(&self) -> Result<()> {
+        Ok(self.check()?)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), CookieError> {
+        self.cookie.validate()?;
+        if !self.generate().bytes().all(is_value) {
+            return Err(CookieError::InvalidValue);
+        }
+        Ok(())
+    }
+
     /// Gets current storage
     pub fn storage(&self) -> Arc<dyn Storage> {
         self.storage.clone()
@@ -25,40 +144,357 @@ impl Config {
         &self.cookie
     }
 
-    /// Gets cookie's max_age or session's expries
+    /// Gets how long the storage keeps a session
+    #[deprecated(since = "0.2.0", note = "use `store_ttl` or `cookie_max_age`")]
     pub fn max_age(&self) -> Duration {
+        self.store_ttl()
+    }
+
+    /// Gets how long the storage keeps a session, passed to [`Storage::set`]
+    /// and [`Storage::touch`] on save and touch
+    ///
+    /// It's the `ttl` if set, otherwise the [`cookie_max_age`](Config::cookie_max_age),
+    /// or `24H` for a browser-session cookie. A longer `ttl` than the cookie's max_age
+    /// keeps remember-me sessions in the storage while the browser has to get the
+    /// cookie reissued, e.g. a `30d` ttl with a `24H` cookie.
+    ///
+    /// Touches extend the stored session by the ttl, but the cookie's `Max-Age`
+    /// only restarts when a `Set-Cookie` is sent, so the cookie expires first unless
+    /// it's reissued, as [`rolling`](Config::rolling) sessions are.
+    pub fn store_ttl(&self) -> Duration {
+        self.ttl.or(self.cookie.max_age).unwrap_or(DEFAULT_TTL)
+    }
+
+    /// Gets the `Max-Age` of the session cookie, `None` for a browser-session cookie
+    ///
+    /// It's the cookie's max_age, independent of the `ttl`, see
+    /// [`store_ttl`](Config::store_ttl).
+    pub fn cookie_max_age(&self) -> Option<Duration> {
         self.cookie.max_age
     }
 
-    /// Generates a session id
+    /// Gets when the session of the metadata times out, the first of the idle
+    /// and absolute timeouts, `None` without timeouts or timestamps
+    ///
+    /// Saves and touches restart the idle timeout, never the absolute one.
+    pub fn timeout_at(&self, metadata: &Metadata) -> Option<SystemTime> {
+        let idle = self
+            .idle_timeout
+            .and_then(|timeout| Some(metadata.last_accessed? + timeout));
+        let absolute = self
+            .absolute_timeout
+            .and_then(|timeout| Some(metadata.created_at? + timeout));
+        match (idle, absolute) {
+            (Some(idle), Some(absolute)) => Some(idle.min(absolute)),
+            (idle, absolute) => idle.or(absolute),
+        }
+    }
+
+    /// Checks if the session of the metadata timed out, see [`timeout_at`](Config::timeout_at)
+    pub fn is_timed_out(&self, metadata: &Metadata) -> bool {
+        self.timeout_at(metadata)
+            .is_some_and(|timeout_at| self.now() >= timeout_at)
+    }
+
+    /// Gets the key of the session id in the storage, see [`key_prefix`](Config::key_prefix)
+    ///
+    /// With [`opaque_ids`](Config::opaque_ids), it's the key of the id decrypted from
+    /// the token. A token which can't be decrypted is used as it is: the storage
+    /// methods which don't verify the ids, like [`set`](Storage::set), write it so.
+    pub fn store_key<'a>(&self, sid: &'a str) -> Cow<'a, str> {
+        let sid = match self.opaque_ids.as_ref().and_then(|ids| ids.open(sid)) {
+            Some(opened) => Cow::Owned(opened.id),
+            None => Cow::Borrowed(sid),
+        };
+        match &self.key_prefix {
+            Some(prefix) => Cow::Owned(format!("{}{}", prefix, sid)),
+            None => sid,
+        }
+    }
+
+    fn has_timeouts(&self) -> bool {
+        self.idle_timeout.is_some() || self.absolute_timeout.is_some()
+    }
+
+    /// Gets the size of the data serialized by the [`serializer`](Config::serializer),
+    /// in bytes
+    pub fn data_size(&self, data: &Data) -> usize {
+        match &self.serializer {
+            Some(serializer) => serializer.serialize(data).map_or(0, |buf| buf.len()),
+            None => data_size(data),
+        }
+    }
+
+    /// Migrates the data of a loaded session to the [`schema_version`](Config::schema_version),
+    /// `None` if it's already there
+    ///
+    /// The [`migration`](Config::migration) gets the stored schema version and the
+    /// data, `0` for data written before it was versioned. Without one, the data
+    /// is only stamped with the current version.
+    pub fn migrate(&self, data: &Data) -> Result<Option<Data>> {
+        let version = data_schema_version(data);
+        if self.schema_version == 0 || version >= self.schema_version {
+            return Ok(None);
+        }
+        let mut data = match &self.migration {
+            Some(migration) => migration.call(version, data.clone())?,
+            None => data.clone(),
+        };
+        data.insert(keys::SCHEMA_VERSION.into(), self.schema_version.into());
+        Ok(Some(data))
+    }
+
+    /// Checks if the data was written under a newer schema version than the config's,
+    /// by a newer release of the app, so it can't be read
+    fn is_from_future(&self, data: &Data) -> bool {
+        self.schema_version > 0 && data_schema_version(data) > self.schema_version
+    }
+
+    /// Runs a hook if any are set, its error is logged unless the hooks are strict
+    pub(crate) async fn run_hook<'a, F, Fut>(&'a self, name: &str, f: F) -> Result<()>
+    where
+        F: FnOnce(&'a dyn SessionHooks) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let hooks = match &self.hooks {
+            Some(hooks) => hooks,
+            None => return Ok(()),
+        };
+        match f(hooks.as_ref()).await {
+            Err(e) if !self.strict_hooks => {
+                log::warn!("session hook `{}` failed: {}", name, e);
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    /// Gets the current time from the clock
+    pub fn now(&self) -> SystemTime {
+        self.clock
+            .as_ref()
+            .map_or_else(SystemTime::now, |clock| clock.call())
+    }
+
+    /// Generates a session id, a token of it with [`opaque_ids`](Config::opaque_ids)
     pub fn generate(&self) -> String {
-        self.generate.call()
+        let id = self.generate.call();
+        match &self.opaque_ids {
+            Some(ids) => ids.issue(&id, self.now()),
+            None => id,
+        }
     }
 
     /// Verifes a session id
+    ///
+    /// The storage methods of the config check the ids they read with it, so
+    /// invalid ids are answered without any storage I/O.
+    ///
+    /// With [`opaque_ids`](Config::opaque_ids), the token must be decrypted and
+    /// the id in it verified.
     pub fn verify(&self, key: &str) -> bool {
-        self.verify.call(key)
+        match &self.opaque_ids {
+            Some(ids) => ids
+                .open(key)
+                .is_some_and(|opened| self.verify.call(&opened.id)),
+            None => self.verify.call(key),
+        }
+    }
+
+    /// Loads the session of the id from the storage, or creates a new one
+    /// under a generated id if the id isn't verified or has no stored session
+    ///
+    /// Integrations pass the id of the request's cookie, or `""` without one.
+    /// The session is [new](Session::is_new) unless it was loaded.
+    ///
+    /// With [`opaque_ids`](Config::opaque_ids), a token only decrypted with a
+    /// previous key is issued again under the active one, and the session's cookie
+    /// [reissued](Session::reissue_cookie).
+    pub async fn load_session(self: &Arc<Self>, sid: &str) -> Result<Session> {
+        if let Some(data) = self.get(sid).await? {
+            let reissued = self
+                .opaque_ids
+                .as_ref()
+                .and_then(|ids| Some((ids, ids.open(sid)?)))
+                .filter(|(_, opened)| opened.reissue)
+                .map(|(ids, opened)| ids.issue(&opened.id, self.now()));
+            let session = Session::new(
+                reissued.as_deref().unwrap_or(sid),
+                SessionStatus::Unchanged,
+                self.clone(),
+            );
+            session.load_data(data)?;
+            if reissued.is_some() {
+                session.reissue_cookie();
+            }
+            return Ok(session);
+        }
+        Ok(Session::new(
+            &self.generate(),
+            SessionStatus::Unchanged,
+            self.clone(),
+        ))
+    }
+
+    /// Stops the background cleanup task and closes the storage,
+    /// flushing pending writes
+    pub async fn shutdown(&self) -> Result<()> {
+        if let Some(cleanup) = &self.cleanup {
+            cleanup.stop();
+        }
+        self.storage.close().await
     }
 }
 
 #[async_trait]
 impl Storage for Config {
-    /// Get a data from storage by the key
+    /// Get a data from storage by the key, `None` if the key isn't verified,
+    /// the session [timed out](Config::is_timed_out) or its data is of a newer
+    /// [`schema_version`](Config::schema_version)
+    ///
+    /// A timed out session is removed, so the `on_expire` hook runs once.
     async fn get(&self, key: &str) -> Result<Option<Data>> {
-        self.storage.get(key).await
+        if !self.verify(key) {
+            return Ok(None);
+        }
+        let store_key = self.store_key(key);
+        match self.storage.get(&store_key).await? {
+            Some(data) if self.is_timed_out(&Metadata::from_data(&data)) => {
+                self.storage.remove(&store_key).await?;
+                self.run_hook("on_expire", |hooks| hooks.on_expire(key))
+                    .await?;
+                Ok(None)
+            }
+            Some(data) if self.is_from_future(&data) => {
+                log::warn!(
+                    "session data has the unknown schema version {}",
+                    data_schema_version(&data)
+                );
+                Ok(None)
+            }
+            data => Ok(data),
+        }
+    }
+
+    /// Get the metadata of a session from storage by the key, `None` if the key
+    /// isn't verified or the session [timed out](Config::is_timed_out)
+    async fn metadata(&self, key: &str) -> Result<Option<Metadata>> {
+        if !self.verify(key) {
+            return Ok(None);
+        }
+        Ok(self
+            .storage
+            .metadata(&self.store_key(key))
+            .await?
+            .filter(|metadata| !self.is_timed_out(metadata)))
     }
 
     /// Set a data to storage by the key
     async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
-        self.storage.set(key, val, exp).await
+        self.storage.set(&self.store_key(key), val, exp).await
+    }
+
+    /// Set a data to storage by the key if the stored version matches
+    async fn set_if_version(
+        &self,
+        key: &str,
+        val: Data,
+        exp: Duration,
+        version: u64,
+    ) -> Result<()> {
+        self.storage
+            .set_if_version(&self.store_key(key), val, exp, version)
+            .await
     }
 
-    /// Remove a data from storage by the key
+    /// Remove a data from storage by the key, nothing to remove if the key
+    /// isn't verified
     async fn remove(&self, key: &str) -> Result<()> {
-        self.storage.remove(key).await
+        if !self.verify(key) {
+            return Ok(());
+        }
+        self.storage.remove(&self.store_key(key)).await
     }
 
-    /// Reset the storage and remove all keys
+    /// Extend the expiry of the data and set its last accessed time, `false` if
+    /// the key isn't verified or the session [timed out](Config::is_timed_out)
+    async fn touch(&self, key: &str, accessed: u64, exp: Duration) -> Result<bool> {
+        if !self.verify(key) {
+            return Ok(false);
+        }
+        if self.has_timeouts() && self.metadata(key).await?.is_none() {
+            return Ok(false);
+        }
+        self.storage
+            .touch(&self.store_key(key), accessed, exp)
+            .await
+    }
+
+    /// Move the data from the `old` key to the `new` key
+    async fn rename(&self, old: &str, new: &str) -> Result<()> {
+        self.storage
+            .rename(&self.store_key(old), &self.store_key(new))
+            .await
+    }
+
+    /// Get all session ids bound to the user, with a [`key_prefix`](Config::key_prefix)
+    /// only those under the prefix, without it, with [`opaque_ids`](Config::opaque_ids)
+    /// as fresh tokens
+    async fn sessions_for(&self, user_id: &str) -> Result<Vec<String>> {
+        let keys = self.storage.sessions_for(user_id).await?;
+        let sids = match &self.key_prefix {
+            Some(prefix) => keys
+                .iter()
+                .filter_map(|key| key.strip_prefix(prefix.as_str()))
+                .map(String::from)
+                .collect(),
+            None => keys,
+        };
+        Ok(match &self.opaque_ids {
+            Some(ids) => sids.iter().map(|sid| ids.issue(sid, self.now())).collect(),
+            None => sids,
+        })
+    }
+
+    /// Remove all sessions bound to the user, with a [`key_prefix`](Config::key_prefix)
+    /// only those under the prefix
+    async fn destroy_for(&self, user_id: &str) -> Result<u64> {
+        if self.key_prefix.is_none() {
+            return self.storage.destroy_for(user_id).await;
+        }
+        let sids = self.sessions_for(user_id).await?;
+        for sid in &sids {
+            self.storage.remove(&self.store_key(sid)).await?;
+        }
+        Ok(sids.len() as u64)
+    }
+
+    /// Try to acquire the lock on the key
+    async fn try_lock(&self, key: &str, token: &str, ttl: Duration) -> Result<bool> {
+        self.storage
+            .try_lock(&self.store_key(key), token, ttl)
+            .await
+    }
+
+    /// Release the lock on the key
+    async fn unlock(&self, key: &str, token: &str) -> Result<bool> {
+        self.storage.unlock(&self.store_key(key), token).await
+    }
+
+    /// Check that the storage is reachable within `ping_timeout`
+    async fn ping(&self) -> Result<()> {
+        match self.ping_timeout {
+            Some(dur) => timeout(dur, self.storage.ping()).await?,
+            None => self.storage.ping().await,
+        }
+    }
+
+    /// Purge expired data from storage
+    async fn cleanup(&self) -> Result<u64> {
+        self.storage.cleanup().await
+    }
+
+    /// Reset the storage and remove all keys, whatever their [`key_prefix`](Config::key_prefix)
     async fn reset(&self) -> Result<()> {
         self.storage.reset().await
     }
@@ -74,6 +510,23 @@ impl fmt::Debug for Config {
         f.debug_struct("Config")
             .field("cookie", &self.cookie)
             .field("storage", &self.storage)
+            .field("ping_timeout", &self.ping_timeout)
+            .field("cleanup", &self.cleanup)
+            .field("rotate_csrf", &self.rotate_csrf)
+            .field("max_data_size", &self.max_data_size)
+            .field("ttl", &self.ttl)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("absolute_timeout", &self.absolute_timeout)
+            .field("rolling", &self.rolling)
+            .field("rolling_interval", &self.rolling_interval)
+            .field("save_uninitialized", &self.save_uninitialized)
+            .field("serializer", &self.serializer)
+            .field("hooks", &self.hooks.is_some())
+            .field("strict_hooks", &self.strict_hooks)
+            .field("key_prefix", &self.key_prefix)
+            .field("schema_version", &self.schema_version)
+            .field("migration", &self.migration.is_some())
+            .field("opaque_ids", &self.opaque_ids)
             .finish()
     }
 }
@@ -88,6 +541,25 @@ where
     fn call(&self) -> String;
 }
 
+/// A trait for telling the time
+pub trait ClockFn
+where
+    Self: Send + Sync + 'static,
+{
+    #[allow(missing_docs)]
+    #[must_use]
+    fn call(&self) -> SystemTime;
+}
+
+/// A trait for migrating the data of a schema version
+pub trait MigrationFn
+where
+    Self: Send + Sync + 'static,
+{
+    #[allow(missing_docs)]
+    fn call(&self, version: u64, data: Data) -> Result<Data>;
+}
+
 /// A trait for verifing session id
 pub trait VerifyFn
 where
@@ -107,6 +579,24 @@ where
     }
 }
 
+impl<F> ClockFn for F
+where
+    F: Send + Sync + 'static + Fn() -> SystemTime,
+{
+    fn call(&self) -> SystemTime {
+        (self)()
+    }
+}
+
+impl<F> MigrationFn for F
+where
+    F: Send + Sync + 'static + Fn(u64, Data) -> Result<Data>,
+{
+    fn call(&self, version: u64, data: Data) -> Result<Data> {
+        (self)(version, data)
+    }
+}
+
 impl<F> VerifyFn for F
 where
     F: Send + Sync + 'static + Fn(&str) -> bool,
@@ -0,0 +1,320 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{
+    signing, ClockFn, Config, ConfigError, ConfigOptions, CookieOptions, GenerateFn, Json, Key,
+    MigrationFn, OpaqueIds, Serialized, Serializer, SessionHooks, Storage, VerifyFn,
+};
+
+type ByteStorageFn = Box<dyn FnOnce(Arc<dyn Serializer>) -> Arc<dyn Storage> + Send>;
+
+/// Builds a [`Config`], see [`Config::builder`]
+///
+/// The storage and the session id generator are required, everything else
+/// has the defaults of [`ConfigOptions`]. With the `nanoid` feature, the session
+/// id generator defaults to [`generate_id`](crate::generate_id).
+#[derive(Default)]
+pub struct ConfigBuilder {
+    storage: Option<Arc<dyn Storage>>,
+    byte_storage: Option<ByteStorageFn>,
+    serializer: Option<Arc<dyn Serializer>>,
+    generate: Option<Box<dyn GenerateFn>>,
+    verify: Option<Box<dyn VerifyFn>>,
+    id_key: Option<Key>,
+    unsigned_ids: bool,
+    opaque_ids: Option<OpaqueIds>,
+    clock: Option<Box<dyn ClockFn>>,
+    hooks: Option<Arc<dyn SessionHooks>>,
+    max_age: Option<Duration>,
+    schema_version: u64,
+    migration: Option<Box<dyn MigrationFn>>,
+    options: ConfigOptions,
+}
+
+impl ConfigBuilder {
+    /// Sets the storage
+    pub fn storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage.replace(storage);
+        self.byte_storage = None;
+        self
+    }
+
+    /// Sets a byte-oriented storage, wrapped in [`Serialized`] with the
+    /// [`serializer`](ConfigBuilder::serializer)
+    pub fn byte_storage<S>(mut self, storage: S) -> Self
+    where
+        S: Storage<Vec<u8>>,
+    {
+        self.byte_storage.replace(Box::new(move |serializer| {
+            Arc::new(Serialized::with_serializer(storage, serializer))
+        }));
+        self.storage = None;
+        self
+    }
+
+    /// Sets the serializer of the [`byte_storage`](ConfigBuilder::byte_storage),
+    /// [`Json`] by default, also measuring the data against the `max_data_size`
+    pub fn serializer(mut self, serializer: impl Serializer) -> Self {
+        self.serializer.replace(Arc::new(serializer));
+        self
+    }
+
+    /// Sets the cookie options
+    pub fn cookie(mut self, cookie: CookieOptions) -> Self {
+        self.options.cookie = cookie;
+        self
+    }
+
+    /// Sets the cookie's max_age, which the storage TTL defaults to,
+    /// whatever cookie options are set
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age.replace(max_age);
+        self
+    }
+
+    /// Sets how long the storage keeps a session, see [`Config::store_ttl`]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.options.ttl.replace(ttl);
+        self
+    }
+
+    /// Sets how long a session may go without being saved or touched,
+    /// see [`Config::timeout_at`]
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.options.idle_timeout.replace(idle_timeout);
+        self
+    }
+
+    /// Sets how long a session may live after it was created, however active it is
+    pub fn absolute_timeout(mut self, absolute_timeout: Duration) -> Self {
+        self.options.absolute_timeout.replace(absolute_timeout);
+        self
+    }
+
+    /// Refreshes the expiry of unchanged sessions on every request,
+    /// see [`Session::roll`](crate::Session::roll)
+    pub fn rolling(mut self, rolling: bool) -> Self {
+        self.options.rolling = rolling;
+        self
+    }
+
+    /// Sets the minimum time between two refreshes of a rolling session
+    pub fn rolling_interval(mut self, rolling_interval: Duration) -> Self {
+        self.options.rolling_interval.replace(rolling_interval);
+        self
+    }
+
+    /// Saves sessions no key was written to, off by default
+    pub fn save_uninitialized(mut self, save_uninitialized: bool) -> Self {
+        self.options.save_uninitialized = save_uninitialized;
+        self
+    }
+
+    /// Sets the callbacks on the lifecycle of sessions
+    pub fn hooks(mut self, hooks: impl SessionHooks) -> Self {
+        self.hooks.replace(Arc::new(hooks));
+        self
+    }
+
+    /// Fails the session method with the error of a hook, which is logged by default
+    pub fn strict_hooks(mut self, strict_hooks: bool) -> Self {
+        self.options.strict_hooks = strict_hooks;
+        self
+    }
+
+    /// Prepends the prefix to the session ids to make the keys of the storage,
+    /// see [`Config::key_prefix`]
+    pub fn key_prefix(mut self, key_prefix: impl Into<String>) -> Self {
+        self.options.key_prefix.replace(key_prefix.into());
+        self
+    }
+
+    /// Sets the schema version of the data, stamped on save and checked on load
+    ///
+    /// Sessions stored under an older version are migrated when loaded, sessions
+    /// of a newer one, written by a newer release, are treated as missing.
+    pub fn schema_version(mut self, schema_version: u64) -> Self {
+        self.schema_version = schema_version;
+        self
+    }
+
+    /// Sets the migration of the data stored under an older schema version
+    ///
+    /// It gets the stored version, `0` for data written before it was versioned,
+    /// and the data, and returns the data of the current version. It runs when
+    /// a session is loaded, the migrated data is written on the next save.
+    ///
+    /// ```ignore
+    /// let config = Config::builder()
+    ///     .schema_version(2)
+    ///     .migration(|version, mut data: Data| {
+    ///         if version < 2 {
+    ///             if let Some(uid) = data.remove("uid") {
+    ///                 data.insert("user_id".into(), uid);
+    ///             }
+    ///         }
+    ///         Ok(data)
+    ///     })
+    ///     .build()?;
+    /// ```
+    pub fn migration(mut self, migration: impl MigrationFn) -> Self {
+        self.migration.replace(Box::new(migration));
+        self
+    }
+
+    /// Sets the session id generator
+    pub fn generate(mut self, generate: impl GenerateFn) -> Self {
+        self.generate.replace(Box::new(generate));
+        self
+    }
+
+    /// Sets the session id verifier, [`verify_id`](crate::verify_id) by default
+    pub fn verify(mut self, verify: impl VerifyFn) -> Self {
+        self.verify.replace(Box::new(verify));
+        self
+    }
+
+    /// Signs the generated session ids with the key, `<id>.<mac>`
+    ///
+    /// The MAC is an HMAC-SHA256 of the id, truncated to 128 bits and in unpadded
+    /// base64url, so the ids stay cookie-safe. The config only verifies ids with a
+    /// valid MAC, compared in constant time, whose id part passes the
+    /// [`verify`](ConfigBuilder::verify)er: forged ids are rejected before reaching
+    /// the storage, wherever they come from. Renewed sessions get signed ids too.
+    pub fn sign_ids(mut self, key: impl Into<Key>) -> Self {
+        self.id_key.replace(key.into());
+        self
+    }
+
+    /// Still verifies the unsigned ids passing the [`verify`](ConfigBuilder::verify)er,
+    /// issued before the ids were [signed](ConfigBuilder::sign_ids), off by default
+    pub fn accept_unsigned_ids(mut self, accept_unsigned_ids: bool) -> Self {
+        self.unsigned_ids = accept_unsigned_ids;
+        self
+    }
+
+    /// Encrypts the session ids into opaque tokens, see [`Config::opaque_ids`]
+    ///
+    /// ```ignore
+    /// let config = Config::builder()
+    ///     .generate(|| format!("eu.{}", generate_id()))
+    ///     .verify(|id: &str| id.strip_prefix("eu.").is_some_and(verify_id))
+    ///     .opaque_ids(OpaqueIds::with_keys(active, vec![previous]))
+    ///     .build()?;
+    /// ```
+    pub fn opaque_ids(mut self, opaque_ids: OpaqueIds) -> Self {
+        self.opaque_ids.replace(opaque_ids);
+        self
+    }
+
+    /// Sets the clock of per-key TTLs
+    pub fn clock(mut self, clock: impl ClockFn) -> Self {
+        self.clock.replace(Box::new(clock));
+        self
+    }
+
+    /// Sets the maximum time to wait for the storage to answer a ping
+    pub fn ping_timeout(mut self, ping_timeout: Duration) -> Self {
+        self.options.ping_timeout.replace(ping_timeout);
+        self
+    }
+
+    /// Replaces the CSRF token after it was verified
+    pub fn rotate_csrf(mut self, rotate_csrf: bool) -> Self {
+        self.options.rotate_csrf = rotate_csrf;
+        self
+    }
+
+    /// Sets the maximum size of the serialized session data in bytes
+    pub fn max_data_size(mut self, max_data_size: usize) -> Self {
+        self.options.max_data_size.replace(max_data_size);
+        self
+    }
+
+    /// Sets all plain-data options at once, e.g. loaded from a config file
+    pub fn options(mut self, options: ConfigOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds the config
+    ///
+    /// Fails with a [`ConfigError`] if the storage or the session id generator is
+    /// missing, if the config doesn't [`validate`](Config::validate), or if the
+    /// verifier rejects the generated ids.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let mut cookie = self.options.cookie;
+        if let Some(max_age) = self.max_age {
+            cookie.max_age.replace(max_age);
+        }
+        let storage = match (self.storage, self.byte_storage) {
+            (Some(storage), _) => storage,
+            (None, Some(byte_storage)) => {
+                byte_storage(self.serializer.clone().unwrap_or_else(|| Arc::new(Json)))
+            }
+            (None, None) => return Err(ConfigError::MissingStorage),
+        };
+        let mut generate: Box<dyn GenerateFn> = match self.generate {
+            Some(generate) => generate,
+            #[cfg(feature = "nanoid")]
+            None => Box::new(crate::generate_id),
+            #[cfg(not(feature = "nanoid"))]
+            None => return Err(ConfigError::MissingGenerate),
+        };
+        let mut verify = self.verify.unwrap_or_else(|| Box::new(crate::verify_id));
+        if let Some(key) = self.id_key {
+            let unsigned_ids = self.unsigned_ids;
+            let sign_key = key.clone();
+            generate = Box::new(move || signing::sign_id(&sign_key, &generate.call()));
+            verify = Box::new(move |sid: &str| match signing::unsign_id(&key, sid) {
+                Some(id) => verify.call(id),
+                None => unsigned_ids && !sid.contains('.') && verify.call(sid),
+            });
+        }
+        let config = Config {
+            cookie,
+            storage,
+            generate,
+            verify,
+            ping_timeout: self.options.ping_timeout,
+            cleanup: None,
+            rotate_csrf: self.options.rotate_csrf,
+            max_data_size: self.options.max_data_size,
+            clock: self.clock,
+            ttl: self.options.ttl,
+            idle_timeout: self.options.idle_timeout,
+            absolute_timeout: self.options.absolute_timeout,
+            rolling: self.options.rolling,
+            rolling_interval: self.options.rolling_interval,
+            save_uninitialized: self.options.save_uninitialized,
+            serializer: self.serializer,
+            hooks: self.hooks,
+            strict_hooks: self.options.strict_hooks,
+            key_prefix: self.options.key_prefix,
+            schema_version: self.schema_version,
+            migration: self.migration,
+            opaque_ids: self.opaque_ids,
+        };
+        config.check()?;
+        if !config.verify(&config.generate()) {
+            return Err(ConfigError::UnverifiedId);
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Debug for ConfigBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigBuilder")
+            .field("storage", &self.storage)
+            .field("serializer", &self.serializer)
+            .field("hooks", &self.hooks.is_some())
+            .field("id_key", &self.id_key)
+            .field("unsigned_ids", &self.unsigned_ids)
+            .field("opaque_ids", &self.opaque_ids)
+            .field("max_age", &self.max_age)
+            .field("schema_version", &self.schema_version)
+            .field("migration", &self.migration.is_some())
+            .field("options", &self.options)
+            .finish()
+    }
+}
@@ -1,24 +1,72 @@
-use std::time::Duration;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use cookie::SameSite;
+use serde::{Deserialize, Serialize};
+
+use crate::{encryption, options::secs, signing, CookieError, Key, Result, SameSite};
 
 /// Cookie's Options
-#[derive(Debug)]
+///
+/// In its serde representation, durations and times are in seconds, the keys are
+/// skipped so secrets don't end up in config files, and unknown fields are rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct CookieOptions {
     /// Cookie's name, `viz.sid` by defaults
     pub name: String,
     /// Cookie's path
     pub path: String,
     /// Cookie's maximum age, `24H` by defaults
-    pub max_age: Duration,
+    ///
+    /// A cookie without it is a browser-session cookie, dropped when the browser closes.
+    #[serde(with = "secs::option")]
+    pub max_age: Option<Duration>,
+    /// Cookie's absolute expiry, for clients ignoring `Max-Age`
+    ///
+    /// It's rendered along with `Max-Age`, which wins in clients supporting both.
+    #[serde(with = "secs::time")]
+    pub expires: Option<SystemTime>,
     /// Cookie's secure
     pub secure: Option<bool>,
     /// Cookie's domain
     pub domain: Option<String>,
-    /// Cookie's http_only
+    /// Cookie's http_only, `true` by defaults
     pub http_only: Option<bool>,
-    /// Cookie's same_site
+    /// Cookie's same_site, [`SameSite::None`] makes the cookie secure
     pub same_site: Option<SameSite>,
+    /// Cookie's partitioned, storing a third-party cookie per top-level site (CHIPS)
+    ///
+    /// It requires a secure cookie, `SameSite=None` is recommended.
+    pub partitioned: Option<bool>,
+    /// Signs the cookie value, so guessed or tampered session ids are rejected
+    /// before reaching the storage, unsigned if `None`
+    #[serde(skip)]
+    pub signing_key: Option<Key>,
+    /// The previous signing keys, still verifying cookie values during a key rotation
+    #[serde(skip)]
+    pub previous_signing_keys: Vec<Key>,
+    /// Encrypts the cookie value, so nothing about the session id leaks,
+    /// unencrypted if `None`
+    #[serde(skip)]
+    pub encryption_key: Option<Key>,
+    /// The previous encryption keys, still decrypting cookie values during a key rotation
+    #[serde(skip)]
+    pub previous_encryption_keys: Vec<Key>,
+    /// Percent-encodes the cookie value, and decodes it when reading the session id,
+    /// `true` by defaults
+    pub encode_value: bool,
+    /// Splits longer cookie values across `name.0`, `name.1`, ... cookies,
+    /// see [`to_set_cookies`](CookieOptions::to_set_cookies)
+    pub chunk_size: Option<usize>,
+}
+
+impl Default for CookieOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CookieOptions {
@@ -27,15 +75,72 @@ impl CookieOptions {
         Self {
             domain: None,
             secure: None,
-            http_only: None,
+            http_only: Some(true),
             same_site: None,
+            partitioned: None,
+            signing_key: None,
+            previous_signing_keys: Vec::new(),
+            encryption_key: None,
+            previous_encryption_keys: Vec::new(),
+            encode_value: true,
+            chunk_size: None,
             path: "/".into(),
             name: "viz.sid".into(),
-            max_age: Duration::from_secs(3600 * 24),
+            max_age: Some(Duration::from_secs(3600 * 24)),
+            expires: None,
         }
     }
 
+    /// Creates new `CookieOptions` for production, `HttpOnly`, `Secure` and `SameSite=Strict`
+    ///
+    /// The cookie isn't sent when following a link from another site, so users
+    /// arriving from one look logged out on the first page. Use it for sessions
+    /// which must not be used across sites, e.g. admin panels or banking.
+    pub fn strict() -> Self {
+        Self::new()
+            .with_secure(true)
+            .with_same_site(SameSite::Strict)
+    }
+
+    /// Creates new `CookieOptions` for production, `HttpOnly`, `Secure` and `SameSite=Lax`
+    ///
+    /// The cookie is sent when following a link from another site, but not with
+    /// cross-site subrequests or forms posted from another site. Use it for most websites.
+    pub fn lax_secure() -> Self {
+        Self::new().with_secure(true).with_same_site(SameSite::Lax)
+    }
+
+    /// Creates new `CookieOptions` for local development, `HttpOnly` and `SameSite=Lax`
+    ///
+    /// The cookie isn't `Secure` so it's sent over plain HTTP to `localhost`.
+    /// Don't use it in production.
+    pub fn development() -> Self {
+        Self::new().with_secure(false).with_same_site(SameSite::Lax)
+    }
+
+    /// Creates new `CookieOptions` whose name has the `__Host-` prefix
+    ///
+    /// The cookie is secure, on the path `/` and without a domain, as browsers
+    /// require for the prefix. It's only sent to the host which set it.
+    pub fn host_prefixed(name: &str) -> Self {
+        let mut options = Self::new().with_name(format!("__Host-{}", name));
+        options.secure.replace(true);
+        options
+    }
+
+    /// Creates new `CookieOptions` whose name has the `__Secure-` prefix
+    ///
+    /// The cookie is secure, as browsers require for the prefix.
+    pub fn secure_prefixed(name: &str) -> Self {
+        let mut options = Self::new().with_name(format!("__Secure-{}", name));
+        options.secure.replace(true);
+        options
+    }
+
     /// Creates new `CookieOptions` with `name`
+    ///
+    /// The name must be a token (RFC 7230), without spaces, `=` or `;`, which
+    /// [`validate`](CookieOptions::validate) and [`to_set_cookie`](CookieOptions::to_set_cookie) check.
     pub fn with_name(mut self, name: String) -> Self {
         self.name = name;
         self
@@ -43,7 +148,19 @@ impl CookieOptions {
 
     /// Creates new `CookieOptions` with `max_age`
     pub fn with_max_age(mut self, max_age: Duration) -> Self {
-        self.max_age = max_age;
+        self.max_age.replace(max_age);
+        self
+    }
+
+    /// Creates new `CookieOptions` without `max_age`, making a browser-session cookie
+    pub fn without_max_age(mut self) -> Self {
+        self.max_age = None;
+        self
+    }
+
+    /// Creates new `CookieOptions` with `expires`
+    pub fn with_expires(mut self, expires: SystemTime) -> Self {
+        self.expires.replace(expires);
         self
     }
 
@@ -72,8 +189,517 @@ impl CookieOptions {
     }
 
     /// Creates new `CookieOptions` with `same_site`
-    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
-        self.same_site.replace(same_site);
+    ///
+    /// With [`SameSite::None`] the cookie is secure, whatever `secure` is set to.
+    pub fn with_same_site(mut self, same_site: impl Into<SameSite>) -> Self {
+        self.same_site.replace(same_site.into());
+        self
+    }
+
+    /// Creates new `CookieOptions` with `partitioned`
+    pub fn with_partitioned(mut self, partitioned: bool) -> Self {
+        self.partitioned.replace(partitioned);
+        self
+    }
+
+    /// Creates new `CookieOptions` with `signing_key`
+    ///
+    /// The cookie value becomes `value.base64(hmac_sha256(value, key))`.
+    pub fn with_signing_key(mut self, key: impl Into<Key>) -> Self {
+        self.signing_key.replace(key.into());
+        self
+    }
+
+    /// Creates new `CookieOptions` with the `active` signing key and the `previous`
+    /// ones, tried in order to verify cookie values signed before a key rotation
+    pub fn with_keys(mut self, active: impl Into<Key>, previous: Vec<Key>) -> Self {
+        self.signing_key.replace(active.into());
+        self.previous_signing_keys = previous;
+        self
+    }
+
+    /// Creates new `CookieOptions` with `encryption_key`
+    ///
+    /// The cookie value becomes `v1.<nonce>.<ciphertext>`, encrypted with
+    /// XChaCha20-Poly1305. With a signing key too, the encrypted value is signed.
+    pub fn with_encryption_key(mut self, key: impl Into<Key>) -> Self {
+        self.encryption_key.replace(key.into());
+        self
+    }
+
+    /// Creates new `CookieOptions` with the `active` encryption key and the `previous`
+    /// ones, tried in order to decrypt cookie values encrypted before a key rotation
+    pub fn with_encryption_keys(mut self, active: impl Into<Key>, previous: Vec<Key>) -> Self {
+        self.encryption_key.replace(active.into());
+        self.previous_encryption_keys = previous;
         self
     }
+
+    /// Creates new `CookieOptions` with `encode_value`
+    ///
+    /// Without it, a value with characters not allowed in a cookie fails to render.
+    pub fn with_encode_value(mut self, encode_value: bool) -> Self {
+        self.encode_value = encode_value;
+        self
+    }
+
+    /// Creates new `CookieOptions` with `chunk_size`, e.g. below the 4096 bytes
+    /// browsers keep per cookie
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size.replace(chunk_size);
+        self
+    }
+
+    /// Creates new `CookieOptions` with `same_site` parsed from a string
+    #[deprecated(note = "use `with_same_site` with a `SameSite`")]
+    pub fn with_same_site_str(self, same_site: &str) -> Result<Self> {
+        Ok(self.with_same_site(same_site.parse::<SameSite>()?))
+    }
+
+    /// Checks the options make a cookie browsers accept
+    ///
+    /// Rendering makes a `SameSite=None` cookie secure, but it's an error
+    /// to turn `secure` off explicitly.
+    pub fn validate(&self) -> Result<(), CookieError> {
+        self.check_chars()?;
+        if self.same_site == Some(SameSite::None) && self.secure == Some(false) {
+            return Err(CookieError::InsecureSameSiteNone);
+        }
+        if self.partitioned == Some(true) && !self.is_secure() {
+            return Err(CookieError::InsecurePartitioned);
+        }
+        self.check_prefix()
+    }
+
+    /// Checks the name, domain and path only have allowed characters
+    fn check_chars(&self) -> Result<(), CookieError> {
+        if self.name.is_empty() {
+            return Err(CookieError::EmptyName);
+        }
+        if !self.name.bytes().all(is_token) {
+            return Err(CookieError::InvalidName);
+        }
+        if matches!(&self.domain, Some(domain) if !domain.bytes().all(is_domain)) {
+            return Err(CookieError::InvalidDomain);
+        }
+        if !self.path.bytes().all(is_attribute) {
+            return Err(CookieError::InvalidPath);
+        }
+        Ok(())
+    }
+
+    /// Checks a `__Host-` or `__Secure-` name has the attributes the prefix requires
+    fn check_prefix(&self) -> Result<(), CookieError> {
+        let host = self.name.starts_with("__Host-");
+        if (host || self.name.starts_with("__Secure-")) && self.secure != Some(true) {
+            return Err(CookieError::InsecurePrefix);
+        }
+        if host && self.domain.is_some() {
+            return Err(CookieError::HostPrefixDomain);
+        }
+        if host && self.path != "/" {
+            return Err(CookieError::HostPrefixPath);
+        }
+        Ok(())
+    }
+
+    /// Finds the raw value of the cookie in a `Cookie` request header
+    ///
+    /// The surrounding double quotes of a value are removed. If the cookie is sent
+    /// more than once, the first one wins, as browsers send the most specific first.
+    pub fn find_in<'a>(&self, cookie_header: &'a str) -> Option<&'a str> {
+        pairs(cookie_header).find_map(|(name, value)| (name == self.name).then_some(value))
+    }
+
+    /// Finds and joins the raw values of the `name.0`, `name.1`, ... chunks of the cookie,
+    /// `None` if one is missing
+    fn find_chunks(&self, cookie_header: &str) -> Option<String> {
+        let mut chunks = BTreeMap::new();
+        for (index, value) in
+            pairs(cookie_header).filter_map(|(name, value)| Some((self.chunk_index(name)?, value)))
+        {
+            chunks.entry(index).or_insert(value);
+        }
+        if chunks.is_empty() || chunks.keys().enumerate().any(|(i, index)| i != *index) {
+            return None;
+        }
+        Some(chunks.values().copied().collect())
+    }
+
+    /// Gets the index of a chunk of the cookie from its name
+    fn chunk_index(&self, name: &str) -> Option<usize> {
+        name.strip_prefix(self.name.as_str())?
+            .strip_prefix('.')?
+            .parse()
+            .ok()
+    }
+
+    /// Finds the session id in a `Cookie` request header
+    ///
+    /// With a signing key, the MAC of the cookie value is verified in constant time.
+    /// With an encryption key, the cookie value is decrypted.
+    /// It's `None` if either fails, as if there was no session.
+    pub fn session_id(&self, cookie_header: &str) -> Option<String> {
+        self.verify(cookie_header).map(|cookie| cookie.session_id)
+    }
+
+    /// Finds the session id in a `Cookie` request header, like [`session_id`],
+    /// telling if the cookie should be reissued
+    ///
+    /// A cookie value only valid under a previous key is accepted, but should be
+    /// reissued under the active key with [`to_set_cookie`].
+    ///
+    /// [`session_id`]: CookieOptions::session_id
+    /// [`to_set_cookie`]: CookieOptions::to_set_cookie
+    pub fn verify(&self, cookie_header: &str) -> Option<VerifiedCookie> {
+        let value = match self.find_in(cookie_header) {
+            Some(value) => Cow::Borrowed(value),
+            None if self.chunk_size.is_some() => Cow::Owned(self.find_chunks(cookie_header)?),
+            None => return None,
+        };
+        self.verify_value(&value)
+    }
+
+    /// Verifies the raw value of the cookie, like [`verify`] does once the value
+    /// is found in a `Cookie` header, e.g. with the value a cookie jar parsed
+    ///
+    /// [`verify`]: CookieOptions::verify
+    pub fn verify_value(&self, value: &str) -> Option<VerifiedCookie> {
+        let value = if self.encode_value {
+            Cow::Owned(decode(value)?)
+        } else {
+            Cow::Borrowed(value)
+        };
+        let mut value = value.as_ref();
+        let mut reissue = false;
+        if let Some(key) = &self.signing_key {
+            let (unsigned, previous) = try_keys(key, &self.previous_signing_keys, |key| {
+                signing::unsign(key, value)
+            })?;
+            value = unsigned;
+            reissue |= previous;
+        }
+        let session_id = match &self.encryption_key {
+            Some(key) => {
+                let (decrypted, previous) = try_keys(key, &self.previous_encryption_keys, |key| {
+                    encryption::decrypt(key, &self.name, value)
+                })?;
+                reissue |= previous;
+                decrypted
+            }
+            None => value.into(),
+        };
+        Some(VerifiedCookie {
+            session_id,
+            reissue,
+        })
+    }
+
+    /// Renders a `Set-Cookie` header value of the cookie with the `value`
+    ///
+    /// The value is encrypted and signed with the keys if there are ones, then
+    /// percent-encoded if it has characters not allowed in a cookie.
+    /// Fails with a [`CookieError`] if the name, domain or path have characters
+    /// not allowed in a cookie, the value too if it isn't encoded, or if the name
+    /// has a `__Host-` or `__Secure-` prefix whose requirements aren't met,
+    /// browsers would reject the cookie.
+    pub fn to_set_cookie(&self, value: &str) -> Result<String, CookieError> {
+        let value = self.seal(value)?;
+        Ok(self.render(&self.name, &value, self.max_age, self.expires))
+    }
+
+    /// Renders the `Set-Cookie` header values of the cookie with the `value`,
+    /// like [`to_set_cookie`], split across `name.0`, `name.1`, ... cookies if
+    /// the value is longer than the [`chunk_size`]
+    ///
+    /// The value is signed and encrypted as a whole, then split. The cookies of
+    /// the `Cookie` request header which aren't replaced are removed, e.g. the last
+    /// chunks of a value which shrank.
+    ///
+    /// [`to_set_cookie`]: CookieOptions::to_set_cookie
+    /// [`chunk_size`]: CookieOptions::chunk_size
+    pub fn to_set_cookies(
+        &self,
+        value: &str,
+        cookie_header: &str,
+    ) -> Result<Vec<String>, CookieError> {
+        let value = self.seal(value)?;
+        let chunks = match self.chunk_size {
+            Some(size) if value.len() > size => value
+                .as_bytes()
+                .chunks(size.max(1))
+                .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut cookies = Vec::new();
+        if chunks.is_empty() {
+            cookies.push(self.render(&self.name, &value, self.max_age, self.expires));
+        } else {
+            for (index, chunk) in chunks.iter().enumerate() {
+                let name = format!("{}.{}", self.name, index);
+                cookies.push(self.render(&name, chunk, self.max_age, self.expires));
+            }
+        }
+        let stale = pairs(cookie_header)
+            .map(|(name, _)| name)
+            .filter(|name| match self.chunk_index(name) {
+                Some(index) => index >= chunks.len(),
+                None => *name == self.name && !chunks.is_empty(),
+            })
+            .collect::<BTreeSet<_>>();
+        cookies.extend(stale.into_iter().map(|name| self.render_removal(name)));
+        Ok(cookies)
+    }
+
+    /// Checks the cookie, then encrypts, signs and encodes the value
+    fn seal(&self, value: &str) -> Result<String, CookieError> {
+        self.check_chars()?;
+        self.check_prefix()?;
+        let mut value = match &self.encryption_key {
+            Some(key) => encryption::encrypt(key, &self.name, value)?,
+            None => value.into(),
+        };
+        if let Some(key) = &self.signing_key {
+            value = signing::sign(key, &value);
+        }
+        if self.encode_value {
+            value = encode(&value);
+        } else if !value.bytes().all(is_value) {
+            return Err(CookieError::InvalidValue);
+        }
+        Ok(value)
+    }
+
+    /// Renders a `Set-Cookie` header value which makes the browser drop the cookie
+    ///
+    /// It has the name, domain, path and partition of the live cookie, which
+    /// browsers match to find the cookie to drop.
+    pub fn to_removal_cookie(&self) -> String {
+        self.render_removal(&self.name)
+    }
+
+    fn render_removal(&self, name: &str) -> String {
+        self.render(name, "", Some(Duration::from_secs(0)), Some(UNIX_EPOCH))
+    }
+
+    fn render(
+        &self,
+        name: &str,
+        value: &str,
+        max_age: Option<Duration>,
+        expires: Option<SystemTime>,
+    ) -> String {
+        let mut cookie = format!("{}={}", name, value);
+        if let Some(max_age) = max_age {
+            cookie.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+        if let Some(expires) = expires {
+            cookie.push_str("; Expires=");
+            cookie.push_str(&to_http_date(expires));
+        }
+        if let Some(domain) = &self.domain {
+            cookie.push_str("; Domain=");
+            cookie.push_str(domain);
+        }
+        cookie.push_str("; Path=");
+        cookie.push_str(&self.path);
+        if self.is_secure() {
+            cookie.push_str("; Secure");
+        }
+        if self.http_only == Some(true) {
+            cookie.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            cookie.push_str("; SameSite=");
+            cookie.push_str(same_site.as_str());
+        }
+        if self.partitioned == Some(true) {
+            cookie.push_str("; Partitioned");
+        }
+        cookie
+    }
+
+    /// Checks the cookie is rendered `Secure`
+    fn is_secure(&self) -> bool {
+        self.secure == Some(true) || self.same_site == Some(SameSite::None)
+    }
+}
+
+#[cfg(feature = "cookie")]
+impl CookieOptions {
+    /// Converts to a cookie of the cookie crate with the `value`
+    ///
+    /// The value is used as is: it isn't encrypted, signed or checked like
+    /// [`to_set_cookie`](CookieOptions::to_set_cookie) does, the cookie crate's jars can do that.
+    pub fn to_cookie(&self, value: &str) -> cookie::Cookie<'static> {
+        let mut cookie = cookie::Cookie::new(self.name.clone(), value.to_string());
+        cookie.set_path(self.path.clone());
+        if let Some(domain) = &self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        if let Some(max_age) = self.max_age {
+            cookie.set_max_age(time::Duration::seconds(max_age.as_secs() as i64));
+        }
+        if let Some(expires) = self.expires {
+            cookie.set_expires(time::OffsetDateTime::from(expires));
+        }
+        cookie.set_secure(if self.is_secure() {
+            Some(true)
+        } else {
+            self.secure
+        });
+        cookie.set_http_only(self.http_only);
+        cookie.set_same_site(self.same_site.map(Into::into));
+        cookie.set_partitioned(self.partitioned);
+        cookie
+    }
+
+    /// Converts to a cookie of the cookie crate with the `value` sealed like
+    /// [`to_set_cookie`](CookieOptions::to_set_cookie) does
+    pub fn to_sealed_cookie(&self, value: &str) -> Result<cookie::Cookie<'static>, CookieError> {
+        Ok(self.to_cookie(&self.seal(value)?))
+    }
+
+    /// Converts from a cookie of the cookie crate, its value is ignored
+    ///
+    /// The path is `/` if the cookie has none.
+    pub fn from_cookie(cookie: &cookie::Cookie<'_>) -> Self {
+        Self {
+            name: cookie.name().into(),
+            path: cookie.path().unwrap_or("/").into(),
+            max_age: cookie
+                .max_age()
+                .map(|max_age| Duration::from_secs(max_age.whole_seconds().max(0) as u64)),
+            expires: cookie.expires_datetime().map(SystemTime::from),
+            secure: cookie.secure(),
+            domain: cookie.domain().map(Into::into),
+            http_only: cookie.http_only(),
+            same_site: cookie.same_site().map(Into::into),
+            partitioned: cookie.partitioned(),
+            ..Self::new()
+        }
+    }
+}
+
+/// Iterates over the names and values of a `Cookie` request header,
+/// the surrounding double quotes of the values are removed
+fn pairs(cookie_header: &str) -> impl Iterator<Item = (&str, &str)> {
+    cookie_header.split(';').filter_map(|pair| {
+        let (name, value) = pair.split_at(pair.find('=')?);
+        let value = value[1..].trim();
+        Some((
+            name.trim(),
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value),
+        ))
+    })
+}
+
+/// A session id read from a verified cookie
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedCookie {
+    /// The session id
+    pub session_id: String,
+    /// The cookie was only valid under a previous key, it should be reissued
+    pub reissue: bool,
+}
+
+/// Tries the active key, then the previous ones in order, tells if a previous one succeeded
+fn try_keys<T, F>(active: &Key, previous: &[Key], f: F) -> Option<(T, bool)>
+where
+    F: Fn(&Key) -> Option<T>,
+{
+    f(active)
+        .map(|t| (t, false))
+        .or_else(|| previous.iter().find_map(&f).map(|t| (t, true)))
+}
+
+/// Checks the byte is allowed in a token (RFC 7230), e.g. a cookie name
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Checks the byte is allowed in a cookie attribute value (RFC 6265)
+fn is_attribute(b: u8) -> bool {
+    !b.is_ascii_control() && b != b';'
+}
+
+/// Checks the byte is allowed in a domain
+fn is_domain(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'.'
+}
+
+/// Checks the byte is allowed in a cookie value (RFC 6265)
+pub(crate) fn is_value(b: u8) -> bool {
+    matches!(b, b'!' | b'#'..=b'+' | b'-'..=b':' | b'<'..=b'[' | b']'..=b'~')
+}
+
+/// Percent-encodes the bytes not allowed in a cookie value (RFC 6265), and `%`
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if is_value(b) && b != b'%' {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Decodes a percent-encoded value, `None` if it's malformed
+fn decode(value: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Formats the time as an HTTP date (RFC 1123), e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+///
+/// Times before the unix epoch are formatted as the epoch.
+pub fn to_http_date(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let days = secs / 86400;
+    let secs = secs % 86400;
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
 }
@@ -0,0 +1,23 @@
+use std::fmt::Write;
+
+use crate::{anyhow, Result};
+
+/// Length of the random part of a token, in bytes
+const TOKEN_LEN: usize = 32;
+
+/// Generates a random token, hex encoded
+pub(crate) fn token() -> Result<String> {
+    let mut buf = [0u8; TOKEN_LEN];
+    getrandom::getrandom(&mut buf).map_err(|e| anyhow!(e.to_string()))?;
+    Ok(buf
+        .iter()
+        .fold(String::with_capacity(TOKEN_LEN * 2), |mut s, b| {
+            let _ = write!(s, "{:02x}", b);
+            s
+        }))
+}
+
+/// Compares in a time which only depends on the length of the inputs
+pub(crate) fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,72 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+
+use crate::{CookieError, Key};
+
+/// The version of the encrypted format
+const VERSION: &str = "v1";
+
+/// The length of the XChaCha20 nonces
+const NONCE_LEN: usize = 24;
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(s: &str) -> Option<Vec<u8>> {
+    base64::decode_config(s, base64::URL_SAFE_NO_PAD).ok()
+}
+
+/// Encrypts the message under a random nonce, `<nonce><ciphertext>`
+///
+/// The associated data is authenticated along with the message.
+pub(crate) fn seal(key: &Key, msg: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut nonce).ok()?;
+    let ciphertext = XChaCha20Poly1305::new(key.as_bytes().into())
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg, aad })
+        .ok()?;
+    Some([&nonce[..], &ciphertext].concat())
+}
+
+/// Decrypts a message sealed with the associated data, `None` if it fails
+pub(crate) fn open(key: &Key, sealed: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, msg) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key.as_bytes().into())
+        .decrypt(XNonce::from_slice(nonce), Payload { msg, aad })
+        .ok()
+}
+
+/// Encrypts the value of the named cookie, `v1.<nonce>.<ciphertext>`
+///
+/// The name is authenticated too, so the value can't be moved to another cookie.
+pub(crate) fn encrypt(key: &Key, name: &str, value: &str) -> Result<String, CookieError> {
+    let sealed = seal(key, value.as_bytes(), name.as_bytes()).ok_or(CookieError::Encryption)?;
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    Ok(format!(
+        "{}.{}.{}",
+        VERSION,
+        encode(nonce),
+        encode(ciphertext)
+    ))
+}
+
+/// Decrypts the value of the named cookie, `None` if it fails
+pub(crate) fn decrypt(key: &Key, name: &str, encrypted: &str) -> Option<String> {
+    let mut parts = encrypted.splitn(3, '.');
+    if parts.next()? != VERSION {
+        return None;
+    }
+    let nonce = decode(parts.next()?)?;
+    let ciphertext = decode(parts.next()?)?;
+    if nonce.len() != NONCE_LEN {
+        return None;
+    }
+    let value = open(key, &[nonce, ciphertext].concat(), name.as_bytes())?;
+    String::from_utf8(value).ok()
+}
@@ -0,0 +1,105 @@
+use std::sync::RwLockWriteGuard;
+
+use crate::{
+    changes::Changes,
+    data::{from_value, to_value, DeserializeOwned, Serialize, Value},
+    Result, SessionBeer,
+};
+
+/// A value of the session data, modified in place under the session's write lock
+///
+/// The session is only marked as changed when the value actually changes.
+/// The lock is held until the `Entry` is dropped.
+#[derive(Debug)]
+pub struct Entry<'a> {
+    beer: RwLockWriteGuard<'a, SessionBeer>,
+    changes: &'a Changes,
+    key: String,
+}
+
+impl<'a> Entry<'a> {
+    pub(crate) fn new(
+        beer: RwLockWriteGuard<'a, SessionBeer>,
+        changes: &'a Changes,
+        key: &str,
+    ) -> Self {
+        Self {
+            beer,
+            changes,
+            key: key.into(),
+        }
+    }
+
+    /// Gets the key of the entry
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Gets the current value
+    pub fn get(&self) -> Option<&Value> {
+        self.beer.data.get(&self.key)
+    }
+
+    /// Sets the value
+    pub fn insert<T: Serialize>(mut self, val: T) -> Result<Self> {
+        self.replace(to_value(val)?);
+        Ok(self)
+    }
+
+    /// Inserts the `default` if the value is missing
+    pub fn or_insert<T: Serialize>(self, default: T) -> Result<Self> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the value computed by `f` if the value is missing
+    pub fn or_insert_with<T, F>(self, f: F) -> Result<Self>
+    where
+        T: Serialize,
+        F: FnOnce() -> T,
+    {
+        if self.get().is_some() {
+            Ok(self)
+        } else {
+            self.insert(f())
+        }
+    }
+
+    /// Inserts the default of `T` if the value is missing
+    pub fn or_default<T: Default + Serialize>(self) -> Result<Self> {
+        self.or_insert_with(T::default)
+    }
+
+    /// Modifies the value in place if it exists
+    pub fn and_modify<F: FnOnce(&mut Value)>(mut self, f: F) -> Self {
+        let key = self.key.clone();
+        if let Some(val) = self.beer.data.get_mut(&key) {
+            let prev = val.clone();
+            f(val);
+            if *val != prev {
+                self.changes.mark(&self.key);
+            }
+        }
+        self
+    }
+
+    /// Modifies the value as a `T` if it exists
+    pub fn and_modify_as<T, F>(mut self, f: F) -> Result<Self>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce(&mut T),
+    {
+        if let Some(val) = self.get() {
+            let mut typed = from_value(val.clone())?;
+            f(&mut typed);
+            self.replace(to_value(typed)?);
+        }
+        Ok(self)
+    }
+
+    fn replace(&mut self, val: Value) {
+        if self.get() != Some(&val) {
+            self.beer.data.insert(self.key.clone(), val);
+            self.changes.mark(&self.key);
+        }
+    }
+}
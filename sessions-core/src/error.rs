@@ -0,0 +1,198 @@
+use std::{error::Error, fmt};
+
+/// The stored session was changed since it was loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    /// The version the session was loaded with
+    pub expected: u64,
+    /// The version currently in the storage
+    pub found: u64,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "session version conflict, expected {} but found {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl Error for Conflict {}
+
+/// The cookie options make a cookie browsers would reject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieError {
+    /// The name is empty
+    EmptyName,
+    /// The name has characters not allowed in a cookie name
+    InvalidName,
+    /// The value, or the generated session ids, have characters not allowed
+    /// in a cookie value
+    InvalidValue,
+    /// The domain has characters not allowed in an attribute
+    InvalidDomain,
+    /// The path has characters not allowed in an attribute
+    InvalidPath,
+    /// `SameSite=None` with `Secure` turned off
+    InsecureSameSiteNone,
+    /// A partitioned cookie without `Secure`
+    InsecurePartitioned,
+    /// A `__Secure-` or `__Host-` name without `Secure`
+    InsecurePrefix,
+    /// A `__Host-` name with a domain
+    HostPrefixDomain,
+    /// A `__Host-` name with a path other than `/`
+    HostPrefixPath,
+    /// The cookie value couldn't be encrypted
+    Encryption,
+}
+
+impl fmt::Display for CookieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::EmptyName => "cookie name is empty",
+            Self::InvalidName => "cookie name has invalid characters",
+            Self::InvalidValue => "cookie value has invalid characters",
+            Self::InvalidDomain => "cookie domain has invalid characters",
+            Self::InvalidPath => "cookie path has invalid characters",
+            Self::InsecureSameSiteNone => "cookie with `SameSite=None` must be secure",
+            Self::InsecurePartitioned => "partitioned cookie must be secure",
+            Self::InsecurePrefix => "cookie with a `__Secure-` or `__Host-` name must be secure",
+            Self::HostPrefixDomain => "cookie with a `__Host-` name can't have a domain",
+            Self::HostPrefixPath => "cookie with a `__Host-` name must have the path `/`",
+            Self::Encryption => "cookie value can't be encrypted",
+        })
+    }
+}
+
+impl Error for CookieError {}
+
+/// A [`Config`](crate::Config) couldn't be built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// No storage was set
+    MissingStorage,
+    /// No session id generator was set, and the `nanoid` feature is off
+    MissingGenerate,
+    /// The session id verifier rejects the generated ids
+    UnverifiedId,
+    /// The cookie options or the generated session ids are invalid
+    Cookie(CookieError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingStorage => f.write_str("config has no storage"),
+            Self::MissingGenerate => f.write_str("config has no session id generator"),
+            Self::UnverifiedId => f.write_str("config rejects its generated session ids"),
+            Self::Cookie(err) => write!(f, "config is invalid: {}", err),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Cookie(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CookieError> for ConfigError {
+    fn from(err: CookieError) -> Self {
+        Self::Cookie(err)
+    }
+}
+
+/// The session data is larger than [`Config::max_data_size`](crate::Config::max_data_size)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataTooLarge {
+    /// The approximate size of the data, in bytes
+    pub size: usize,
+    /// The limit, in bytes
+    pub limit: usize,
+}
+
+impl fmt::Display for DataTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "session data of {} bytes exceeds the limit of {} bytes",
+            self.size, self.limit
+        )
+    }
+}
+
+impl Error for DataTooLarge {}
+
+/// The storage doesn't support the operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsupported {
+    /// The unsupported operation
+    pub operation: &'static str,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "this storage doesn't support {}", self.operation)
+    }
+}
+
+impl Error for Unsupported {}
+
+/// A session value couldn't be read or written
+///
+/// There is no lock variant: the session recovers its locks after a panic.
+#[derive(Debug)]
+pub enum DataError {
+    /// The value couldn't be serialized
+    Serialize {
+        /// The key of the value
+        key: String,
+        /// The serialization error
+        source: serde_json::Error,
+    },
+    /// The stored value couldn't be deserialized as the requested type
+    Deserialize {
+        /// The key of the value
+        key: String,
+        /// The deserialization error
+        source: serde_json::Error,
+    },
+    /// The session is destroyed, its data can't be written
+    Destroyed,
+    /// The whole state wasn't serialized to an object
+    NotAnObject,
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serialize { key, source } => {
+                write!(f, "session value `{}` can't be serialized: {}", key, source)
+            }
+            Self::Deserialize { key, source } => {
+                write!(
+                    f,
+                    "session value `{}` can't be deserialized: {}",
+                    key, source
+                )
+            }
+            Self::Destroyed => f.write_str("session is destroyed"),
+            Self::NotAnObject => f.write_str("session state must be serialized to an object"),
+        }
+    }
+}
+
+impl Error for DataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Serialize { source, .. } | Self::Deserialize { source, .. } => Some(source),
+            Self::Destroyed | Self::NotAnObject => None,
+        }
+    }
+}
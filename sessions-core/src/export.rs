@@ -0,0 +1,28 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Data, SessionStatus};
+
+/// The state of a session in a serializable form, see [`Session::export`]
+///
+/// Meant for "export my data" endpoints and debugging tools, and for moving
+/// a session to another store with [`Session::import`].
+///
+/// [`Session::export`]: crate::Session::export
+/// [`Session::import`]: crate::Session::import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionExport {
+    /// The session id
+    pub id: String,
+    /// The session status when it was exported
+    pub status: SessionStatus,
+    /// The version of the session data, `0` if it was never saved
+    pub version: u64,
+    /// When the session was first saved, `None` if unknown
+    pub created_at: Option<SystemTime>,
+    /// When the session was last saved, `None` if unknown
+    pub last_accessed: Option<SystemTime>,
+    /// The data, without the keys written on save
+    pub data: Data,
+}
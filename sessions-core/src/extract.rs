@@ -0,0 +1,86 @@
+use std::fmt;
+
+use ::axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::{
+    data::{DeserializeOwned, Serialize},
+    Session, TypedSession,
+};
+
+/// What a rejected session extractor answers, `500 Internal Server Error` by default
+///
+/// Added to the router as an `Extension`, e.g. to redirect to a page explaining
+/// the app is misconfigured.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum OnRejection {
+    /// Answers `500 Internal Server Error` with the rejection message
+    #[default]
+    InternalError,
+    /// Redirects to the uri with `303 See Other`
+    Redirect(String),
+}
+
+/// Rejects the [`Session`] extractors of a request the session layer didn't load
+/// a session into, answering as the [`OnRejection`] extension says
+#[derive(Debug)]
+pub struct SessionRejection {
+    on_rejection: OnRejection,
+}
+
+impl fmt::Display for SessionRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("no session in the request, the `SessionLayer` isn't installed on this route")
+    }
+}
+
+impl std::error::Error for SessionRejection {}
+
+impl IntoResponse for SessionRejection {
+    fn into_response(self) -> Response {
+        match &self.on_rejection {
+            OnRejection::InternalError => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+            }
+            OnRejection::Redirect(uri) => Redirect::to(uri).into_response(),
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = SessionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Session>()
+            .cloned()
+            .ok_or_else(|| SessionRejection {
+                on_rejection: parts
+                    .extensions
+                    .get::<OnRejection>()
+                    .cloned()
+                    .unwrap_or_default(),
+            })
+    }
+}
+
+impl<S, T> FromRequestParts<S> for TypedSession<T>
+where
+    S: Send + Sync,
+    T: Serialize + DeserializeOwned + Default + Send,
+{
+    type Rejection = SessionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Session::from_request_parts(parts, state)
+            .await
+            .map(TypedSession::new)
+    }
+}
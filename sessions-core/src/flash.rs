@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// The level of a flash message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Informational
+    Info,
+    /// An action succeeded
+    Success,
+    /// Something needs attention
+    Warning,
+    /// An action failed
+    Error,
+}
+
+/// A message shown once on the next request, see [`Session::flash`]
+///
+/// [`Session::flash`]: crate::Session::flash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Flash<T = String> {
+    /// The level of the message
+    pub level: Level,
+    /// The message
+    pub message: T,
+}
@@ -0,0 +1,48 @@
+use std::ops::Deref;
+
+use crate::{Result, Session};
+
+/// Holds the session of a request, saving it once the response is produced
+///
+/// Integrations create the guard when the session is loaded and call [`finish`]
+/// after the handler ran, so a changed session is saved without the handler
+/// calling [`Session::save`].
+///
+/// [`finish`]: SessionGuard::finish
+#[derive(Debug)]
+pub struct SessionGuard {
+    session: Session,
+}
+
+impl SessionGuard {
+    /// Creates new `SessionGuard` over the session
+    pub fn new(session: Session) -> Self {
+        Self { session }
+    }
+
+    /// Gets the session
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Unwraps the session without saving it
+    pub fn into_inner(self) -> Session {
+        self.session
+    }
+
+    /// Saves the session if it changed, then rolls it if it wasn't saved,
+    /// see [`Session::save`] and [`Session::roll`]
+    pub async fn finish(self) -> Result<()> {
+        self.session.save().await?;
+        self.session.roll().await?;
+        Ok(())
+    }
+}
+
+impl Deref for SessionGuard {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
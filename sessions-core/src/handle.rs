@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use ::http::HeaderMap;
+
+use crate::{request_session_id, Config, Result, Session, SessionStatus, Storage};
+
+/// A session loaded at the HTTP upgrade of a WebSocket, for the life of the socket
+///
+/// It's cheap to clone into the tasks of the socket, the clones share the session.
+/// A socket outlives the request, so the session is [revalidated](SessionHandle::revalidate)
+/// against the storage to notice it was destroyed elsewhere, e.g. by a logout.
+#[derive(Clone, Debug)]
+pub struct SessionHandle {
+    session: Session,
+}
+
+impl SessionHandle {
+    /// Loads the stored session of the `Cookie` headers of the upgrade request
+    ///
+    /// It's `None` without a verified id or a stored session: unlike
+    /// [`Config::load_session`], no session is created, the socket isn't authenticated.
+    pub async fn load(headers: &HeaderMap, config: &Arc<Config>) -> Result<Option<Self>> {
+        let sid = match request_session_id(headers, config) {
+            Some(sid) => sid,
+            None => return Ok(None),
+        };
+        Ok(match config.get(&sid).await? {
+            Some(data) => {
+                let session = Session::new(&sid, SessionStatus::Unchanged, config.clone());
+                session.load_data(data)?;
+                Some(Self { session })
+            }
+            None => None,
+        })
+    }
+
+    /// Gets the session
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Re-fetches the session from the storage, `false` once it's gone
+    ///
+    /// The data is replaced by the stored one, the changes not saved yet are lost.
+    /// A session destroyed elsewhere, or timed out, is marked destroyed here too,
+    /// so it can't be written anymore: the socket should be closed.
+    pub async fn revalidate(&self) -> Result<bool> {
+        if self.session.is_destroyed() {
+            return Ok(false);
+        }
+        match self.session.config().get(&self.session.id()?).await? {
+            Some(data) => {
+                self.session.load_data(data)?;
+                Ok(true)
+            }
+            None => {
+                self.session.set_status(SessionStatus::Destroyed);
+                Ok(false)
+            }
+        }
+    }
+}
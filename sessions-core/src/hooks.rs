@@ -0,0 +1,35 @@
+use crate::{async_trait, Result};
+
+/// Callbacks on the lifecycle of sessions, e.g. for audit logs or metrics,
+/// see [`ConfigBuilder::hooks`](crate::ConfigBuilder::hooks)
+///
+/// Each hook runs after the store operation succeeded. Its error is logged, or
+/// fails the session method with [`Config::strict_hooks`](crate::Config::strict_hooks).
+#[async_trait]
+pub trait SessionHooks: Send + Sync + 'static {
+    /// Called when a session is saved for the first time
+    async fn on_create(&self, _sid: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when an existing session is saved
+    async fn on_save(&self, _sid: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a session is destroyed
+    async fn on_destroy(&self, _sid: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a session id is renewed
+    async fn on_renew(&self, _old_sid: &str, _new_sid: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a read finds the session [timed out](crate::Config::is_timed_out),
+    /// once as the session is removed
+    async fn on_expire(&self, _sid: &str) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+/// The length of the session ids of [`generate_id`]
+#[cfg(feature = "nanoid")]
+pub const ID_LENGTH: usize = 32;
+
+/// The minimum length of the session ids passing [`verify_id`]
+pub const MIN_ID_LENGTH: usize = 16;
+
+/// The maximum length of the session ids passing [`verify_id`]
+pub const MAX_ID_LENGTH: usize = 128;
+
+/// Generates a session id of [`ID_LENGTH`] characters from a CSPRNG
+///
+/// The characters are `A-Za-z0-9_-`, URL- and cookie-safe, so an id has 192 bits
+/// of entropy.
+#[cfg(feature = "nanoid")]
+pub fn generate_id() -> String {
+    nanoid::nanoid!(ID_LENGTH)
+}
+
+/// Checks the structure of a session id, the default session id verifier
+///
+/// The id must have between [`MIN_ID_LENGTH`] and [`MAX_ID_LENGTH`] characters
+/// in `A-Za-z0-9_-`, so ids like `../` or with control characters never reach
+/// the storage. Signed cookies are checked before, by
+/// [`CookieOptions::verify`](crate::CookieOptions::verify).
+pub fn verify_id(sid: &str) -> bool {
+    (MIN_ID_LENGTH..=MAX_ID_LENGTH).contains(&sid.len())
+        && sid
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+#[cfg(feature = "http")]
+use std::future::Future;
+
+#[cfg(feature = "http")]
+use ::http::{
+    header::{COOKIE, SET_COOKIE},
+    HeaderMap, HeaderValue, Request, Response,
+};
+
+use crate::{Config, Result, Session, SessionGuard};
+
+/// Joins the values of the `Cookie` headers of a request into one, HTTP/2
+/// clients may send a header per cookie
+pub fn join_cookie_headers<'a>(values: impl IntoIterator<Item = &'a str>) -> String {
+    values.into_iter().collect::<Vec<_>>().join("; ")
+}
+
+/// Loads the session of the cookie in the `Cookie` header of a request, or
+/// creates a new one, see [`Config::load_session`]
+///
+/// The session remembers the header, see [`Session::set_request_cookies`]. A
+/// loaded session whose cookie was only valid under a previous key gets it
+/// [reissued](Session::reissue_cookie) under the active one.
+pub async fn load_request_session(config: &Arc<Config>, cookie_header: &str) -> Result<Session> {
+    let (sid, reissue) = config
+        .cookie()
+        .verify(cookie_header)
+        .map(|cookie| (cookie.session_id, cookie.reissue))
+        .unwrap_or_default();
+    let session = config.load_session(&sid).await?;
+    if reissue && !session.is_new() {
+        session.reissue_cookie();
+    }
+    session.set_request_cookies(cookie_header);
+    Ok(session)
+}
+
+/// Loads the session of the cookies a cookie jar parsed, as names and values,
+/// like [`load_request_session`], for the integrations going through the cookie
+/// jars of frameworks
+///
+/// The session cookie may be split in chunks, see [`CookieOptions::chunk_size`],
+/// the cookies of other names are ignored.
+///
+/// [`CookieOptions::chunk_size`]: crate::CookieOptions::chunk_size
+pub async fn load_cookie_session<'a, I>(config: &Arc<Config>, cookies: I) -> Result<Session>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let name = &config.cookie().name;
+    let cookie_header = cookies
+        .into_iter()
+        .filter(|(cookie, _)| {
+            cookie
+                .strip_prefix(name.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .map(|(cookie, value)| format!("{}={}", cookie, value))
+        .collect::<Vec<_>>()
+        .join("; ");
+    load_request_session(config, &cookie_header).await
+}
+
+/// Finishes the session once the handler responded, with a [`SessionGuard`],
+/// then renders the `Set-Cookie` values of its status, see [`Session::set_cookies`]
+///
+/// A changed session is saved, an unchanged one [rolled](Session::roll).
+pub async fn finish_session(session: &Session) -> Result<Vec<String>> {
+    SessionGuard::new(session.clone()).finish().await?;
+    session.set_cookies()
+}
+
+/// Finishes the session like [`finish_session`], getting the cookies of its status
+/// for the cookie jars of frameworks
+///
+/// They are the chunks of the session cookie and the removal of the stale ones
+/// when it's split, see [`Session::set_cookies`].
+#[cfg(feature = "cookie")]
+pub async fn finish_session_cookies(session: &Session) -> Result<Vec<cookie::Cookie<'static>>> {
+    finish_session(session)
+        .await?
+        .into_iter()
+        .map(|set_cookie| Ok(cookie::Cookie::parse(set_cookie)?))
+        .collect()
+}
+
+/// Finds the session id in the `Cookie` headers of a request, verified by the
+/// [`CookieOptions`](crate::CookieOptions) of the config
+#[cfg(feature = "http")]
+pub fn request_session_id(headers: &HeaderMap, config: &Config) -> Option<String> {
+    config.cookie().session_id(&cookie_header(headers))
+}
+
+/// Loads the session of the request's cookie, or creates a new one,
+/// see [`load_request_session`]
+///
+/// The cookie is read right away, so the future doesn't borrow the request.
+#[cfg(feature = "http")]
+pub fn extract_session<B>(
+    req: &Request<B>,
+    config: &Arc<Config>,
+) -> impl Future<Output = Result<Session>> + Send + 'static {
+    let cookies = cookie_header(req.headers());
+    let config = config.clone();
+    async move { load_request_session(&config, &cookies).await }
+}
+
+/// Saves the session if it changed, rolls it otherwise, then appends the
+/// `Set-Cookie` values of its status to the response, see [`finish_session`]
+///
+/// The response gets the cookie once the session was saved, renewed, rolled or
+/// [reissued](Session::reissue_cookie), the removal cookie once it was destroyed,
+/// nothing if it's unchanged. A [fresh](Session::is_fresh) session is only saved
+/// with [`Config::save_uninitialized`]. The session brings its config.
+#[cfg(feature = "http")]
+pub async fn apply_session<B>(res: &mut Response<B>, session: &Session) -> Result<()> {
+    for cookie in finish_session(session).await? {
+        res.headers_mut()
+            .append(SET_COOKIE, HeaderValue::from_str(&cookie)?);
+    }
+    Ok(())
+}
+
+/// Joins the `Cookie` headers
+#[cfg(feature = "http")]
+fn cookie_header(headers: &HeaderMap) -> String {
+    join_cookie_headers(
+        headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|val| val.to_str().ok()),
+    )
+}
@@ -0,0 +1,35 @@
+use std::fmt;
+
+use crate::{anyhow, Result};
+
+/// A secret key signing or encrypting the session cookie
+///
+/// Its `Debug` output doesn't show the key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    /// Generates a random key
+    pub fn generate() -> Result<Self> {
+        let mut key = [0u8; 32];
+        getrandom::getrandom(&mut key).map_err(|e| anyhow!(e.to_string()))?;
+        Ok(Self(key))
+    }
+
+    /// Gets the bytes of the key
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for Key {
+    fn from(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
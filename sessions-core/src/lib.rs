@@ -1,26 +1,170 @@
 //! Sessions Core
 
-#![forbid(unsafe_code, rust_2018_idioms)]
-#![deny(missing_debug_implementations, nonstandard_style)]
+#![forbid(unsafe_code)]
+#![deny(missing_debug_implementations, nonstandard_style, rust_2018_idioms)]
 #![warn(missing_docs, missing_doc_code_examples, unreachable_pub)]
 
+#[cfg(feature = "actix")]
+mod actix;
+pub mod binary;
+mod blocking;
+mod changes;
+mod cleanup;
 mod config;
+mod config_builder;
 mod cookie_options;
+mod csrf;
+mod encryption;
+mod entry;
+mod error;
+mod export;
+#[cfg(feature = "axum")]
+mod extract;
+mod flash;
+mod guard;
+#[cfg(feature = "http")]
+mod handle;
+mod hooks;
+mod id;
+mod integration;
+mod key;
+pub mod local;
+mod lock;
+mod metadata;
+mod namespace;
+#[cfg(feature = "ntex")]
+mod ntex;
+mod opaque_id;
+mod options;
+mod payload;
+#[cfg(feature = "poem")]
+mod poem;
+pub mod record;
+#[cfg(feature = "rocket")]
+mod rocket;
+mod same_site;
+mod serialized;
+mod serializer;
 mod session;
+mod session_core;
+mod session_data;
+mod signing;
+mod snapshot;
+mod status;
 mod storage;
+mod timeout;
+mod ttl;
+mod typed;
+mod values;
+#[cfg(feature = "viz")]
+mod viz;
 
 pub use anyhow::{anyhow, Error, Result};
 pub use async_trait::async_trait;
-pub use config::{Config, GenerateFn, VerifyFn};
-pub use cookie_options::CookieOptions;
-pub use session::Session;
+pub use blocking::{BlockingSession, BlockingStore};
+pub use cleanup::{spawn_cleanup, CleanupHandle};
+pub use config::{ClockFn, Config, GenerateFn, MigrationFn, VerifyFn};
+pub use config_builder::ConfigBuilder;
+#[cfg(feature = "cookie")]
+pub use cookie;
+pub use cookie_options::{to_http_date, CookieOptions, VerifiedCookie};
+pub use entry::Entry;
+pub use error::{ConfigError, Conflict, CookieError, DataError, DataTooLarge, Unsupported};
+pub use export::SessionExport;
+#[cfg(feature = "axum")]
+pub use extract::{OnRejection, SessionRejection};
+pub use flash::{Flash, Level};
+pub use guard::SessionGuard;
+#[cfg(feature = "http")]
+pub use handle::SessionHandle;
+pub use hooks::SessionHooks;
+#[cfg(feature = "nanoid")]
+pub use id::{generate_id, ID_LENGTH};
+pub use id::{verify_id, MAX_ID_LENGTH, MIN_ID_LENGTH};
+#[cfg(feature = "cookie")]
+pub use integration::finish_session_cookies;
+#[cfg(feature = "http")]
+pub use integration::{apply_session, extract_session, request_session_id};
+pub use integration::{
+    finish_session, join_cookie_headers, load_cookie_session, load_request_session,
+};
+pub use key::Key;
+pub use lock::LockGuard;
+pub use metadata::{from_millis, to_millis, Metadata};
+pub use namespace::NamespacedSession;
+pub use opaque_id::{OpaqueId, OpaqueIds};
+pub use options::ConfigOptions;
+pub use payload::Payload;
+pub use same_site::SameSite;
+pub use serialized::Serialized;
+#[cfg(feature = "bincode")]
+pub use serializer::Bincode;
+#[cfg(feature = "cbor")]
+pub use serializer::Cbor;
+#[cfg(feature = "msgpack")]
+pub use serializer::MessagePack;
+#[cfg(feature = "postcard")]
+pub use serializer::Postcard;
+pub use serializer::{CanonicalJson, EncryptedSerializer, Json, Serializer};
+pub use session::{Session, SessionBeer};
+pub use session_data::SessionData;
+pub use snapshot::StateSnapshot;
+pub use status::SessionStatus;
 pub use storage::Storage;
+pub use typed::TypedSession;
+pub use values::Values;
 
 /// A data state
 pub type Data = data::Map<String, data::Value>;
 
+/// Reserved keys in the session data
+pub mod keys {
+    /// The user id bound to the session
+    pub const USER: &str = "_user";
+    /// The version of the stored data, bumped on every save
+    pub const VERSION: &str = "_version";
+    /// When the session was first saved, in milliseconds since the unix epoch
+    pub const CREATED_AT: &str = "_created_at";
+    /// When the session was last saved, in milliseconds since the unix epoch
+    pub const LAST_ACCESSED: &str = "_last_accessed";
+    /// The pending flash messages
+    pub const FLASH: &str = "_flash";
+    /// The CSRF token
+    pub const CSRF: &str = "_csrf";
+    /// The schema version of the data migrated by `Session::migrate`
+    pub const SCHEMA: &str = "_schema";
+    /// The schema version of the data under `Config::schema_version`, apart from
+    /// the one of `Session::migrate`
+    pub const SCHEMA_VERSION: &str = "_schema_version";
+    /// The max_age of the session in seconds, see `Session::set_max_age`
+    pub const MAX_AGE: &str = "_max_age";
+    /// When a record saved by a tower-sessions store expires, in milliseconds since the unix epoch
+    pub const EXPIRY_DATE: &str = "_expiry_date";
+}
+
+/// Gets the schema version of the data migrated by `Session::migrate`, `0` if it's unversioned
+pub fn data_schema(data: &Data) -> u64 {
+    data.get(keys::SCHEMA)
+        .and_then(data::Value::as_u64)
+        .unwrap_or(0)
+}
+
+/// Gets the schema version of the data under `Config::schema_version`, `0` if it's unversioned
+pub fn data_schema_version(data: &Data) -> u64 {
+    data.get(keys::SCHEMA_VERSION)
+        .and_then(data::Value::as_u64)
+        .unwrap_or(0)
+}
+
+/// Gets the version of the data, `0` if it was never saved
+pub fn data_version(data: &Data) -> u64 {
+    data.get(keys::VERSION)
+        .and_then(data::Value::as_u64)
+        .unwrap_or(0)
+}
+
 #[allow(missing_docs)]
 pub mod data {
     pub use ::serde::{de::DeserializeOwned, Serialize};
-    pub use ::serde_json::{from_value, to_value, Map, Value};
+    pub use ::serde_json::{from_value, to_value, Map, Number, Value};
 }
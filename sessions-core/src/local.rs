@@ -0,0 +1,272 @@
+//! Storage and Session for single-threaded runtimes
+//!
+//! Kept out of the crate root since every [`Storage`] is a [`LocalStorage`] too,
+//! having both traits in scope makes their methods ambiguous.
+
+use std::{
+    fmt::{self, Debug},
+    rc::Rc,
+    sync::{RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    async_trait,
+    data::{DeserializeOwned, Serialize, Value},
+    session::{decode, encode, SessionBeer},
+    session_core::{stamp, SessionCore},
+    Data, Metadata, Payload, Result, SessionStatus, Storage,
+};
+
+/// A Storage Trait for single-threaded runtimes, its futures aren't `Send`
+///
+/// Every [`Storage`] is a `LocalStorage` too.
+#[async_trait(?Send)]
+pub trait LocalStorage<P = Data>: Debug + 'static
+where
+    P: Payload,
+{
+    /// Get a data from storage by the key
+    async fn get(&self, key: &str) -> Result<Option<P>>;
+
+    /// Set a session to storage
+    async fn set(&self, key: &str, val: P, exp: Duration) -> Result<()>;
+
+    /// Remove a data from storage by the key
+    async fn remove(&self, key: &str) -> Result<()>;
+
+    /// Check that the storage is reachable
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Purge expired data from storage, returns the number of removed keys
+    async fn cleanup(&self) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Reset the storage and remove all keys
+    async fn reset(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Close the connection
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl<S, P> LocalStorage<P> for S
+where
+    S: Storage<P>,
+    P: Payload,
+{
+    async fn get(&self, key: &str) -> Result<Option<P>> {
+        Storage::get(self, key).await
+    }
+
+    async fn set(&self, key: &str, val: P, exp: Duration) -> Result<()> {
+        Storage::set(self, key, val, exp).await
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        Storage::remove(self, key).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Storage::ping(self).await
+    }
+
+    async fn cleanup(&self) -> Result<u64> {
+        Storage::cleanup(self).await
+    }
+
+    async fn reset(&self) -> Result<()> {
+        Storage::reset(self).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        Storage::close(self).await
+    }
+}
+
+/// A Session persisted to a [`LocalStorage`], for single-threaded runtimes
+///
+/// It shares the state of [`Session`](crate::Session): only changed keys are
+/// saved, saves stamp the version and times, and a destroyed session is cleared.
+#[derive(Clone)]
+pub struct LocalSession {
+    /// Session's Storage
+    storage: Rc<dyn LocalStorage>,
+    /// Session's expires
+    max_age: Duration,
+    /// Session's status, changes and `SessionBeer`
+    core: Rc<SessionCore>,
+}
+
+impl LocalSession {
+    /// Creates new `LocalSession` with `id` `status` `LocalStorage` and `max_age`
+    pub fn new(
+        id: &str,
+        status: SessionStatus,
+        storage: Rc<dyn LocalStorage>,
+        max_age: Duration,
+    ) -> Self {
+        Self {
+            storage,
+            max_age,
+            core: Rc::new(SessionCore::new(id, status)),
+        }
+    }
+
+    /// Reads the session expires
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Reads the session beer
+    pub fn beer(&self) -> RwLockReadGuard<'_, SessionBeer> {
+        self.core.beer()
+    }
+
+    /// Writes the session beer, fails once the session is destroyed
+    pub fn beer_mut(&self) -> Result<RwLockWriteGuard<'_, SessionBeer>> {
+        self.core.beer_mut()
+    }
+
+    /// Reads the session state
+    pub fn data(&self) -> Data {
+        self.beer().data.clone()
+    }
+
+    /// Writes the session state, e.g. the one loaded from the storage
+    pub fn set_data(&self, data: Data) -> Result<()> {
+        self.beer_mut()?.data = data;
+        Ok(())
+    }
+
+    /// Gets the session id
+    pub fn id(&self) -> String {
+        self.beer().id.clone()
+    }
+
+    /// Gets the session metadata, written on every save
+    pub fn metadata(&self) -> Metadata {
+        Metadata::from_data(&self.beer().data)
+    }
+
+    /// Gets the session data status, `true` if any key changed since the last save
+    pub fn data_status(&self) -> bool {
+        !self.core.changes.is_empty()
+    }
+
+    /// Gets the keys changed since the last save, in no particular order
+    pub fn changed_keys(&self) -> Vec<String> {
+        self.core.changes.keys()
+    }
+
+    /// Gets the session status
+    pub fn status(&self) -> SessionStatus {
+        self.core.status()
+    }
+
+    /// Gets a value by the key
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let val = self.beer().data.get(key).cloned();
+        val.map(|val| decode(key, val)).transpose()
+    }
+
+    /// Sets a value by the key, returns the previous one without deserializing it
+    pub fn set<T: Serialize>(&self, key: &str, val: T) -> Result<Option<Value>> {
+        self.core.set_value(key, encode(key, val)?, |_| Ok(()))
+    }
+
+    /// Sets a value by the key, returns the previous one as a `T`
+    pub fn replace<T>(&self, key: &str, val: T) -> Result<Option<T>>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let prev = self.set(key, val)?;
+        prev.map(|prev| decode(key, prev)).transpose()
+    }
+
+    /// Removes a value, returns it
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.core
+            .remove_value(key)?
+            .map(|prev| decode(key, prev))
+            .transpose()
+    }
+
+    /// Clears the state, the version and times of the session are kept
+    pub fn clear(&self) -> Result<()> {
+        self.core.clear()
+    }
+
+    /// Saves the current state to the store
+    ///
+    /// Persists whenever a key changed since the last save, a session which was
+    /// never written to isn't persisted. Destroyed sessions are never saved.
+    pub async fn save(&self) -> Result<()> {
+        let changes = match self.core.take_changes(false) {
+            Some(changes) => changes,
+            None => return Ok(()),
+        };
+        let state = stamp(self.data(), SystemTime::now());
+        if let Err(e) = self
+            .storage
+            .set(&self.id(), state.clone(), self.max_age)
+            .await
+        {
+            self.core.changes.restore(changes);
+            return Err(e);
+        }
+        self.core.commit(&state)?;
+        self.core.mark_saved();
+        Ok(())
+    }
+
+    /// Renews the new state under the new `id`
+    pub async fn renew(&self, id: &str) -> Result<()> {
+        if !matches!(
+            self.status(),
+            SessionStatus::Renewed | SessionStatus::Destroyed
+        ) {
+            self.storage.remove(&self.id()).await?;
+            {
+                let mut beer = self.beer_mut()?;
+                beer.data.clear();
+                beer.id = id.into();
+            }
+            self.core.changes.clear();
+            self.core.remember(None);
+            self.storage.set(id, self.data(), self.max_age).await?;
+            self.core.set_status(SessionStatus::Renewed);
+        }
+        Ok(())
+    }
+
+    /// Destroys the current state from store
+    ///
+    /// The data is cleared too: afterwards reads see an empty session, and writes
+    /// fail with an error.
+    pub async fn destroy(&self) -> Result<()> {
+        if self.status() != SessionStatus::Destroyed {
+            self.storage.remove(&self.id()).await?;
+            self.core.destroy()?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for LocalSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalSession")
+            .field("status", &self.status())
+            .field("changes", &self.core.changes)
+            .field("beer", &*self.beer())
+            .field("storage", &self.storage)
+            .finish()
+    }
+}
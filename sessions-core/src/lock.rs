@@ -0,0 +1,68 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    process,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Data, Payload, Result, Storage};
+
+/// An acquired advisory lock on a session
+///
+/// The lock expires by itself after its ttl, so a crashed holder can't keep it forever.
+pub struct LockGuard<'a, P: Payload = Data> {
+    storage: &'a dyn Storage<P>,
+    key: String,
+    token: String,
+    released: AtomicBool,
+}
+
+impl<'a, P: Payload> LockGuard<'a, P> {
+    pub(crate) fn new(storage: &'a dyn Storage<P>, key: &str, token: String) -> Self {
+        Self {
+            storage,
+            token,
+            key: key.into(),
+            released: AtomicBool::new(false),
+        }
+    }
+
+    /// Gets the locked key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Releases the lock, returns `false` if it was already released or has expired
+    pub async fn release(&self) -> Result<bool> {
+        if self.released.swap(true, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        self.storage.unlock(&self.key, &self.token).await
+    }
+}
+
+impl<P: Payload> fmt::Debug for LockGuard<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockGuard")
+            .field("key", &self.key)
+            .field("released", &self.released)
+            .finish()
+    }
+}
+
+/// Generates a token identifying the lock holder
+pub(crate) fn token() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos()),
+    );
+    format!("{:016x}", hasher.finish())
+}
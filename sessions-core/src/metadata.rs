@@ -0,0 +1,44 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{data::Value, data_version, keys, Data};
+
+/// Session's metadata, written to the data on every save
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// The version of the stored data
+    pub version: u64,
+    /// When the session was first saved, `None` if unknown
+    pub created_at: Option<SystemTime>,
+    /// When the session was last saved, `None` if unknown
+    pub last_accessed: Option<SystemTime>,
+}
+
+impl Metadata {
+    /// Reads the metadata from the data, missing fields are unknown
+    pub fn from_data(data: &Data) -> Self {
+        Self {
+            version: data_version(data),
+            created_at: read_time(data, keys::CREATED_AT),
+            last_accessed: read_time(data, keys::LAST_ACCESSED),
+        }
+    }
+}
+
+/// Converts the time to milliseconds since the unix epoch
+pub fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Converts milliseconds since the unix epoch to time, `0` is unknown
+pub fn from_millis(millis: u64) -> Option<SystemTime> {
+    if millis == 0 {
+        None
+    } else {
+        Some(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+}
+
+fn read_time(data: &Data, key: &str) -> Option<SystemTime> {
+    from_millis(data.get(key).and_then(Value::as_u64)?)
+}
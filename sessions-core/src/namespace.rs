@@ -0,0 +1,126 @@
+use crate::{
+    data::{DeserializeOwned, Serialize, Value},
+    Result, Session,
+};
+
+/// Separates the names of nested namespaces and the keys
+const SEPARATOR: char = '.';
+
+/// A view of a [`Session`] whose keys are prefixed with its namespace,
+/// see [`Session::namespace`]
+///
+/// Components writing to the same session get isolated keyspaces, while sharing
+/// the id, the store and the save lifecycle. The values are stored as top-level
+/// keys `"{namespace}.{key}"`, so a top-level key with a dot can collide with them.
+#[derive(Clone, Debug)]
+pub struct NamespacedSession {
+    session: Session,
+    prefix: String,
+}
+
+impl NamespacedSession {
+    pub(crate) fn new(session: Session, name: &str) -> Self {
+        Self {
+            session,
+            prefix: format!("{}{}", name, SEPARATOR),
+        }
+    }
+
+    /// Gets the full name of the namespace, nested names are separated by dots
+    pub fn name(&self) -> &str {
+        &self.prefix[..self.prefix.len() - 1]
+    }
+
+    /// Gets the underlying session
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Gets a namespace nested in this one
+    pub fn namespace(&self, name: &str) -> NamespacedSession {
+        NamespacedSession::new(self.session.clone(), &self.key(name))
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Gets a value by the key
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.session.get(&self.key(key))
+    }
+
+    /// Gets a value by the key without deserializing it
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        self.session.get_value(&self.key(key))
+    }
+
+    /// Sets a value by the key, returns the previous one without deserializing it
+    pub fn set<T: Serialize>(&self, key: &str, val: T) -> Result<Option<Value>> {
+        self.session.set(&self.key(key), val)
+    }
+
+    /// Sets a value by the key, returns the previous one as a `T`
+    pub fn replace<T>(&self, key: &str, val: T) -> Result<Option<T>>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        self.session.replace(&self.key(key), val)
+    }
+
+    /// Removes a value, returns it
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.session.remove(&self.key(key))
+    }
+
+    /// Removes a value and returns it, so it can be consumed only once
+    pub fn take<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.session.take(&self.key(key))
+    }
+
+    /// Checks if the key has a value
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.session.contains_key(&self.key(key))
+    }
+
+    /// Clears the values of the namespace and its nested namespaces,
+    /// the other keys are left as is
+    pub fn clear(&self) -> Result<()> {
+        self.session.remove_prefixed(&self.prefix)
+    }
+
+    /// Calls `f` on each key-value pair of the namespace, with the keys unprefixed
+    pub fn for_each<F: FnMut(&str, &Value)>(&self, mut f: F) {
+        self.session.for_each(|key, val| {
+            if let Some(key) = key.strip_prefix(self.prefix.as_str()) {
+                f(key, val)
+            }
+        })
+    }
+
+    /// Gets the keys of the namespace, unprefixed
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        self.for_each(|key, _| keys.push(key.to_string()));
+        keys
+    }
+
+    /// Iterates over the key-value pairs of the namespace cloned out of the session
+    pub fn iter(&self) -> impl Iterator<Item = (String, Value)> {
+        let mut pairs = Vec::new();
+        self.for_each(|key, val| pairs.push((key.to_string(), val.clone())));
+        pairs.into_iter()
+    }
+
+    /// Counts the values of the namespace
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.for_each(|_, _| len += 1);
+        len
+    }
+
+    /// Checks if the namespace has no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
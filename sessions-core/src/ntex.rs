@@ -0,0 +1,23 @@
+use ntex::{
+    http::Payload,
+    web::{
+        error::{ErrorInternalServerError, InternalError},
+        ErrorRenderer, FromRequest, HttpRequest,
+    },
+};
+
+use crate::Session;
+
+/// Extracts the session the `SessionMiddleware` loaded into the request,
+/// failing with `500 Internal Server Error` on routes without it
+impl<Err: ErrorRenderer> FromRequest<Err> for Session {
+    type Error = InternalError<&'static str, Err>;
+
+    async fn from_request(req: &HttpRequest, _: &mut Payload) -> Result<Self, Self::Error> {
+        req.extensions().get::<Session>().cloned().ok_or_else(|| {
+            ErrorInternalServerError(
+                "no session in the request, the `SessionMiddleware` isn't installed on this route",
+            )
+        })
+    }
+}
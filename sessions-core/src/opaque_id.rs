@@ -0,0 +1,86 @@
+use std::{
+    convert::TryInto,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{encryption, to_millis, Key};
+
+/// The associated data of the tokens, so they can't be mixed with other encrypted values
+const AAD: &[u8] = b"sessions.id.v1";
+
+/// Encrypts the session ids into opaque tokens, see [`Config::opaque_ids`]
+///
+/// A token is the XChaCha20-Poly1305 encryption of the id, as the storage knows it,
+/// and of the time it was issued, in unpadded base64url. The id can embed a tenant
+/// hint or a shard key without exposing it, and tokens of the same id aren't
+/// linkable. Tokens are decrypted with the active key, then the previous ones, so
+/// the keys can be rotated.
+///
+/// [`Config::opaque_ids`]: crate::Config::opaque_ids
+#[derive(Debug, Clone)]
+pub struct OpaqueIds {
+    key: Key,
+    previous_keys: Vec<Key>,
+}
+
+/// A session id decrypted from a token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpaqueId {
+    /// The session id, as the storage knows it
+    pub id: String,
+    /// When the token was issued
+    pub issued_at: SystemTime,
+    /// The token was only decrypted with a previous key
+    pub reissue: bool,
+}
+
+impl OpaqueIds {
+    /// Creates new `OpaqueIds` encrypting the ids with the key
+    pub fn new(key: impl Into<Key>) -> Self {
+        Self::with_keys(key, Vec::new())
+    }
+
+    /// Creates new `OpaqueIds` with the `active` key and the `previous` ones,
+    /// tried in order to decrypt tokens issued before a key rotation
+    pub fn with_keys(active: impl Into<Key>, previous: Vec<Key>) -> Self {
+        Self {
+            key: active.into(),
+            previous_keys: previous,
+        }
+    }
+
+    /// Issues a token of the session id
+    ///
+    /// # Panics
+    ///
+    /// If the OS random number generator fails, as generating an id does.
+    pub fn issue(&self, id: &str, issued_at: SystemTime) -> String {
+        let msg = [&to_millis(issued_at).to_be_bytes()[..], id.as_bytes()].concat();
+        let sealed =
+            encryption::seal(&self.key, &msg, AAD).expect("the OS random number generator failed");
+        base64::encode_config(sealed, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Decrypts the session id of a token, `None` if it wasn't issued under any of the keys
+    pub fn open(&self, token: &str) -> Option<OpaqueId> {
+        let sealed = base64::decode_config(token, base64::URL_SAFE_NO_PAD).ok()?;
+        let (msg, reissue) = encryption::open(&self.key, &sealed, AAD)
+            .map(|msg| (msg, false))
+            .or_else(|| {
+                self.previous_keys
+                    .iter()
+                    .find_map(|key| encryption::open(key, &sealed, AAD))
+                    .map(|msg| (msg, true))
+            })?;
+        if msg.len() < 8 {
+            return None;
+        }
+        let (millis, id) = msg.split_at(8);
+        Some(OpaqueId {
+            id: String::from_utf8(id.to_vec()).ok()?,
+            issued_at: UNIX_EPOCH
+                + Duration::from_millis(u64::from_be_bytes(millis.try_into().ok()?)),
+            reissue,
+        })
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CookieOptions;
+
+/// The plain-data options of a [`Config`](crate::Config), e.g. loaded from a config file
+///
+/// Durations are in seconds, unknown fields are rejected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigOptions {
+    /// Maximum time to wait for the storage to answer a ping
+    #[serde(with = "secs::option")]
+    pub ping_timeout: Option<Duration>,
+    /// Replaces the CSRF token after it was verified
+    pub rotate_csrf: bool,
+    /// Maximum size of the serialized session data in bytes
+    pub max_data_size: Option<usize>,
+    /// How long the storage keeps a session
+    #[serde(with = "secs::option")]
+    pub ttl: Option<Duration>,
+    /// Expires a session which wasn't saved or touched for this long
+    #[serde(with = "secs::option")]
+    pub idle_timeout: Option<Duration>,
+    /// Expires a session this long after it was created
+    #[serde(with = "secs::option")]
+    pub absolute_timeout: Option<Duration>,
+    /// Refreshes the expiry of unchanged sessions on every request
+    pub rolling: bool,
+    /// Minimum time between two refreshes of a rolling session
+    #[serde(with = "secs::option")]
+    pub rolling_interval: Option<Duration>,
+    /// Saves sessions no key was written to
+    pub save_uninitialized: bool,
+    /// Fails the session method with the error of a hook
+    pub strict_hooks: bool,
+    /// Prepended to the session ids to make the keys of the storage
+    pub key_prefix: Option<String>,
+    /// Cookie Options, last as TOML tables follow the values
+    pub cookie: CookieOptions,
+}
+
+/// (De)serializes durations and times as seconds
+pub(crate) mod secs {
+    pub(crate) mod option {
+        use std::time::Duration;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub(crate) fn serialize<S: Serializer>(
+            dur: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match dur {
+                Some(dur) => serializer.serialize_some(&dur.as_secs()),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error> {
+            Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_secs))
+        }
+    }
+
+    /// Times as seconds since the unix epoch
+    pub(crate) mod time {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub(crate) fn serialize<S: Serializer>(
+            time: &Option<SystemTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match time {
+                Some(time) => serializer
+                    .serialize_some(&time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<SystemTime>, D::Error> {
+            Ok(Option::<u64>::deserialize(deserializer)?
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+        }
+    }
+}
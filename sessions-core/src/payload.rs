@@ -0,0 +1,43 @@
+use crate::{data::Value, keys, record, Data, Metadata};
+
+/// A payload persisted by storages
+///
+/// Storages either keep the [`Data`] as is, or the bytes of a [`record`].
+pub trait Payload: Send + Sync + 'static {
+    /// Reads the metadata of the payload
+    fn metadata(&self) -> Metadata;
+
+    /// Reads the user bound to the payload
+    fn user(&self) -> Option<&str>;
+
+    /// Sets the last accessed time, in milliseconds since the unix epoch
+    fn set_accessed(&mut self, millis: u64);
+}
+
+impl Payload for Data {
+    fn metadata(&self) -> Metadata {
+        Metadata::from_data(self)
+    }
+
+    fn user(&self) -> Option<&str> {
+        self.get(keys::USER).and_then(Value::as_str)
+    }
+
+    fn set_accessed(&mut self, millis: u64) {
+        self.insert(keys::LAST_ACCESSED.into(), millis.into());
+    }
+}
+
+impl Payload for Vec<u8> {
+    fn metadata(&self) -> Metadata {
+        record::decode_metadata(self).unwrap_or_default()
+    }
+
+    fn user(&self) -> Option<&str> {
+        record::decode(self)?.user
+    }
+
+    fn set_accessed(&mut self, millis: u64) {
+        record::set_accessed(self, millis);
+    }
+}
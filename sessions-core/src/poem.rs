@@ -0,0 +1,16 @@
+use poem::{http::StatusCode, Error, FromRequest, Request, RequestBody, Result};
+
+use crate::Session;
+
+/// Extracts the session the `SessionMiddleware` loaded into the request,
+/// failing with `500 Internal Server Error` on routes without it
+impl<'a> FromRequest<'a> for Session {
+    async fn from_request(req: &'a Request, _: &mut RequestBody) -> Result<Self> {
+        req.extensions().get::<Session>().cloned().ok_or_else(|| {
+            Error::from_string(
+                "no session in the request, the `SessionMiddleware` isn't installed on this route",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })
+    }
+}
@@ -0,0 +1,111 @@
+//! The byte record persisted by byte-oriented storages
+//!
+//! A record starts with a header carrying the metadata and the bound user, so
+//! storages can read them without knowing how the data itself was serialized:
+//!
+//! | format `u8` | version `u64` | created at `u64` | last accessed `u64` | user length `u16` | user | body |
+//!
+//! Integers are big endian, times are milliseconds since the unix epoch with `0` as unknown.
+//! Records written before the header was introduced are plain JSON objects.
+
+use std::convert::TryInto;
+
+use crate::{anyhow, from_millis, to_millis, Data, Metadata, Payload, Result};
+
+/// The current record format
+pub const FORMAT: u8 = 1;
+
+/// The length of the fixed part of the header, up to and including the user length
+pub const HEADER_LEN: usize = 1 + 8 * 3 + 2;
+
+/// The offset of the last accessed time in the header
+pub const ACCESSED_OFFSET: usize = 1 + 8 * 2;
+
+/// A decoded record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'a> {
+    /// The metadata of the data
+    pub metadata: Metadata,
+    /// The user bound to the data
+    pub user: Option<&'a str>,
+    /// The serialized data
+    pub body: &'a [u8],
+}
+
+/// Encodes the serialized `body` of the data with its header
+///
+/// Fails if the bound user id is longer than `u16::MAX` bytes.
+pub fn encode(data: &Data, body: &[u8]) -> Result<Vec<u8>> {
+    let metadata = data.metadata();
+    let user = data.user().unwrap_or_default().as_bytes();
+    if user.len() > u16::MAX as usize {
+        return Err(anyhow!(
+            "the user id bound to the session is longer than {} bytes",
+            u16::MAX
+        ));
+    }
+
+    let mut buf = Vec::with_capacity(HEADER_LEN + user.len() + body.len());
+    buf.push(FORMAT);
+    buf.extend_from_slice(&metadata.version.to_be_bytes());
+    buf.extend_from_slice(&metadata.created_at.map_or(0, to_millis).to_be_bytes());
+    buf.extend_from_slice(&metadata.last_accessed.map_or(0, to_millis).to_be_bytes());
+    buf.extend_from_slice(&(user.len() as u16).to_be_bytes());
+    buf.extend_from_slice(user);
+    buf.extend_from_slice(body);
+    Ok(buf)
+}
+
+/// Decodes a record, `None` if the bytes aren't a record
+pub fn decode(buf: &[u8]) -> Option<Record<'_>> {
+    match *buf.first()? {
+        b'{' => Some(Record {
+            metadata: Metadata::default(),
+            user: None,
+            body: buf,
+        }),
+        FORMAT => {
+            let user_len =
+                u16::from_be_bytes(buf.get(HEADER_LEN - 2..HEADER_LEN)?.try_into().ok()?);
+            let body_start = HEADER_LEN + user_len as usize;
+            let user = std::str::from_utf8(buf.get(HEADER_LEN..body_start)?).ok()?;
+            Some(Record {
+                metadata: decode_metadata(buf)?,
+                user: if user.is_empty() { None } else { Some(user) },
+                body: &buf[body_start..],
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Decodes the metadata from the fixed part of the header only
+pub fn decode_metadata(buf: &[u8]) -> Option<Metadata> {
+    match *buf.first()? {
+        b'{' => Some(Metadata::default()),
+        FORMAT => {
+            let read = |i: usize| -> Option<u64> {
+                Some(u64::from_be_bytes(
+                    buf.get(1 + i * 8..1 + (i + 1) * 8)?.try_into().ok()?,
+                ))
+            };
+            Some(Metadata {
+                version: read(0)?,
+                created_at: from_millis(read(1)?),
+                last_accessed: from_millis(read(2)?),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Overwrites the last accessed time in the header, returns `false` if the bytes aren't a record
+pub fn set_accessed(buf: &mut [u8], millis: u64) -> bool {
+    match buf.first() {
+        Some(&FORMAT) if buf.len() >= HEADER_LEN => {
+            buf[ACCESSED_OFFSET..ACCESSED_OFFSET + 8].copy_from_slice(&millis.to_be_bytes());
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,23 @@
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+};
+
+use crate::Session;
+
+/// Guards routes with the session the `SessionFairing` loaded for the request,
+/// failing with `500 Internal Server Error` without it
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Session {
+    type Error = &'static str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.local_cache(|| None::<Session>) {
+            Some(session) => Outcome::Success(session.clone()),
+            None => Outcome::Error((
+                Status::InternalServerError,
+                "no session in the request, the `SessionFairing` isn't attached",
+            )),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{anyhow, Error};
+
+/// The `SameSite` attribute of the session cookie, a lowercase string in serde
+///
+/// Browsers reject `SameSite=None` cookies which aren't `Secure`, so the cookie
+/// is always rendered `Secure` with [`SameSite::None`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    /// Only sent with same-site requests
+    #[serde(alias = "Strict")]
+    Strict,
+    /// Also sent with top-level cross-site navigations
+    #[serde(alias = "Lax")]
+    Lax,
+    /// Sent with all requests, requires `Secure`
+    #[serde(alias = "None")]
+    None,
+}
+
+impl SameSite {
+    /// Gets the attribute value
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SameSite {
+    type Err = Error;
+
+    /// Parses the attribute value, case-insensitively
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "strict" => Self::Strict,
+            "lax" => Self::Lax,
+            "none" => Self::None,
+            _ => return Err(anyhow!("invalid SameSite value `{}`", s)),
+        })
+    }
+}
+
+#[cfg(feature = "cookie")]
+impl From<cookie::SameSite> for SameSite {
+    fn from(same_site: cookie::SameSite) -> Self {
+        match same_site {
+            cookie::SameSite::Strict => Self::Strict,
+            cookie::SameSite::Lax => Self::Lax,
+            cookie::SameSite::None => Self::None,
+        }
+    }
+}
+
+#[cfg(feature = "cookie")]
+impl From<SameSite> for cookie::SameSite {
+    fn from(same_site: SameSite) -> Self {
+        match same_site {
+            SameSite::Strict => Self::Strict,
+            SameSite::Lax => Self::Lax,
+            SameSite::None => Self::None,
+        }
+    }
+}
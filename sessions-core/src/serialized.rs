@@ -0,0 +1,152 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    async_trait, keys, record, to_millis, Data, Json, Metadata, Result, Serializer, Storage,
+};
+
+/// Stores the data in a byte-oriented storage, as [`record`]s whose body is
+/// written by a [`Serializer`], JSON by default
+#[derive(Debug, Clone)]
+pub struct Serialized<S> {
+    inner: S,
+    serializer: Arc<dyn Serializer>,
+}
+
+impl<S> Serialized<S> {
+    /// Creates new `Serialized` over the byte-oriented storage, serializing as JSON
+    pub fn new(inner: S) -> Self {
+        Self::with_serializer(inner, Arc::new(Json))
+    }
+
+    /// Creates new `Serialized` over the byte-oriented storage with the serializer
+    pub fn with_serializer(inner: S, serializer: Arc<dyn Serializer>) -> Self {
+        Self { inner, serializer }
+    }
+
+    /// Gets the byte-oriented storage
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Gets the serializer
+    pub fn serializer(&self) -> &dyn Serializer {
+        &*self.serializer
+    }
+
+    fn encode(&self, val: &Data) -> Result<Vec<u8>> {
+        record::encode(val, &self.serializer.serialize(val)?)
+    }
+
+    /// Decodes a record, data written by another serializer is missing
+    ///
+    /// The metadata of the header wins over the one of the body, as `touch` only
+    /// rewrites the header.
+    fn decode(&self, buf: &[u8]) -> Option<Data> {
+        let record = record::decode(buf)?;
+        let data = if buf.first() == Some(&b'{') {
+            // Written before the record format
+            Json.deserialize(record.body)
+        } else {
+            self.serializer.deserialize(record.body)
+        };
+        let mut data = data
+            .map_err(|e| log::warn!("session data can't be deserialized: {}", e))
+            .ok()?;
+        let Metadata {
+            version,
+            created_at,
+            last_accessed,
+        } = record.metadata;
+        if version > 0 {
+            data.insert(keys::VERSION.into(), version.into());
+        }
+        for (key, time) in [
+            (keys::CREATED_AT, created_at),
+            (keys::LAST_ACCESSED, last_accessed),
+        ] {
+            if let Some(time) = time {
+                data.insert(key.into(), to_millis(time).into());
+            }
+        }
+        Some(data)
+    }
+}
+
+#[async_trait]
+impl<S> Storage for Serialized<S>
+where
+    S: Storage<Vec<u8>>,
+{
+    async fn get(&self, key: &str) -> Result<Option<Data>> {
+        Ok(self
+            .inner
+            .get(key)
+            .await?
+            .as_deref()
+            .and_then(|buf| self.decode(buf)))
+    }
+
+    async fn metadata(&self, key: &str) -> Result<Option<Metadata>> {
+        self.inner.metadata(key).await
+    }
+
+    async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
+        self.inner.set(key, self.encode(&val)?, exp).await
+    }
+
+    async fn set_if_version(
+        &self,
+        key: &str,
+        val: Data,
+        exp: Duration,
+        version: u64,
+    ) -> Result<()> {
+        self.inner
+            .set_if_version(key, self.encode(&val)?, exp, version)
+            .await
+    }
+
+    async fn touch(&self, key: &str, accessed: u64, exp: Duration) -> Result<bool> {
+        self.inner.touch(key, accessed, exp).await
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.inner.remove(key).await
+    }
+
+    async fn rename(&self, old: &str, new: &str) -> Result<()> {
+        self.inner.rename(old, new).await
+    }
+
+    async fn sessions_for(&self, user_id: &str) -> Result<Vec<String>> {
+        self.inner.sessions_for(user_id).await
+    }
+
+    async fn destroy_for(&self, user_id: &str) -> Result<u64> {
+        self.inner.destroy_for(user_id).await
+    }
+
+    async fn try_lock(&self, key: &str, token: &str, ttl: Duration) -> Result<bool> {
+        self.inner.try_lock(key, token, ttl).await
+    }
+
+    async fn unlock(&self, key: &str, token: &str) -> Result<bool> {
+        self.inner.unlock(key, token).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.inner.ping().await
+    }
+
+    async fn cleanup(&self) -> Result<u64> {
+        self.inner.cleanup().await
+    }
+
+    async fn reset(&self) -> Result<()> {
+        self.inner.reset().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+}
@@ -0,0 +1,546 @@
+use std::fmt;
+
+use crate::{anyhow, encryption, Data, Key, Result};
+
+/// Serializes the data persisted by byte-oriented storages, see [`Serialized`]
+///
+/// The body of each [`record`](crate::record) is written with it, the header
+/// keeps its own format. The session API always works on JSON values.
+///
+/// [`Serialized`]: crate::Serialized
+pub trait Serializer: fmt::Debug + Send + Sync + 'static {
+    /// Serializes the data
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>>;
+
+    /// Deserializes the data, fails if the bytes weren't written by this serializer
+    fn deserialize(&self, buf: &[u8]) -> Result<Data>;
+}
+
+/// Serializes the data as JSON, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Json {
+    /// Gets the canonical variant of the JSON serializer
+    pub fn canonical() -> CanonicalJson {
+        CanonicalJson
+    }
+}
+
+impl Serializer for Json {
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(data)?)
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Data> {
+        Ok(serde_json::from_slice(buf)?)
+    }
+}
+
+/// Serializes the data as canonical JSON, see [`Json::canonical`]
+///
+/// The keys of the objects are sorted recursively, there's no whitespace and the
+/// floats are written in their shortest form, `-0.0` as `0.0`. Equal data is
+/// written as the same bytes, whichever order its keys were inserted in. It reads
+/// any JSON.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CanonicalJson;
+
+impl Serializer for CanonicalJson {
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>> {
+        Ok(canonical(data))
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Data> {
+        Json.deserialize(buf)
+    }
+}
+
+/// Writes the data as canonical JSON
+pub(crate) fn canonical(data: &Data) -> Vec<u8> {
+    serde_json::to_vec(&sorted::Sorted::Data(data)).expect("JSON values are serializable")
+}
+
+mod sorted {
+    use serde::{Serialize, Serializer};
+
+    use crate::{data::Value, Data};
+
+    /// Writes the objects with their keys sorted
+    pub(super) enum Sorted<'a> {
+        Data(&'a Data),
+        Value(&'a Value),
+    }
+
+    impl Serialize for Sorted<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Self::Data(data) => sorted_map(data, serializer),
+                Self::Value(Value::Object(map)) => sorted_map(map, serializer),
+                Self::Value(Value::Array(vals)) => {
+                    serializer.collect_seq(vals.iter().map(Sorted::Value))
+                }
+                Self::Value(Value::Number(n)) if n.as_f64() == Some(0.0) && n.is_f64() => {
+                    serializer.serialize_f64(0.0)
+                }
+                Self::Value(val) => val.serialize(serializer),
+            }
+        }
+    }
+
+    fn sorted_map<S: Serializer>(map: &Data, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut pairs = map.iter().collect::<Vec<_>>();
+        pairs.sort_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_map(
+            pairs
+                .into_iter()
+                .map(|(key, val)| (key, Sorted::Value(val))),
+        )
+    }
+}
+
+/// Encrypts the bytes of another serializer, so the data is encrypted whichever
+/// storage is plugged in
+///
+/// The bytes are encrypted with XChaCha20-Poly1305 under the active key and a random
+/// nonce, as `<format><nonce><ciphertext>`. They're decrypted with the active key, then
+/// the previous ones, so the keys can be rotated: data encrypted under a previous key
+/// is encrypted under the active one on its next save. Bytes which can't be decrypted,
+/// tampered with or under an unknown key, fail to deserialize, so [`Serialized`] reads
+/// the session as missing.
+///
+/// The header of the [`record`](crate::record), with the user id and the times, isn't
+/// encrypted. A fresh nonce makes each save different bytes, so saves of unchanged
+/// data are skipped before serializing, comparing the plaintext.
+///
+/// ```ignore
+/// let config = Config::builder()
+///     .byte_storage(storage)
+///     .serializer(EncryptedSerializer::new(Json, key))
+///     .build()?;
+/// ```
+///
+/// [`Serialized`]: crate::Serialized
+#[derive(Debug, Clone)]
+pub struct EncryptedSerializer<S> {
+    inner: S,
+    key: Key,
+    previous_keys: Vec<Key>,
+}
+
+impl<S> EncryptedSerializer<S> {
+    /// The first byte of the encrypted format
+    const FORMAT: u8 = 1;
+
+    /// Creates new `EncryptedSerializer` encrypting the bytes of the serializer with the key
+    pub fn new(inner: S, key: impl Into<Key>) -> Self {
+        Self::with_keys(inner, key, Vec::new())
+    }
+
+    /// Creates new `EncryptedSerializer` with the `active` key and the `previous`
+    /// ones, tried in order to decrypt data encrypted before a key rotation
+    pub fn with_keys(inner: S, active: impl Into<Key>, previous: Vec<Key>) -> Self {
+        Self {
+            inner,
+            key: active.into(),
+            previous_keys: previous,
+        }
+    }
+
+    /// Gets the serializer of the plaintext
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: Serializer> Serializer for EncryptedSerializer<S> {
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>> {
+        let sealed = encryption::seal(&self.key, &self.inner.serialize(data)?, &[Self::FORMAT])
+            .ok_or_else(|| anyhow!("session data can't be encrypted"))?;
+        Ok([&[Self::FORMAT][..], &sealed].concat())
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Data> {
+        let plaintext = match buf.split_first() {
+            Some((&Self::FORMAT, sealed)) => std::iter::once(&self.key)
+                .chain(&self.previous_keys)
+                .find_map(|key| encryption::open(key, sealed, &[Self::FORMAT])),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("session data can't be decrypted"))?;
+        self.inner.deserialize(&plaintext)
+    }
+}
+
+/// Serializes the data as MessagePack
+///
+/// Smaller than JSON, it reads the numbers back as the JSON path does: non-negative
+/// integers as `u64`, negative ones as `i64` and floats as `f64`. The
+/// [`binary`](crate::binary) values are written as bin.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl Serializer for MessagePack {
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec(&msgpack::Wire::Data(data))?)
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Data> {
+        match rmp_serde::from_slice::<msgpack::Read>(buf)?.0 {
+            crate::data::Value::Object(object) => Ok(object),
+            _ => Err(crate::DataError::NotAnObject.into()),
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+mod msgpack {
+    use std::fmt;
+
+    use serde::{
+        de::{MapAccess, SeqAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    use crate::{
+        binary,
+        data::{Map, Number, Value},
+        Data,
+    };
+
+    /// Writes the data with the binary values as bytes
+    pub(super) enum Wire<'a> {
+        Data(&'a Data),
+        Value(&'a Value),
+    }
+
+    impl Serialize for Wire<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let val = match self {
+                Self::Data(data) => {
+                    return serializer
+                        .collect_map(data.iter().map(|(key, val)| (key, Wire::Value(val))))
+                }
+                Self::Value(val) => val,
+            };
+            if let Some(bytes) = binary::from_value(val) {
+                return serializer.serialize_bytes(&bytes);
+            }
+            match val {
+                Value::Array(vals) => serializer.collect_seq(vals.iter().map(Wire::Value)),
+                Value::Object(map) => {
+                    serializer.collect_map(map.iter().map(|(key, val)| (key, Wire::Value(val))))
+                }
+                val => val.serialize(serializer),
+            }
+        }
+    }
+
+    /// Reads a value with the bytes as binary values
+    pub(super) struct Read(pub(super) Value);
+
+    impl<'de> Deserialize<'de> for Read {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(ReadVisitor).map(Read)
+        }
+    }
+
+    struct ReadVisitor;
+
+    impl<'de> Visitor<'de> for ReadVisitor {
+        type Value = Value;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a MessagePack value")
+        }
+
+        fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+            Ok(Value::Bool(b))
+        }
+
+        fn visit_i64<E>(self, n: i64) -> Result<Value, E> {
+            Ok(n.into())
+        }
+
+        fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+            Ok(n.into())
+        }
+
+        fn visit_f64<E>(self, f: f64) -> Result<Value, E> {
+            Ok(Number::from_f64(f).map_or(Value::Null, Value::Number))
+        }
+
+        fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+            Ok(Value::String(s.into()))
+        }
+
+        fn visit_string<E>(self, s: String) -> Result<Value, E> {
+            Ok(Value::String(s))
+        }
+
+        fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Value, E> {
+            Ok(binary::to_value(bytes))
+        }
+
+        fn visit_none<E>(self) -> Result<Value, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_unit<E>(self) -> Result<Value, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+            let mut vals = Vec::with_capacity(seq.size_hint().unwrap_or_default().min(4096));
+            while let Some(Read(val)) = seq.next_element()? {
+                vals.push(val);
+            }
+            Ok(Value::Array(vals))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+            let mut object = Map::new();
+            while let Some((key, Read(val))) = map.next_entry::<String, Read>()? {
+                object.insert(key, val);
+            }
+            Ok(Value::Object(object))
+        }
+    }
+}
+
+/// Serializes the data as CBOR
+///
+/// The [`binary`](crate::binary) values are written as byte strings. Reading data
+/// written elsewhere, tags are dropped, keeping the tagged values, and maps must
+/// have text keys.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Serializer for Cbor {
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>> {
+        let object = ciborium::Value::Map(
+            data.iter()
+                .map(|(key, val)| (key.as_str().into(), cbor::to_cbor(val)))
+                .collect(),
+        );
+        let mut buf = Vec::new();
+        ciborium::into_writer(&object, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Data> {
+        match cbor::from_cbor(ciborium::from_reader(buf)?)? {
+            crate::data::Value::Object(object) => Ok(object),
+            _ => Err(crate::DataError::NotAnObject.into()),
+        }
+    }
+}
+
+#[cfg(feature = "cbor")]
+mod cbor {
+    use std::convert::TryFrom;
+
+    use ciborium::Value;
+
+    use crate::{anyhow, binary, data, Result};
+
+    pub(super) fn to_cbor(val: &data::Value) -> Value {
+        if let Some(bytes) = binary::from_value(val) {
+            return Value::Bytes(bytes);
+        }
+        match val {
+            data::Value::Null => Value::Null,
+            data::Value::Bool(b) => Value::Bool(*b),
+            data::Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(n), _) => Value::Integer(n.into()),
+                (None, Some(n)) => Value::Integer(n.into()),
+                _ => Value::Float(n.as_f64().unwrap_or_default()),
+            },
+            data::Value::String(s) => Value::Text(s.clone()),
+            data::Value::Array(vals) => Value::Array(vals.iter().map(to_cbor).collect()),
+            data::Value::Object(map) => Value::Map(
+                map.iter()
+                    .map(|(key, val)| (key.as_str().into(), to_cbor(val)))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub(super) fn from_cbor(val: Value) -> Result<data::Value> {
+        Ok(match val {
+            Value::Null => data::Value::Null,
+            Value::Bool(b) => data::Value::Bool(b),
+            Value::Integer(n) => {
+                let n = i128::from(n);
+                match (u64::try_from(n), i64::try_from(n)) {
+                    (Ok(n), _) => n.into(),
+                    (_, Ok(n)) => n.into(),
+                    _ => return Err(anyhow!("CBOR integer {} is out of range", n)),
+                }
+            }
+            Value::Float(f) => data::Number::from_f64(f)
+                .ok_or_else(|| anyhow!("CBOR float {} isn't finite", f))?
+                .into(),
+            Value::Text(s) => data::Value::String(s),
+            Value::Bytes(bytes) => binary::to_value(&bytes),
+            Value::Tag(_, val) => from_cbor(*val)?,
+            Value::Array(vals) => {
+                data::Value::Array(vals.into_iter().map(from_cbor).collect::<Result<_>>()?)
+            }
+            Value::Map(pairs) => data::Value::Object(
+                pairs
+                    .into_iter()
+                    .map(|(key, val)| match key {
+                        Value::Text(key) => Ok((key, from_cbor(val)?)),
+                        _ => Err(anyhow!("CBOR map keys must be text")),
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            _ => return Err(anyhow!("CBOR value isn't supported")),
+        })
+    }
+}
+
+/// Serializes the data with bincode
+///
+/// Bincode isn't self-describing, so the JSON values are written as a tagged
+/// mirror of them: an enum with a variant per kind of value, where numbers are
+/// `u64`, `i64` or `f64` as serde_json holds them, objects are lists of pairs and
+/// [`binary`](crate::binary) values are bytes. Its layout is stable as long as the
+/// variants keep their order.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Serializer for Bincode {
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>> {
+        use bincode::Options;
+
+        let object = data
+            .iter()
+            .map(|(key, val)| (key.as_str(), compact::Value::from(val)))
+            .collect::<Vec<_>>();
+        Ok(bincode::DefaultOptions::new().serialize(&object)?)
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Data> {
+        use bincode::Options;
+
+        let object: Vec<(String, compact::Value)> = bincode::DefaultOptions::new()
+            .with_limit(buf.len() as u64)
+            .reject_trailing_bytes()
+            .deserialize(buf)?;
+        Ok(object
+            .into_iter()
+            .map(|(key, val)| (key, val.into()))
+            .collect())
+    }
+}
+
+/// Serializes the data with postcard, for compact payloads on embedded and wasm targets
+///
+/// Postcard isn't self-describing either, the data is written as the tagged
+/// mirror of [`Bincode`], which reads it too. Every shape of value is
+/// supported, numbers keep their kind and [`binary`](crate::binary) values are
+/// written as bytes. Reading fails on bytes which aren't exactly such a mirror,
+/// trailing bytes included.
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Serializer for Postcard {
+    fn serialize(&self, data: &Data) -> Result<Vec<u8>> {
+        let object = data
+            .iter()
+            .map(|(key, val)| (key.as_str(), compact::Value::from(val)))
+            .collect::<Vec<_>>();
+        Ok(postcard::to_allocvec(&object)?)
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Data> {
+        let (object, rest): (Vec<(String, compact::Value)>, _) = postcard::take_from_bytes(buf)?;
+        if !rest.is_empty() {
+            return Err(anyhow!("postcard data has {} trailing bytes", rest.len()));
+        }
+        Ok(object
+            .into_iter()
+            .map(|(key, val)| (key, val.into()))
+            .collect())
+    }
+}
+
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+mod compact {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{binary, data};
+
+    /// A JSON value bincode and postcard can read back
+    #[derive(Serialize, Deserialize)]
+    pub(super) enum Value {
+        Null,
+        Bool(bool),
+        U64(u64),
+        I64(i64),
+        F64(f64),
+        String(String),
+        Array(Vec<Value>),
+        Object(Vec<(String, Value)>),
+        Bytes(Vec<u8>),
+    }
+
+    impl From<&data::Value> for Value {
+        fn from(val: &data::Value) -> Self {
+            if let Some(bytes) = binary::from_value(val) {
+                return Self::Bytes(bytes);
+            }
+            match val {
+                data::Value::Null => Self::Null,
+                data::Value::Bool(b) => Self::Bool(*b),
+                data::Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                    (Some(n), _) => Self::U64(n),
+                    (None, Some(n)) => Self::I64(n),
+                    _ => Self::F64(n.as_f64().unwrap_or_default()),
+                },
+                data::Value::String(s) => Self::String(s.clone()),
+                data::Value::Array(vals) => Self::Array(vals.iter().map(Self::from).collect()),
+                data::Value::Object(map) => Self::Object(
+                    map.iter()
+                        .map(|(key, val)| (key.clone(), Self::from(val)))
+                        .collect(),
+                ),
+            }
+        }
+    }
+
+    impl From<Value> for data::Value {
+        fn from(val: Value) -> Self {
+            match val {
+                Value::Null => Self::Null,
+                Value::Bool(b) => Self::Bool(b),
+                Value::U64(n) => n.into(),
+                Value::I64(n) => n.into(),
+                Value::F64(n) => n.into(),
+                Value::String(s) => Self::String(s),
+                Value::Array(vals) => Self::Array(vals.into_iter().map(Self::from).collect()),
+                Value::Object(pairs) => Self::Object(
+                    pairs
+                        .into_iter()
+                        .map(|(key, val)| (key, val.into()))
+                        .collect(),
+                ),
+                Value::Bytes(bytes) => binary::to_value(&bytes),
+            }
+        }
+    }
+}
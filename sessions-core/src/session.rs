@@ -1,16 +1,24 @@
 use std::{
     fmt,
+    future::Future,
+    io,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError, RwLockReadGuard, RwLockWriteGuard,
     },
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use crate::{
     anyhow,
-    data::{from_value, to_value, DeserializeOwned, Serialize},
-    Config, Data, Result, Storage,
+    binary::{self, RawValue},
+    csrf,
+    data::{from_value, to_value, DeserializeOwned, Serialize, Value},
+    data_schema, data_version, keys,
+    session_core::{stamp, SessionCore, HIDDEN, STAMPS},
+    to_millis, ttl, Config, Data, DataError, DataTooLarge, Entry, Flash, Level, Metadata,
+    NamespacedSession, Payload, Result, SessionExport, SessionStatus, StateSnapshot, Storage,
+    Unsupported, Values,
 };
 
 /// Session
@@ -18,41 +26,140 @@ use crate::{
 pub struct Session {
     /// Session's Config
     config: Arc<Config>,
-    /// Session's status, 0: inited, 1: saved, 2: renewed, 3: destroyed
-    status: Arc<AtomicUsize>,
-    /// Session's Data status, false: unchanged, true: changed
-    data_status: Arc<AtomicBool>,
-    /// Session's `SessionBeer`
-    beer: Arc<RwLock<SessionBeer>>,
+    /// Session's status, changes and `SessionBeer`
+    core: Arc<SessionCore>,
+    /// The cookie is sent even if the session is unchanged, see [`Session::reissue_cookie`]
+    reissue: Arc<AtomicBool>,
+    /// The `Cookie` header of the request, see [`Session::set_request_cookies`]
+    request_cookies: Arc<Mutex<String>>,
 }
 
 impl Session {
     /// Creates new `Session` with `id` `status` and `Config`
-    pub fn new(id: &str, status: usize, config: Arc<Config>) -> Self {
+    pub fn new(id: &str, status: SessionStatus, config: Arc<Config>) -> Self {
         Self {
             config,
-            status: Arc::new(AtomicUsize::new(status)),
-            data_status: Arc::new(AtomicBool::new(false)),
-            beer: Arc::new(RwLock::new(SessionBeer {
-                id: id.into(),
-                data: Data::new(),
-            })),
+            core: Arc::new(SessionCore::new(id, status)),
+            reissue: Arc::default(),
+            request_cookies: Arc::default(),
         }
     }
 
-    /// Reads the session expires or cookie max_age
+    /// Gets the config of the session
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Gets the storage of the session, see [`Config::storage`]
+    pub fn store(&self) -> Arc<dyn Storage> {
+        self.config.storage()
+    }
+
+    /// Gets the name of the session cookie
+    pub fn name(&self) -> &str {
+        &self.config.cookie.name
+    }
+
+    /// Reconstructs an exported session, all its keys are marked as changed
+    /// so it's written on the next save
+    pub fn import(export: SessionExport, config: Arc<Config>) -> Self {
+        let session = Self::new(&export.id, SessionStatus::Unchanged, config);
+        let mut data = export.data;
+        for key in STAMPS {
+            data.remove(*key);
+        }
+        session.core.changes.mark_all(data.keys());
+        if export.version > 0 {
+            data.insert(keys::VERSION.into(), export.version.into());
+        }
+        for (key, time) in [
+            (keys::CREATED_AT, export.created_at),
+            (keys::LAST_ACCESSED, export.last_accessed),
+        ] {
+            if let Some(time) = time {
+                data.insert(key.into(), to_millis(time).into());
+            }
+        }
+        // A new session isn't destroyed
+        if let Ok(mut beer) = session.beer_mut() {
+            beer.data = data;
+        }
+        session
+    }
+
+    /// Exports the state in a serializable form
+    pub fn export(&self) -> Result<SessionExport> {
+        let snapshot = self.snapshot()?;
+        let meta = self.metadata()?;
+        Ok(SessionExport {
+            id: snapshot.id,
+            status: self.status(),
+            version: snapshot.version,
+            created_at: meta.created_at,
+            last_accessed: meta.last_accessed,
+            data: snapshot.data,
+        })
+    }
+
+    /// Reads how long the storage keeps the session, the override of
+    /// [`set_max_age`](Session::set_max_age) or [`Config::store_ttl`]
     pub fn max_age(&self) -> Duration {
-        self.config.max_age()
+        self.max_age_override()
+            .unwrap_or_else(|| self.config.store_ttl())
+    }
+
+    /// Reads the `Max-Age` of the session cookie, the override of
+    /// [`set_max_age`](Session::set_max_age) or [`Config::cookie_max_age`]
+    pub fn cookie_max_age(&self) -> Option<Duration> {
+        self.max_age_override()
+            .or_else(|| self.config.cookie_max_age())
+    }
+
+    /// Overrides how long this session lives, in the storage and in the browser,
+    /// e.g. for a "remember me" login
+    ///
+    /// It's stored with the data, so it survives reloads once saved.
+    pub fn set_max_age(&self, max_age: Duration) -> Result<()> {
+        let secs = max_age.as_secs();
+        let prev = self
+            .beer_mut()?
+            .data
+            .insert(keys::MAX_AGE.into(), secs.into());
+        if prev.as_ref().and_then(Value::as_u64) != Some(secs) {
+            self.core.changes.mark(keys::MAX_AGE);
+        }
+        Ok(())
+    }
+
+    /// Removes the override of [`set_max_age`](Session::set_max_age), reverting
+    /// to the config
+    pub fn clear_max_age(&self) -> Result<()> {
+        if self.beer_mut()?.data.remove(keys::MAX_AGE).is_some() {
+            self.core.changes.mark(keys::MAX_AGE);
+        }
+        Ok(())
+    }
+
+    fn max_age_override(&self) -> Option<Duration> {
+        self.beer()
+            .ok()?
+            .data
+            .get(keys::MAX_AGE)
+            .and_then(Value::as_u64)
+            .map(Duration::from_secs)
     }
 
     /// Reads the session beer
+    ///
+    /// A lock poisoned by a panic is recovered, the data is only replaced as a whole
+    /// so it's never left half-written.
     pub fn beer(&self) -> Result<RwLockReadGuard<'_, SessionBeer>> {
-        self.beer.read().map_err(|e| anyhow!(e.to_string()))
+        Ok(self.core.beer())
     }
 
-    /// Writes the session beer
+    /// Writes the session beer, fails once the session is destroyed
     pub fn beer_mut(&self) -> Result<RwLockWriteGuard<'_, SessionBeer>> {
-        self.beer.write().map_err(|e| anyhow!(e.to_string()))
+        self.core.beer_mut()
     }
 
     /// Reads the session state
@@ -66,6 +173,108 @@ impl Session {
         Ok(())
     }
 
+    /// Writes the state loaded from the store, remembering it to skip saving it unchanged
+    ///
+    /// Data of an older schema version is [migrated](Config::migrate), the keys
+    /// it changed are marked to write it on the next save.
+    pub(crate) fn load_data(&self, data: Data) -> Result<()> {
+        self.core.remember(Some(&data));
+        match self.config.migrate(&data)? {
+            Some(migrated) => {
+                self.core.changes.mark_all(changed_keys(&data, &migrated));
+                self.set_data(migrated)
+            }
+            None => self.set_data(data),
+        }
+    }
+
+    /// Replaces the whole state, the previous keys are removed
+    ///
+    /// Fails with a [`DataError::NotAnObject`] if the state isn't serialized to an object.
+    /// The version, times and schema versions of the session are kept.
+    pub fn set_state<T: Serialize>(&self, state: T) -> Result<()> {
+        let mut state = match to_value(state) {
+            Ok(Value::Object(state)) => state,
+            Ok(_) => return Err(DataError::NotAnObject.into()),
+            Err(source) => {
+                return Err(DataError::Serialize {
+                    key: String::new(),
+                    source,
+                }
+                .into())
+            }
+        };
+        let mut beer = self.beer_mut()?;
+        for key in HIDDEN {
+            match beer.data.get(*key) {
+                Some(val) => state.insert((*key).into(), val.clone()),
+                None => state.remove(*key),
+            };
+        }
+        self.check_size(&state)?;
+        self.core.changes.mark_all(
+            beer.data
+                .keys()
+                .chain(state.keys())
+                .filter(|key| !HIDDEN.contains(&key.as_str())),
+        );
+        beer.data = state;
+        Ok(())
+    }
+
+    /// Takes the whole state, leaving the session empty
+    ///
+    /// The version, times and schema versions of the session are kept.
+    pub fn take_state(&self) -> Result<Data> {
+        let mut beer = self.beer_mut()?;
+        let mut state = Data::new();
+        for key in HIDDEN {
+            if let Some(val) = beer.data.remove(*key) {
+                state.insert((*key).into(), val);
+            }
+        }
+        let state = std::mem::replace(&mut beer.data, state);
+        self.core.changes.mark_all(state.keys());
+        Ok(state)
+    }
+
+    /// Takes a snapshot of the data, to be restored later on with [`restore`](Session::restore)
+    pub fn snapshot(&self) -> Result<StateSnapshot> {
+        let beer = self.beer()?;
+        Ok(StateSnapshot {
+            id: beer.id.clone(),
+            version: data_version(&beer.data),
+            data: beer
+                .data
+                .iter()
+                .filter(|(key, _)| !HIDDEN.contains(&key.as_str()))
+                .map(|(key, val)| (key.clone(), val.clone()))
+                .collect(),
+        })
+    }
+
+    /// Replaces the data by the snapshot at once, the changed keys are marked
+    ///
+    /// The keys written on save are kept, so the next save doesn't go back in versions.
+    pub fn restore(&self, snapshot: StateSnapshot) -> Result<()> {
+        let mut beer = self.beer_mut()?;
+        let mut data = snapshot.data;
+        for key in HIDDEN {
+            data.remove(*key);
+            if let Some(val) = beer.data.get(*key) {
+                data.insert(key.to_string(), val.clone());
+            }
+        }
+        self.core.changes.mark_all(
+            beer.data
+                .keys()
+                .chain(data.keys())
+                .filter(|key| beer.data.get(*key) != data.get(*key)),
+        );
+        beer.data = data;
+        Ok(())
+    }
+
     /// Gets the session id
     pub fn id(&self) -> Result<String> {
         Ok(self.beer()?.id.clone())
@@ -74,94 +283,1066 @@ impl Session {
     /// Gets the session id
     pub fn set_id(&self, id: &str) -> Result<()> {
         self.beer_mut()?.id = id.into();
+        self.core.remember(None);
         Ok(())
     }
 
-    /// Gets the session data status
+    /// Gets the version of the session data, bumped on every save
+    pub fn version(&self) -> Result<u64> {
+        Ok(data_version(&self.beer()?.data))
+    }
+
+    /// Checks if the session was never saved, unlike a session loaded from the store
+    pub fn is_new(&self) -> bool {
+        matches!(self.version(), Ok(0))
+    }
+
+    /// Gets the session metadata, written on every save
+    pub fn metadata(&self) -> Result<Metadata> {
+        Ok(Metadata::from_data(&self.beer()?.data))
+    }
+
+    /// Gets when the session was first saved, `None` if unknown
+    pub fn created_at(&self) -> Option<SystemTime> {
+        self.metadata().ok()?.created_at
+    }
+
+    /// Gets when the session was last saved, `None` if unknown
+    pub fn last_accessed(&self) -> Option<SystemTime> {
+        self.metadata().ok()?.last_accessed
+    }
+
+    /// Gets when the stored session expires, `None` if it was never saved or is destroyed
+    ///
+    /// It's the first of the storage TTL, the idle timeout and the absolute timeout.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        if self.status() == SessionStatus::Destroyed {
+            return None;
+        }
+        let metadata = self.metadata().ok()?;
+        let expires_at = metadata.last_accessed? + self.max_age();
+        Some(
+            self.config
+                .timeout_at(&metadata)
+                .map_or(expires_at, |timeout_at| timeout_at.min(expires_at)),
+        )
+    }
+
+    /// Gets the time left until the stored session expires, zero once it has expired
+    pub fn expires_in(&self) -> Option<Duration> {
+        Some(
+            self.expires_at()?
+                .duration_since(self.config.now())
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Gets the session data status, `true` if any key changed since the last save
     pub fn data_status(&self) -> bool {
-        self.data_status.load(Ordering::Relaxed)
+        !self.core.changes.is_empty()
+    }
+
+    /// Gets the keys changed since the session was loaded or last saved, in no particular order
+    pub fn changed_keys(&self) -> Vec<String> {
+        self.core.changes.keys()
     }
 
     /// Gets the session status
-    pub fn status(&self) -> usize {
-        self.status.load(Ordering::Relaxed)
+    pub fn status(&self) -> SessionStatus {
+        self.core.status()
+    }
+
+    /// Gets the session status as an integer, 0: unchanged, 1: saved, 2: renewed,
+    /// 3: destroyed, 4: purged
+    #[deprecated(
+        since = "0.2.0",
+        note = "use `status`, which returns a `SessionStatus`"
+    )]
+    pub fn status_code(&self) -> usize {
+        u8::from(self.status()).into()
+    }
+
+    pub(crate) fn set_status(&self, status: SessionStatus) {
+        self.core.set_status(status);
     }
 
     /// Gets a value by the key
-    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
-        from_value(self.data().ok()?.get(key).cloned()?).ok()
+    ///
+    /// Fails with a [`DataError::Deserialize`] if the value isn't a `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.get_value(key).map(|val| decode(key, val)).transpose()
     }
 
-    /// Sets a value by the key
-    pub fn set<T: DeserializeOwned + Serialize>(&self, key: &str, val: T) -> Option<T> {
-        let prev = self
-            .beer_mut()
-            .ok()?
+    /// Gets a value by the key without deserializing it
+    ///
+    /// A value whose TTL ran out is pruned and reads as missing.
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        let now = self.now();
+        let beer = self.beer().ok()?;
+        let val = beer.data.get(key)?;
+        if ttl::is_expired(val, now) {
+            drop(beer);
+            self.prune(key, now);
+            return None;
+        }
+        ttl::open(val, now).cloned()
+    }
+
+    /// Gets a value by the key without deserializing it, telling bytes apart
+    pub fn get_raw(&self, key: &str) -> Option<RawValue> {
+        self.get_value(key).map(RawValue::from)
+    }
+
+    /// Gets the bytes of the key, `None` if the value is missing or isn't bytes
+    pub fn get_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        binary::from_value(&self.get_value(key)?)
+    }
+
+    /// Sets bytes by the key, returns the previous value without deserializing it
+    ///
+    /// Serializers with byte strings, as `MessagePack` and `Cbor`, store the bytes
+    /// themselves, JSON stores their base64, see [`binary`].
+    pub fn set_bytes(&self, key: &str, bytes: impl AsRef<[u8]>) -> Result<Option<Value>> {
+        self.set_value(key, binary::to_value(bytes.as_ref()))
+    }
+
+    /// Sets a value which expires after the `ttl`, before the session does
+    ///
+    /// The expiry is stored along with the value, the expired value is pruned
+    /// when it's accessed. It reads as missing from [`get`](Session::get),
+    /// [`take`](Session::take), [`remove`](Session::remove), [`contains_key`](Session::contains_key),
+    /// [`get_many`](Session::get_many) and [`for_each`](Session::for_each) and
+    /// the accessors built on them, the other ones see the value wrapped with its expiry.
+    pub fn set_with_ttl<T: Serialize>(&self, key: &str, val: T, ttl: Duration) -> Result<()> {
+        let expires_at = self.now().saturating_add(ttl.as_millis() as u64);
+        self.set_value(key, ttl::seal(encode(key, val)?, expires_at))?;
+        Ok(())
+    }
+
+    /// Removes the value of the key if it expired
+    fn prune(&self, key: &str, now: u64) {
+        if let Ok(mut beer) = self.beer_mut() {
+            if matches!(beer.data.get(key), Some(val) if ttl::is_expired(val, now)) {
+                beer.data.remove(key);
+                self.core.changes.mark(key);
+            }
+        }
+    }
+
+    fn now(&self) -> u64 {
+        to_millis(self.config.now())
+    }
+
+    /// Sets a value by the key, returns the previous one without deserializing it
+    pub fn set<T: Serialize>(&self, key: &str, val: T) -> Result<Option<Value>> {
+        self.set_value(key, encode(key, val)?)
+    }
+
+    /// Sets a value by the key without serializing it, returns the previous one
+    ///
+    /// Fails with a [`DataTooLarge`] error, leaving the data as is, if the whole data
+    /// would exceed [`Config::max_data_size`].
+    pub fn set_value(&self, key: &str, val: Value) -> Result<Option<Value>> {
+        self.core.set_value(key, val, |data| self.check_size(data))
+    }
+
+    /// Sets a value by the key, returns the previous one as a `T`
+    ///
+    /// The value is set even if the previous one isn't a `T`, which fails with
+    /// a [`DataError::Deserialize`].
+    pub fn replace<T>(&self, key: &str, val: T) -> Result<Option<T>>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let prev = self.set(key, val)?;
+        prev.map(|prev| decode(key, prev)).transpose()
+    }
+
+    /// Checks if the key has a value, without deserializing it
+    pub fn contains_key(&self, key: &str) -> bool {
+        let now = self.now();
+        self.beer()
+            .map(|beer| matches!(beer.data.get(key), Some(val) if !ttl::is_expired(val, now)))
+            .unwrap_or(false)
+    }
+
+    /// Checks if any of the keys has a value, the expired ones aren't counted
+    pub fn has_any(&self, keys: &[&str]) -> bool {
+        let now = self.now();
+        self.beer()
+            .map(|beer| {
+                keys.iter().any(
+                    |key| matches!(beer.data.get(*key), Some(val) if !ttl::is_expired(val, now)),
+                )
+            })
+            .unwrap_or(false)
+    }
+
+    /// Checks if the session has no values, the keys stamped on save aren't counted
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Counts the values, the keys stamped on save aren't counted
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.for_each(|_, _| len += 1);
+        len
+    }
+
+    /// Gets the keys, the keys stamped on save are skipped
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        self.for_each(|key, _| keys.push(key.to_string()));
+        keys
+    }
+
+    /// Iterates over the key-value pairs cloned out of the session,
+    /// the keys stamped on save are skipped
+    pub fn iter(&self) -> impl Iterator<Item = (String, Value)> {
+        let mut pairs = Vec::new();
+        self.for_each(|key, val| pairs.push((key.to_string(), val.clone())));
+        pairs.into_iter()
+    }
+
+    /// Iterates over the key-value pairs cloned out of the session, telling bytes
+    /// apart, the keys stamped on save are skipped
+    pub fn iter_raw(&self) -> impl Iterator<Item = (String, RawValue)> {
+        self.iter().map(|(key, val)| (key, val.into()))
+    }
+
+    /// Calls `f` on each key-value pair under the read lock, without cloning them,
+    /// the keys stamped on save are skipped
+    pub fn for_each<F: FnMut(&str, &Value)>(&self, mut f: F) {
+        let now = self.now();
+        if let Ok(beer) = self.beer() {
+            beer.data
+                .iter()
+                .filter(|(key, _)| !HIDDEN.contains(&key.as_str()))
+                .filter_map(|(key, val)| Some((key, ttl::open(val, now)?)))
+                .for_each(|(key, val)| f(key, val));
+        }
+    }
+
+    /// Gets the values of the keys in one pass, `None` for the missing ones
+    pub fn get_many(&self, keys: &[&str]) -> Vec<Option<Value>> {
+        match self.beer() {
+            Ok(beer) => {
+                let now = self.now();
+                keys.iter()
+                    .map(|key| ttl::open(beer.data.get(*key)?, now).cloned())
+                    .collect()
+            }
+            Err(_) => vec![None; keys.len()],
+        }
+    }
+
+    /// Gets the typed values of a tuple of keys in one pass,
+    /// `None` for the missing or wrong-typed ones
+    pub fn get_all<'a, T: Values<'a>>(&self, keys: T::Keys) -> Result<T::Output> {
+        Ok(T::read(&self.beer()?.data, keys))
+    }
+
+    /// Gets a value by the key, or inserts the one computed by `f` if it's missing
+    ///
+    /// `f` is only called on a miss. An existing value which can't be deserialized
+    /// into `T` is an error and is left untouched.
+    pub fn get_or_insert_with<T, F>(&self, key: &str, f: F) -> Result<T>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce() -> T,
+    {
+        let mut beer = self.beer_mut()?;
+        if let Some(val) = beer.data.get(key) {
+            return decode(key, val.clone());
+        }
+        let val = f();
+        beer.data.insert(key.into(), encode(key, &val)?);
+        self.core.changes.mark(key);
+        Ok(val)
+    }
+
+    /// Gets a value by the key, or inserts the `default` if it's missing
+    pub fn get_or_insert<T>(&self, key: &str, default: T) -> Result<T>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        self.get_or_insert_with(key, || default)
+    }
+
+    /// Gets the entry of the key for in-place modification
+    pub fn entry(&self, key: &str) -> Result<Entry<'_>> {
+        Ok(Entry::new(self.beer_mut()?, &self.core.changes, key))
+    }
+
+    /// Modifies a value as a `T` under the write lock, returns the result of `f`
+    ///
+    /// Fails if the value is missing or can't be deserialized into `T`.
+    pub fn modify<T, F, R>(&self, key: &str, f: F) -> Result<R>
+    where
+        T: DeserializeOwned + Serialize,
+        F: FnOnce(&mut T) -> R,
+    {
+        self.apply(key, || None, f)
+    }
+
+    /// Modifies a value as a `T` under the write lock, starting from the default
+    /// of `T` if it's missing, returns the result of `f`
+    pub fn modify_or_default<T, F, R>(&self, key: &str, f: F) -> Result<R>
+    where
+        T: DeserializeOwned + Serialize + Default,
+        F: FnOnce(&mut T) -> R,
+    {
+        self.apply(key, || Some(T::default()), f)
+    }
+
+    fn apply<T, D, F, R>(&self, key: &str, default: D, f: F) -> Result<R>
+    where
+        T: DeserializeOwned + Serialize,
+        D: FnOnce() -> Option<T>,
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut beer = self.beer_mut()?;
+        let mut val = match beer.data.get(key) {
+            Some(val) => decode(key, val.clone())?,
+            None => default().ok_or_else(|| anyhow!("missing session value `{}`", key))?,
+        };
+        // A panicking `f` leaves the stored value as is, it only sees a copy
+        let res = f(&mut val);
+        let val = encode(key, val)?;
+        if beer.data.get(key) != Some(&val) {
+            beer.data.insert(key.into(), val);
+            self.core.changes.mark(key);
+        }
+        Ok(res)
+    }
+
+    /// Spreads the fields of a value serializing to an object as top-level keys,
+    /// overwriting the existing ones
+    pub fn extend<T: Serialize>(&self, val: T) -> Result<()> {
+        match to_value(val)? {
+            Value::Object(map) => self.extend_pairs(map),
+            _ => Err(anyhow!("session values must extend from an object")),
+        }
+    }
+
+    /// Inserts the key-value pairs, overwriting the existing keys
+    pub fn extend_pairs<I>(&self, pairs: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        let mut beer = self.beer_mut()?;
+        for (key, val) in pairs {
+            if beer.data.get(&key) != Some(&val) {
+                self.core.changes.mark(&key);
+                beer.data.insert(key, val);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a value, returns it
+    ///
+    /// The value is removed even if it isn't a `T`, which fails with
+    /// a [`DataError::Deserialize`].
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.remove_value(key)?
+            .map(|prev| decode(key, prev))
+            .transpose()
+    }
+
+    /// Removes a value, returns it without deserializing it
+    pub fn remove_value(&self, key: &str) -> Result<Option<Value>> {
+        let prev = self.core.remove_value(key)?;
+        Ok(prev.and_then(|prev| ttl::into_open(prev, self.now())))
+    }
+
+    /// Removes a value and returns it, so it can be consumed only once
+    ///
+    /// The value is removed even if it isn't a `T`, which fails with
+    /// a [`DataError::Deserialize`].
+    pub fn take<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.take_raw(key)?.map(|val| decode(key, val)).transpose()
+    }
+
+    /// Removes a value and returns it without deserializing it
+    pub fn take_raw(&self, key: &str) -> Result<Option<Value>> {
+        let prev = match self.beer_mut()?.data.remove(key) {
+            Some(prev) => prev,
+            None => return Ok(None),
+        };
+        self.core.changes.mark(key);
+        Ok(ttl::into_open(prev, self.now()))
+    }
+
+    /// Adds a flash message, kept until it's taken by [`take_flashes`](Session::take_flashes)
+    pub fn flash<T: Serialize>(&self, level: Level, message: T) -> Result<()> {
+        let flash = to_value(Flash { level, message })?;
+        let mut beer = self.beer_mut()?;
+        match beer
             .data
-            .insert(key.into(), to_value(val).ok()?);
-        self.data_status.store(true, Ordering::SeqCst);
-        from_value(prev?).ok()
+            .entry(keys::FLASH)
+            .or_insert_with(|| Value::Array(Vec::new()))
+        {
+            Value::Array(flashes) => flashes.push(flash),
+            val => *val = Value::Array(vec![flash]),
+        }
+        self.core.changes.mark(keys::FLASH);
+        Ok(())
+    }
+
+    /// Takes the flash messages in the order they were added, so they're shown once
+    ///
+    /// Fails if a message can't be deserialized into `T`, the messages are removed anyway.
+    pub fn take_flashes<T: DeserializeOwned>(&self) -> Result<Vec<Flash<T>>> {
+        match self.take_raw(keys::FLASH)? {
+            Some(flashes) => decode(keys::FLASH, flashes),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Adds `by` to a counter under the write lock and returns the new value
+    ///
+    /// A missing key counts as zero. Fails if the value isn't an `i64` or on overflow.
+    pub fn increment(&self, key: &str, by: i64) -> Result<i64> {
+        self.count(key, |n| n.checked_add(by))
     }
 
-    /// Removes a value
-    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
-        let prev = self.beer_mut().ok()?.data.remove(key)?;
-        self.data_status.store(true, Ordering::SeqCst);
-        from_value(prev).ok()
+    /// Subtracts `by` from a counter under the write lock and returns the new value,
+    /// see [`increment`](Session::increment)
+    pub fn decrement(&self, key: &str, by: i64) -> Result<i64> {
+        self.count(key, |n| n.checked_sub(by))
     }
 
-    /// Clears the state
+    /// Adds `by` to a counter, clamping at the `i64` bounds instead of failing on overflow
+    pub fn saturating_increment(&self, key: &str, by: i64) -> Result<i64> {
+        self.count(key, |n| Some(n.saturating_add(by)))
+    }
+
+    /// Subtracts `by` from a counter, clamping at the `i64` bounds instead of failing on overflow
+    pub fn saturating_decrement(&self, key: &str, by: i64) -> Result<i64> {
+        self.count(key, |n| Some(n.saturating_sub(by)))
+    }
+
+    fn count<F: FnOnce(i64) -> Option<i64>>(&self, key: &str, f: F) -> Result<i64> {
+        let mut beer = self.beer_mut()?;
+        let n = match beer.data.get(key) {
+            Some(val) => val
+                .as_i64()
+                .ok_or_else(|| anyhow!("session value `{}` isn't an integer", key))?,
+            None => 0,
+        };
+        let n = f(n).ok_or_else(|| anyhow!("session counter `{}` overflowed", key))?;
+        if beer.data.insert(key.into(), n.into()) != Some(n.into()) {
+            self.core.changes.mark(key);
+        }
+        Ok(n)
+    }
+
+    /// Gets the CSRF token, generating a random one if the session has none
+    pub fn csrf_token(&self) -> Result<String> {
+        let mut beer = self.beer_mut()?;
+        if let Some(token) = beer.data.get(keys::CSRF).and_then(Value::as_str) {
+            return Ok(token.to_string());
+        }
+        let token = csrf::token()?;
+        beer.data.insert(keys::CSRF.into(), token.clone().into());
+        self.core.changes.mark(keys::CSRF);
+        Ok(token)
+    }
+
+    /// Checks the token against the CSRF token of the session in constant time
+    ///
+    /// With [`Config::rotate_csrf`] a verified token is replaced by a new one.
+    pub fn verify_csrf(&self, token: &str) -> bool {
+        let mut beer = match self.beer_mut() {
+            Ok(beer) => beer,
+            Err(_) => return false,
+        };
+        let verified = matches!(
+            beer.data.get(keys::CSRF).and_then(Value::as_str),
+            Some(expected) if csrf::eq(expected.as_bytes(), token.as_bytes())
+        );
+        if verified && self.config.rotate_csrf {
+            match csrf::token() {
+                Ok(next) => {
+                    beer.data.insert(keys::CSRF.into(), next.into());
+                    self.core.changes.mark(keys::CSRF);
+                }
+                Err(_) => return false,
+            }
+        }
+        verified
+    }
+
+    /// Clears the state, the version, times and schema versions of the session are kept
     pub fn clear(&self) -> Result<()> {
-        self.beer_mut()?.data.clear();
-        self.data_status.store(true, Ordering::SeqCst);
+        self.core.clear()
+    }
+
+    /// Moves the value of the `old` key to the `new` key, returns whether it moved
+    ///
+    /// Nothing moves if there's no `old` value, or if there's a `new` value
+    /// and `overwrite` is `false`.
+    pub fn rename_key(&self, old: &str, new: &str, overwrite: bool) -> Result<bool> {
+        let mut beer = self.beer_mut()?;
+        if old == new || (!overwrite && beer.data.contains_key(new)) {
+            return Ok(false);
+        }
+        let val = match beer.data.remove(old) {
+            Some(val) => val,
+            None => return Ok(false),
+        };
+        beer.data.insert(new.into(), val);
+        self.core.changes.mark(old);
+        self.core.changes.mark(new);
+        Ok(true)
+    }
+
+    /// Runs the migration over the raw data if its stored schema version is
+    /// older than `version`, returns whether it ran
+    ///
+    /// The schema version is stored under [`keys::SCHEMA`] along with the migrated
+    /// data, so a migration runs once per loaded session. If it fails, the data is
+    /// left as is. A [`Config::migration`] migrates every session when it's loaded
+    /// instead, under its own [`keys::SCHEMA_VERSION`].
+    pub fn migrate<F>(&self, version: u64, f: F) -> Result<bool>
+    where
+        F: FnOnce(&mut Data) -> Result<()>,
+    {
+        let mut beer = self.beer_mut()?;
+        if data_schema(&beer.data) >= version {
+            return Ok(false);
+        }
+        let mut data = beer.data.clone();
+        f(&mut data)?;
+        data.insert(keys::SCHEMA.into(), version.into());
+        self.core.changes.mark_all(changed_keys(&beer.data, &data));
+        beer.data = data;
+        Ok(true)
+    }
+
+    /// Removes the values whose keys start with the prefix
+    pub(crate) fn remove_prefixed(&self, prefix: &str) -> Result<()> {
+        let mut beer = self.beer_mut()?;
+        let keys = beer
+            .data
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            beer.data.remove(key);
+        }
+        self.core.changes.mark_all(&keys);
+        Ok(())
+    }
+
+    /// Gets a view of the session whose keys are prefixed with the namespace
+    pub fn namespace(&self, name: &str) -> NamespacedSession {
+        NamespacedSession::new(self.clone(), name)
+    }
+
+    /// Binds the session to a user, so it can be found by `Storage::sessions_for`
+    pub fn bind_user(&self, user_id: &str) -> Result<()> {
+        let prev = self
+            .beer_mut()?
+            .data
+            .insert(keys::USER.into(), user_id.into());
+        if prev.as_ref().and_then(Value::as_str) != Some(user_id) {
+            self.core.changes.mark(keys::USER);
+        }
+        Ok(())
+    }
+
+    /// Gets the user id bound to the session
+    pub fn user(&self) -> Option<String> {
+        self.get(keys::USER).ok().flatten()
+    }
+
+    /// Unbinds the session from its user
+    pub fn unbind_user(&self) -> Result<()> {
+        if self.beer_mut()?.data.remove(keys::USER).is_some() {
+            self.core.changes.mark(keys::USER);
+        }
         Ok(())
     }
 
     /// Saves the current state to the store
+    ///
+    /// Persists whenever a key changed since the last save, so read-only requests
+    /// and identical writes don't touch the store. A [fresh](Session::is_fresh)
+    /// session is only persisted with [`Config::save_uninitialized`], so it gets
+    /// no cookie either. Destroyed sessions are never saved.
     pub async fn save(&self) -> Result<()> {
-        if self.status.fetch_add(1, Ordering::SeqCst) == 0 {
+        let changes = match self.core.take_changes(self.config.save_uninitialized) {
+            Some(changes) => changes,
+            None => return Ok(()),
+        };
+        let created = self.version()? == 0;
+        if let Err(e) = self.persist().await {
+            self.core.changes.restore(changes);
+            return Err(e);
+        }
+        self.core.mark_saved();
+        self.run_save_hook(created).await
+    }
+
+    /// Runs the [`on_create`](crate::SessionHooks::on_create) hook after the first
+    /// save, the [`on_save`](crate::SessionHooks::on_save) hook otherwise
+    async fn run_save_hook(&self, created: bool) -> Result<()> {
+        let id = self.id()?;
+        if created {
             self.config
-                .set(&self.id()?, self.data()?.clone(), self.max_age())
-                .await?;
+                .run_hook("on_create", |hooks| hooks.on_create(&id))
+                .await
+        } else {
+            self.config
+                .run_hook("on_save", |hooks| hooks.on_save(&id))
+                .await
+        }
+    }
+
+    /// Writes the stamped state to the store
+    async fn persist(&self) -> Result<()> {
+        let state = self.next_state()?;
+        self.config
+            .set(&self.id()?, state.clone(), self.max_age())
+            .await?;
+        self.core.commit(&state)
+    }
+
+    /// Checks if the session was never saved and no key was written since it
+    /// was created, so it needs neither a save nor a cookie
+    pub fn is_fresh(&self) -> bool {
+        self.status() == SessionStatus::Unchanged && self.core.changes.is_empty() && self.is_new()
+    }
+
+    /// Saves the current state to the store, fails with a [`Conflict`](crate::Conflict)
+    /// error if the stored state was changed since it was loaded
+    ///
+    /// On conflict the app can reload the state, merge its changes and save again.
+    pub async fn save_with_conflict_check(&self) -> Result<()> {
+        if !matches!(
+            self.status(),
+            SessionStatus::Destroyed | SessionStatus::Purged
+        ) {
+            let changes = self.core.changes.take();
+            let state = self.next_state()?;
+            if let Err(e) = self
+                .config
+                .set_if_version(
+                    &self.id()?,
+                    state.clone(),
+                    self.max_age(),
+                    data_version(&state) - 1,
+                )
+                .await
+            {
+                self.core.changes.restore(changes);
+                return Err(e);
+            }
+            self.core.commit(&state)?;
+            self.core.mark_saved();
+            self.run_save_hook(data_version(&state) == 1).await?;
+        }
+        Ok(())
+    }
+
+    /// Clones the state stamped with the next version and the access time
+    fn next_state(&self) -> Result<Data> {
+        let mut data = self.data()?;
+        if self.config.schema_version > 0 {
+            data.insert(
+                keys::SCHEMA_VERSION.into(),
+                self.config.schema_version.into(),
+            );
+        }
+        let state = stamp(data, self.config.now());
+        self.check_size(&state)?;
+        Ok(state)
+    }
+
+    /// Gets the approximate size of the data serialized, in bytes,
+    /// see [`Config::data_size`]
+    pub fn approx_size(&self) -> usize {
+        self.beer()
+            .map_or(0, |beer| self.config.data_size(&beer.data))
+    }
+
+    fn check_size(&self, data: &Data) -> Result<()> {
+        if let Some(limit) = self.config.max_data_size.filter(|limit| *limit > 0) {
+            let size = self.config.data_size(data);
+            if size > limit {
+                return Err(DataTooLarge { size, limit }.into());
+            }
         }
         Ok(())
     }
 
     /// Renews the new state
     pub async fn renew(&mut self) -> Result<()> {
-        if self.status.load(Ordering::Relaxed) < 2 {
-            self.config.remove(&self.id()?).await?;
+        if !matches!(
+            self.status(),
+            SessionStatus::Renewed | SessionStatus::Destroyed
+        ) {
+            let old = self.id()?;
+            self.config.remove(&old).await?;
             self.beer_mut()?.data.clear();
-            self.set_id(&self.config.generate())?;
+            self.core.changes.clear();
+            let new = self.config.generate();
+            self.set_id(&new)?;
+            self.config.set(&new, self.data()?, self.max_age()).await?;
+            self.set_status(SessionStatus::Renewed);
             self.config
-                .set(&self.id()?, self.data()?, self.max_age())
+                .run_hook("on_renew", |hooks| hooks.on_renew(&old, &new))
                 .await?;
-            self.status.store(2, Ordering::SeqCst);
         }
         Ok(())
     }
 
+    /// Renews the session id, keeping the state, e.g. against session fixation on login
+    ///
+    /// The stored state is renamed to the new id, so there is no window where
+    /// neither id exists. Storages which can't rename get the state written under
+    /// the new id before the old one is removed. Unsaved changes are written under the new id.
+    pub async fn renew_keeping_data(&self) -> Result<()> {
+        if !matches!(
+            self.status(),
+            SessionStatus::Destroyed | SessionStatus::Purged
+        ) {
+            let old = self.id()?;
+            let new = self.config.generate();
+            match self.config.rename(&old, &new).await {
+                Err(e) if e.is::<Unsupported>() => {
+                    if let Some(state) = self.config.get(&old).await? {
+                        self.config.set(&new, state, self.max_age()).await?;
+                        self.config.remove(&old).await?;
+                    }
+                }
+                res => res?,
+            }
+            self.set_id(&new)?;
+            let changes = self.core.changes.take();
+            if !changes.is_empty() {
+                if let Err(e) = self.persist().await {
+                    self.core.changes.restore(changes);
+                    return Err(e);
+                }
+            }
+            self.set_status(SessionStatus::Renewed);
+            self.config
+                .run_hook("on_renew", |hooks| hooks.on_renew(&old, &new))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Runs `f` while holding the session lock, then saves and releases the lock
+    ///
+    /// The state is reloaded from the store once the lock is acquired, so `f` sees
+    /// the changes of the previous holder.
+    pub async fn with_lock<F, Fut, T>(&self, ttl: Duration, f: F) -> Result<T>
+    where
+        F: FnOnce(Session) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let id = self.id()?;
+        let guard = self.config.lock(&id, ttl).await?;
+        let res = async {
+            if let Some(data) = self.config.get(&id).await? {
+                self.load_data(data)?;
+            }
+            let output = f(self.clone()).await?;
+            self.save().await?;
+            Ok(output)
+        }
+        .await;
+        guard.release().await?;
+        res
+    }
+
+    /// Extends the lifetime of the stored session without rewriting its data,
+    /// the data status is left as is
+    ///
+    /// Does nothing on destroyed sessions or sessions which were never saved.
+    /// The session becomes [`Purged`](SessionStatus::Purged) if the store doesn't have it anymore.
+    pub async fn touch(&self) -> Result<()> {
+        self.touch_for(self.max_age()).await.map(|_| ())
+    }
+
+    async fn touch_for(&self, exp: Duration) -> Result<bool> {
+        if matches!(
+            self.status(),
+            SessionStatus::Destroyed | SessionStatus::Purged
+        ) || self.version()? == 0
+        {
+            return Ok(false);
+        }
+        let now = to_millis(self.config.now());
+        if self.config.touch(&self.id()?, now, exp).await? {
+            self.beer_mut()?.data.set_accessed(now);
+            Ok(true)
+        } else {
+            self.set_status(SessionStatus::Purged);
+            Ok(false)
+        }
+    }
+
+    /// Touches an unchanged stored session when [`Config::rolling`] is on, so
+    /// it expires `max_age` after the last request instead of the last save
+    ///
+    /// Sessions accessed less than [`Config::rolling_interval`] ago are left
+    /// alone. A touched session becomes [`Saved`](SessionStatus::Saved), so the
+    /// cookie is reissued with a fresh `Max-Age`. The storage TTL never extends
+    /// past the [`Config::absolute_timeout`]. Returns whether it was touched.
+    pub async fn roll(&self) -> Result<bool> {
+        if !self.config.rolling || self.status() != SessionStatus::Unchanged {
+            return Ok(false);
+        }
+        let metadata = self.metadata()?;
+        let now = self.config.now();
+        if let (Some(interval), Some(last_accessed)) =
+            (self.config.rolling_interval, metadata.last_accessed)
+        {
+            if now < last_accessed + interval {
+                return Ok(false);
+            }
+        }
+        let mut exp = self.max_age();
+        if let Some(timeout) = self.config.absolute_timeout {
+            if let Some(created_at) = metadata.created_at {
+                let left = (created_at + timeout)
+                    .duration_since(now)
+                    .unwrap_or_default();
+                exp = exp.min(left);
+            }
+        }
+        let touched = self.touch_for(exp).await?;
+        if touched {
+            self.set_status(SessionStatus::Saved);
+        }
+        Ok(touched)
+    }
+
     /// Destroys the current state from store
+    ///
+    /// The data is cleared too: afterwards reads see an empty session, and writes
+    /// are ignored or fail with an error. Send [`removal_cookie`](Session::removal_cookie)
+    /// so the browser drops the cookie.
     pub async fn destroy(&self) -> Result<()> {
-        if self.status.load(Ordering::Relaxed) < 3 {
-            self.config.remove(&self.id()?).await?;
-            self.status.store(3, Ordering::SeqCst);
+        if self.status() != SessionStatus::Destroyed {
+            let id = self.id()?;
+            self.config.remove(&id).await?;
+            self.core.destroy()?;
+            self.config
+                .run_hook("on_destroy", |hooks| hooks.on_destroy(&id))
+                .await?;
         }
         Ok(())
     }
+
+    /// Checks if the session was destroyed
+    pub fn is_destroyed(&self) -> bool {
+        self.status() == SessionStatus::Destroyed
+    }
+
+    /// Renders a `Set-Cookie` value which makes the browser drop the session cookie
+    ///
+    /// It has the name, domain and path of the session cookie, or browsers would keep it.
+    pub fn removal_cookie(&self) -> String {
+        self.config.cookie().to_removal_cookie()
+    }
+
+    /// Sends the session cookie even if the session is unchanged, e.g. when the
+    /// cookie of the request was only valid under a previous key
+    pub fn reissue_cookie(&self) {
+        self.reissue.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks if the session cookie is sent even if the session is unchanged,
+    /// see [`reissue_cookie`](Session::reissue_cookie)
+    pub fn is_reissued(&self) -> bool {
+        self.reissue.load(Ordering::SeqCst)
+    }
+
+    /// Remembers the `Cookie` header of the request, so [`set_cookies`] removes
+    /// the chunks of the session cookie it no longer needs
+    ///
+    /// [`set_cookies`]: Session::set_cookies
+    pub fn set_request_cookies(&self, cookie_header: &str) {
+        *self
+            .request_cookies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = cookie_header.into();
+    }
+
+    /// Renders the `Set-Cookie` value the response needs for the session status
+    ///
+    /// The session cookie once saved or renewed, or [reissued](Session::reissue_cookie),
+    /// with the [`cookie_max_age`], the [`removal_cookie`] once destroyed or purged,
+    /// `None` if unchanged.
+    ///
+    /// [`cookie_max_age`]: Session::cookie_max_age
+    /// [`removal_cookie`]: Session::removal_cookie
+    pub fn cookie_header(&self) -> Result<Option<String>> {
+        Ok(match self.status() {
+            SessionStatus::Unchanged if !self.is_reissued() => None,
+            SessionStatus::Unchanged | SessionStatus::Saved | SessionStatus::Renewed => {
+                let cookie = self.config.cookie();
+                Some(match self.max_age_override() {
+                    Some(max_age) => cookie
+                        .clone()
+                        .with_max_age(max_age)
+                        .to_set_cookie(&self.id()?)?,
+                    None => cookie.to_set_cookie(&self.id()?)?,
+                })
+            }
+            SessionStatus::Destroyed | SessionStatus::Purged => Some(self.removal_cookie()),
+        })
+    }
+
+    /// Renders the `Set-Cookie` values the response needs for the session status,
+    /// like [`cookie_header`], split across chunks past the [`chunk_size`]
+    ///
+    /// The chunks of the [request cookies](Session::set_request_cookies) which
+    /// aren't replaced are removed, see [`CookieOptions::to_set_cookies`].
+    ///
+    /// [`cookie_header`]: Session::cookie_header
+    /// [`chunk_size`]: crate::CookieOptions::chunk_size
+    /// [`CookieOptions::to_set_cookies`]: crate::CookieOptions::to_set_cookies
+    pub fn set_cookies(&self) -> Result<Vec<String>> {
+        Ok(match self.status() {
+            SessionStatus::Unchanged if !self.is_reissued() => Vec::new(),
+            SessionStatus::Unchanged | SessionStatus::Saved | SessionStatus::Renewed => {
+                let request_cookies = self
+                    .request_cookies
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone();
+                let cookie = self.config.cookie();
+                match self.max_age_override() {
+                    Some(max_age) => cookie
+                        .clone()
+                        .with_max_age(max_age)
+                        .to_set_cookies(&self.id()?, &request_cookies)?,
+                    None => cookie.to_set_cookies(&self.id()?, &request_cookies)?,
+                }
+            }
+            SessionStatus::Destroyed | SessionStatus::Purged => vec![self.removal_cookie()],
+        })
+    }
+
+    /// Converts the cookie the response needs for the session status to a cookie
+    /// of the cookie crate, like [`cookie_header`](Session::cookie_header) renders it
+    ///
+    /// For the cookie jars of frameworks, its value is sealed with the keys of the
+    /// [`CookieOptions`](crate::CookieOptions), read it back with
+    /// [`CookieOptions::verify_value`](crate::CookieOptions::verify_value).
+    #[cfg(feature = "cookie")]
+    pub fn to_cookie(&self) -> Result<Option<cookie::Cookie<'static>>> {
+        Ok(match self.status() {
+            SessionStatus::Unchanged if !self.is_reissued() => None,
+            SessionStatus::Unchanged | SessionStatus::Saved | SessionStatus::Renewed => {
+                let cookie = self.config.cookie();
+                Some(match self.max_age_override() {
+                    Some(max_age) => cookie
+                        .clone()
+                        .with_max_age(max_age)
+                        .to_sealed_cookie(&self.id()?)?,
+                    None => cookie.to_sealed_cookie(&self.id()?)?,
+                })
+            }
+            SessionStatus::Destroyed | SessionStatus::Purged => {
+                let mut cookie = self.config.cookie().to_cookie("");
+                cookie.set_max_age(time::Duration::ZERO);
+                cookie.set_expires(time::OffsetDateTime::UNIX_EPOCH);
+                Some(cookie)
+            }
+        })
+    }
+}
+
+impl Drop for Session {
+    /// Warns when the last handle of a session is dropped with unsaved changes,
+    /// `Drop` can't save it as saving is async
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.core) == 1
+            && !self.core.changes.is_empty()
+            && self.status() != SessionStatus::Destroyed
+        {
+            log::warn!(
+                "session dropped with unsaved changes to {:?}, call `Session::save` or finish it with `SessionGuard`",
+                self.core.changes.keys()
+            );
+        }
+    }
 }
 
 impl fmt::Debug for Session {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Session")
-            .field("status", &self.status)
-            .field("data_status", &self.data_status)
-            .field("beer", &self.beer)
+            .field("status", &self.status())
+            .field("reissue", &self.is_reissued())
+            .field("changes", &self.core.changes)
+            .field("beer", &*self.core.beer())
             .field("config", &self.config)
             .finish()
     }
 }
 
+/// Counts the bytes of the data serialized as JSON, without buffering them
+pub(crate) fn data_size(data: &Data) -> usize {
+    struct Counter(usize);
+
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    serde_json::to_writer(&mut counter, data).map_or(0, |_| counter.0)
+}
+
+/// Serializes the value of the key
+pub(crate) fn encode<T: Serialize>(key: &str, val: T) -> Result<Value> {
+    to_value(val).map_err(|source| {
+        DataError::Serialize {
+            key: key.into(),
+            source,
+        }
+        .into()
+    })
+}
+
+/// Deserializes the value of the key
+pub(crate) fn decode<T: DeserializeOwned>(key: &str, val: Value) -> Result<T> {
+    from_value(val).map_err(|source| {
+        DataError::Deserialize {
+            key: key.into(),
+            source,
+        }
+        .into()
+    })
+}
+
+/// Gets the keys whose values differ between the two states
+fn changed_keys<'a>(old: &'a Data, new: &'a Data) -> impl Iterator<Item = &'a String> {
+    old.iter()
+        .filter(move |(key, val)| new.get(*key) != Some(val))
+        .chain(
+            new.iter()
+                .filter(move |(key, val)| old.get(*key) != Some(val)),
+        )
+        .map(|(key, _)| key)
+}
+
 /// A Session Beer
 #[derive(Debug, Clone, Default)]
 pub struct SessionBeer {
@@ -0,0 +1,220 @@
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    changes::Changes, data::Value, data_version, keys, serializer, to_millis, Data, DataError,
+    Result, SessionBeer, SessionStatus,
+};
+
+/// Keys written by [`stamp`] on every save
+pub(crate) const STAMPS: &[&str] = &[keys::VERSION, keys::CREATED_AT, keys::LAST_ACCESSED];
+
+/// The stamps and the schema versions, kept out of the values and across
+/// replacements of the state, the schema versions still count as changes
+pub(crate) const HIDDEN: &[&str] = &[
+    keys::VERSION,
+    keys::CREATED_AT,
+    keys::LAST_ACCESSED,
+    keys::SCHEMA,
+    keys::SCHEMA_VERSION,
+];
+
+/// The state of a session, whatever storage it's persisted to
+///
+/// [`Session`](crate::Session) and [`LocalSession`](crate::local::LocalSession)
+/// share it, so they track changes, stamp, save, clear and destroy alike.
+#[derive(Debug)]
+pub(crate) struct SessionCore {
+    /// Session's status, a [`SessionStatus`] as `u8`
+    status: AtomicU8,
+    /// The keys changed since the session was loaded or last saved
+    pub(crate) changes: Changes,
+    /// The [`digest`] of the state as loaded or last saved, `None` if unknown
+    stored: Mutex<Option<[u8; 32]>>,
+    /// Session's `SessionBeer`
+    beer: RwLock<SessionBeer>,
+}
+
+impl SessionCore {
+    pub(crate) fn new(id: &str, status: SessionStatus) -> Self {
+        Self {
+            status: AtomicU8::new(status.into()),
+            changes: Changes::default(),
+            stored: Mutex::default(),
+            beer: RwLock::new(SessionBeer {
+                id: id.into(),
+                data: Data::new(),
+            }),
+        }
+    }
+
+    /// Reads the session beer, a lock poisoned by a panic is recovered
+    pub(crate) fn beer(&self) -> RwLockReadGuard<'_, SessionBeer> {
+        self.beer.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Writes the session beer, fails once the session is destroyed
+    pub(crate) fn beer_mut(&self) -> Result<RwLockWriteGuard<'_, SessionBeer>> {
+        let beer = self.beer.write().unwrap_or_else(PoisonError::into_inner);
+        if self.status() == SessionStatus::Destroyed {
+            return Err(DataError::Destroyed.into());
+        }
+        Ok(beer)
+    }
+
+    pub(crate) fn status(&self) -> SessionStatus {
+        SessionStatus::try_from(self.status.load(Ordering::SeqCst))
+            .expect("only `SessionStatus` values are stored")
+    }
+
+    pub(crate) fn set_status(&self, status: SessionStatus) {
+        self.status.store(status.into(), Ordering::SeqCst);
+    }
+
+    /// Marks a new or loaded session as saved
+    pub(crate) fn mark_saved(&self) {
+        let _ = self.status.compare_exchange(
+            SessionStatus::Unchanged.into(),
+            SessionStatus::Saved.into(),
+            Ordering::SeqCst,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        data_version(&self.beer().data)
+    }
+
+    /// Remembers the state in the store, `None` if it's unknown
+    pub(crate) fn remember(&self, data: Option<&Data>) {
+        *self.stored.lock().unwrap_or_else(PoisonError::into_inner) = data.map(digest);
+    }
+
+    /// Checks if the state is semantically the one in the store
+    fn is_stored(&self) -> bool {
+        let stored = *self.stored.lock().unwrap_or_else(PoisonError::into_inner);
+        stored.is_some() && stored == Some(digest(&self.beer().data))
+    }
+
+    /// Sets a value, marking the key only if the value differs
+    ///
+    /// The data is left as is if `check` rejects it.
+    pub(crate) fn set_value<F>(&self, key: &str, val: Value, check: F) -> Result<Option<Value>>
+    where
+        F: FnOnce(&Data) -> Result<()>,
+    {
+        let mut beer = self.beer_mut()?;
+        if beer.data.get(key) == Some(&val) {
+            return Ok(Some(val));
+        }
+        let prev = beer.data.insert(key.into(), val);
+        if let Err(e) = check(&beer.data) {
+            match prev {
+                Some(prev) => beer.data.insert(key.into(), prev),
+                None => beer.data.remove(key),
+            };
+            return Err(e);
+        }
+        self.changes.mark(key);
+        Ok(prev)
+    }
+
+    /// Removes a value, marking the key if there was one
+    pub(crate) fn remove_value(&self, key: &str) -> Result<Option<Value>> {
+        let prev = self.beer_mut()?.data.remove(key);
+        if prev.is_some() {
+            self.changes.mark(key);
+        }
+        Ok(prev)
+    }
+
+    /// Clears the values, the [`HIDDEN`] keys are kept
+    pub(crate) fn clear(&self) -> Result<()> {
+        let mut beer = self.beer_mut()?;
+        self.changes.mark_all(
+            beer.data
+                .keys()
+                .filter(|key| !HIDDEN.contains(&key.as_str())),
+        );
+        beer.data.retain(|key, _| HIDDEN.contains(&key.as_str()));
+        Ok(())
+    }
+
+    /// Takes the changed keys when the state has to be written, `None` when the
+    /// save is skipped
+    ///
+    /// Destroyed sessions are never saved, neither are sessions whose changes
+    /// were written back to the stored values. A [fresh](crate::Session::is_fresh)
+    /// session is only saved with `save_uninitialized`. The keys are restored
+    /// with [`Changes::restore`] if the write fails.
+    pub(crate) fn take_changes(&self, save_uninitialized: bool) -> Option<HashSet<String>> {
+        let status = self.status();
+        if matches!(status, SessionStatus::Destroyed | SessionStatus::Purged) {
+            return None;
+        }
+        let changes = self.changes.take();
+        if changes.is_empty()
+            && (status != SessionStatus::Unchanged || self.version() > 0 || !save_uninitialized)
+        {
+            return None;
+        }
+        // The changed keys were written back to the values in the store
+        if !changes.is_empty() && self.is_stored() {
+            return None;
+        }
+        Some(changes)
+    }
+
+    /// Remembers the saved state and copies its metadata back to the session
+    pub(crate) fn commit(&self, state: &Data) -> Result<()> {
+        self.remember(Some(state));
+        commit(state, &mut self.beer_mut()?.data);
+        Ok(())
+    }
+
+    /// Clears the data once the stored state was removed, later writes fail
+    pub(crate) fn destroy(&self) -> Result<()> {
+        self.beer_mut()?.data.clear();
+        self.set_status(SessionStatus::Destroyed);
+        self.changes.clear();
+        Ok(())
+    }
+}
+
+/// Hashes the canonical JSON of the state without the keys stamped on save, equal
+/// for semantically equal states
+fn digest(data: &Data) -> [u8; 32] {
+    let mut data = data.clone();
+    for key in STAMPS {
+        data.remove(*key);
+    }
+    Sha256::digest(serializer::canonical(&data)).into()
+}
+
+/// Stamps the state with the next version and the access time
+pub(crate) fn stamp(mut state: Data, now: SystemTime) -> Data {
+    let now = to_millis(now);
+    let version = data_version(&state) + 1;
+    state.insert(keys::VERSION.into(), version.into());
+    state.entry(keys::CREATED_AT).or_insert_with(|| now.into());
+    state.insert(keys::LAST_ACCESSED.into(), now.into());
+    state
+}
+
+/// Copies the metadata of the saved state to the data
+fn commit(state: &Data, data: &mut Data) {
+    for key in HIDDEN {
+        if let Some(val) = state.get(*key) {
+            data.insert(key.to_string(), val.clone());
+        }
+    }
+}
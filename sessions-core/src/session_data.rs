@@ -0,0 +1,13 @@
+use crate::{Result, Session};
+
+/// A struct whose fields are stored as top-level keys of the session data
+///
+/// Usually implemented with `#[derive(SessionData)]` from the `sessions` crate,
+/// using the field names as keys.
+pub trait SessionData: Sized {
+    /// Reads the fields from the session, missing fields are `Default::default()`
+    fn load(session: &Session) -> Result<Self>;
+
+    /// Writes the fields to the session
+    fn store(&self, session: &Session) -> Result<()>;
+}
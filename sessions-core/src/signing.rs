@@ -0,0 +1,55 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{csrf, Key};
+
+fn mac(key: &Key, value: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("any key length");
+    mac.update(value.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The length of the MAC of signed session ids, truncated to 128 bits
+const ID_TAG_LEN: usize = 16;
+
+/// Appends the MAC of the value, `value.base64(hmac_sha256(value, key))`
+pub(crate) fn sign(key: &Key, value: &str) -> String {
+    sign_truncated(key, value, usize::MAX)
+}
+
+/// Gets the value of a signed value if its MAC is valid
+pub(crate) fn unsign<'a>(key: &Key, signed: &'a str) -> Option<&'a str> {
+    unsign_truncated(key, signed, usize::MAX)
+}
+
+/// Appends the truncated MAC of the session id, `id.base64(hmac_sha256(id, key)[..16])`
+pub(crate) fn sign_id(key: &Key, id: &str) -> String {
+    sign_truncated(key, id, ID_TAG_LEN)
+}
+
+/// Gets the session id of a signed one if its truncated MAC is valid
+pub(crate) fn unsign_id<'a>(key: &Key, signed: &'a str) -> Option<&'a str> {
+    unsign_truncated(key, signed, ID_TAG_LEN)
+}
+
+fn sign_truncated(key: &Key, value: &str, len: usize) -> String {
+    let mut mac = mac(key, value);
+    mac.truncate(len);
+    format!(
+        "{}.{}",
+        value,
+        base64::encode_config(mac, base64::URL_SAFE_NO_PAD)
+    )
+}
+
+fn unsign_truncated<'a>(key: &Key, signed: &'a str, len: usize) -> Option<&'a str> {
+    let (value, tag) = signed.split_at(signed.rfind('.')?);
+    let tag = base64::decode_config(&tag[1..], base64::URL_SAFE_NO_PAD).ok()?;
+    let mut mac = mac(key, value);
+    mac.truncate(len);
+    if csrf::eq(&tag, &mac) {
+        Some(value)
+    } else {
+        None
+    }
+}
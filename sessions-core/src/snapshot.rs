@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Data;
+
+/// An owned copy of the session data, see [`Session::snapshot`]
+///
+/// Snapshots are serializable, so they can be stashed under a key of another
+/// session, e.g. to return to an admin session after impersonating a user.
+///
+/// [`Session::snapshot`]: crate::Session::snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// The id of the session the snapshot was taken from
+    pub id: String,
+    /// The version of the session data when the snapshot was taken
+    pub version: u64,
+    /// The data, without the keys written on save
+    pub data: Data,
+}
@@ -0,0 +1,48 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{anyhow, Error};
+
+/// The status of a [`Session`] in the current request
+///
+/// Integrations match on it to decide whether to set, refresh or delete the cookie.
+///
+/// [`Session`]: crate::Session
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum SessionStatus {
+    /// Created or loaded, not saved yet
+    #[default]
+    Unchanged = 0,
+    /// Saved to the store
+    Saved = 1,
+    /// Renewed under a new id
+    Renewed = 2,
+    /// Destroyed, the data is cleared and the cookie should be removed
+    Destroyed = 3,
+    /// The stored session is gone, e.g. it expired or was destroyed by another request
+    Purged = 4,
+}
+
+impl From<SessionStatus> for u8 {
+    fn from(status: SessionStatus) -> Self {
+        status as u8
+    }
+}
+
+impl TryFrom<u8> for SessionStatus {
+    type Error = Error;
+
+    fn try_from(n: u8) -> Result<Self, Self::Error> {
+        Ok(match n {
+            0 => Self::Unchanged,
+            1 => Self::Saved,
+            2 => Self::Renewed,
+            3 => Self::Destroyed,
+            4 => Self::Purged,
+            n => return Err(anyhow!("invalid session status {}", n)),
+        })
+    }
+}
@@ -1,19 +1,140 @@
 use std::{fmt::Debug, time::Duration};
 
-use crate::{async_trait, Data, Result};
+use futures_timer::Delay;
+
+use crate::{async_trait, lock, Conflict, Data, LockGuard, Metadata, Payload, Result, Unsupported};
+
+/// How long to wait before retrying to acquire a held lock
+const LOCK_RETRY: Duration = Duration::from_millis(10);
 
 /// A Storage Trait
+///
+/// Storages persist the session [`Data`] by default, byte-oriented storages persist
+/// `Vec<u8>` [`record`](crate::record)s and are wrapped in [`Serialized`](crate::Serialized).
 #[async_trait]
-pub trait Storage: Debug + Send + Sync + 'static {
+pub trait Storage<P = Data>: Debug + Send + Sync + 'static
+where
+    P: Payload,
+{
     /// Get a data from storage by the key
-    async fn get(&self, key: &str) -> Result<Option<Data>>;
+    async fn get(&self, key: &str) -> Result<Option<P>>;
+
+    /// Get the metadata of a session from storage by the key
+    ///
+    /// Storages should override it if they can read it without loading the data.
+    async fn metadata(&self, key: &str) -> Result<Option<Metadata>> {
+        Ok(self.get(key).await?.as_ref().map(Payload::metadata))
+    }
 
     /// Set a session to storage
-    async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()>;
+    async fn set(&self, key: &str, val: P, exp: Duration) -> Result<()>;
+
+    /// Set a session to storage only if the stored version is still `version`
+    ///
+    /// Fails with a [`Conflict`] error otherwise. The default implementation
+    /// isn't atomic, storages should override it.
+    async fn set_if_version(&self, key: &str, val: P, exp: Duration, version: u64) -> Result<()> {
+        let found = self
+            .get(key)
+            .await?
+            .as_ref()
+            .map_or(0, |val| val.metadata().version);
+        if found != version {
+            return Err(Conflict {
+                expected: version,
+                found,
+            }
+            .into());
+        }
+        self.set(key, val, exp).await
+    }
 
     /// Remove a data from storage by the key
     async fn remove(&self, key: &str) -> Result<()>;
 
+    /// Extend the expiry of the data and set its last accessed time, without rewriting it
+    ///
+    /// Returns `false` if the key doesn't exist. The default implementation
+    /// loads and sets the data again, storages should override it.
+    async fn touch(&self, key: &str, accessed: u64, exp: Duration) -> Result<bool> {
+        match self.get(key).await? {
+            Some(mut val) => {
+                val.set_accessed(accessed);
+                self.set(key, val, exp).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Move the data from the `old` key to the `new` key, keeping its expiry
+    ///
+    /// Does nothing if `old` doesn't exist, fails if `new` already exists.
+    async fn rename(&self, _old: &str, _new: &str) -> Result<()> {
+        Err(Unsupported {
+            operation: "renaming",
+        }
+        .into())
+    }
+
+    /// Get all session ids bound to the user
+    async fn sessions_for(&self, _user_id: &str) -> Result<Vec<String>> {
+        Err(Unsupported {
+            operation: "indexing sessions by user",
+        }
+        .into())
+    }
+
+    /// Remove all sessions bound to the user, returns the number of removed sessions
+    async fn destroy_for(&self, _user_id: &str) -> Result<u64> {
+        Err(Unsupported {
+            operation: "indexing sessions by user",
+        }
+        .into())
+    }
+
+    /// Try to acquire the lock on the key for the `token` holder, expiring after `ttl`
+    async fn try_lock(&self, _key: &str, _token: &str, _ttl: Duration) -> Result<bool> {
+        Err(Unsupported {
+            operation: "locking",
+        }
+        .into())
+    }
+
+    /// Release the lock on the key if it's still held by the `token` holder
+    async fn unlock(&self, _key: &str, _token: &str) -> Result<bool> {
+        Err(Unsupported {
+            operation: "locking",
+        }
+        .into())
+    }
+
+    /// Acquire an advisory lock on the key, waits until it's available
+    ///
+    /// The lock expires after `ttl` if it's not released.
+    async fn lock(&self, key: &str, ttl: Duration) -> Result<LockGuard<'_, P>>
+    where
+        Self: Sized,
+    {
+        let token = lock::token();
+        while !self.try_lock(key, &token, ttl).await? {
+            Delay::new(LOCK_RETRY).await;
+        }
+        Ok(LockGuard::new(self, key, token))
+    }
+
+    /// Check that the storage is reachable
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Purge expired data from storage, returns the number of removed keys
+    ///
+    /// Storages which expire keys natively don't need to do anything here.
+    async fn cleanup(&self) -> Result<u64> {
+        Ok(0)
+    }
+
     /// Reset the storage and remove all keys
     async fn reset(&self) -> Result<()> {
         Ok(())
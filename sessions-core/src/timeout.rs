@@ -0,0 +1,17 @@
+use std::{future::Future, time::Duration};
+
+use futures_timer::Delay;
+use futures_util::future::{select, Either};
+
+use crate::{anyhow, Result};
+
+/// Awaits the future, failing if it doesn't complete within `dur`
+pub(crate) async fn timeout<F>(dur: Duration, fut: F) -> Result<F::Output>
+where
+    F: Future,
+{
+    match select(Box::pin(fut), Delay::new(dur)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(anyhow!("operation timed out after {:?}", dur)),
+    }
+}
@@ -0,0 +1,50 @@
+use crate::data::{Map, Value};
+
+/// The expiry of a value set with a TTL, in milliseconds since the unix epoch
+const EXPIRES_AT: &str = "_expires_at";
+/// The value set with a TTL
+const VALUE: &str = "_value";
+
+/// Wraps the value in an envelope recording its expiry
+pub(crate) fn seal(val: Value, expires_at: u64) -> Value {
+    let mut envelope = Map::with_capacity(2);
+    envelope.insert(EXPIRES_AT.into(), expires_at.into());
+    envelope.insert(VALUE.into(), val);
+    Value::Object(envelope)
+}
+
+/// Gets the expiry of an envelope, `None` for plain values
+fn expires_at(val: &Value) -> Option<u64> {
+    match val {
+        Value::Object(map) if map.len() == 2 && map.contains_key(VALUE) => {
+            map.get(EXPIRES_AT)?.as_u64()
+        }
+        _ => None,
+    }
+}
+
+/// Checks if the value is an envelope which expired at `now`
+pub(crate) fn is_expired(val: &Value, now: u64) -> bool {
+    matches!(expires_at(val), Some(expires_at) if expires_at <= now)
+}
+
+/// Unwraps the value, `None` if it expired at `now`
+pub(crate) fn open(val: &Value, now: u64) -> Option<&Value> {
+    match expires_at(val) {
+        Some(expires_at) if expires_at <= now => None,
+        Some(_) => val.get(VALUE),
+        None => Some(val),
+    }
+}
+
+/// Unwraps the owned value, `None` if it expired at `now`
+pub(crate) fn into_open(val: Value, now: u64) -> Option<Value> {
+    match expires_at(&val) {
+        Some(expires_at) if expires_at <= now => None,
+        Some(_) => match val {
+            Value::Object(mut map) => map.remove(VALUE),
+            _ => unreachable!("envelopes are objects"),
+        },
+        None => Some(val),
+    }
+}
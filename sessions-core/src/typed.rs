@@ -0,0 +1,121 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use crate::{
+    data::{from_value, DeserializeOwned, Serialize, Value},
+    Result, Session,
+};
+
+/// A [`Session`] whose whole state is read as a `T`
+///
+/// The state is loaded into `T` on first access and written back on [`save`],
+/// an empty session starts from `T::default()`.
+/// Untyped access keeps working on the inner session: on save the fields of `T`
+/// overwrite the keys they are serialized to, other keys are kept as is.
+/// So untyped changes to the keys of `T` made after the first typed access are lost.
+///
+/// [`save`]: TypedSession::save
+pub struct TypedSession<T> {
+    session: Session,
+    state: Mutex<Option<T>>,
+    /// The stored state couldn't be read as a `T` and was replaced by its default
+    defaulted: AtomicBool,
+    /// The state was borrowed mutably since it was loaded
+    changed: AtomicBool,
+}
+
+impl<T> TypedSession<T>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    /// Creates new `TypedSession` over the session
+    pub fn new(session: Session) -> Self {
+        Self {
+            session,
+            state: Mutex::new(None),
+            defaulted: AtomicBool::new(false),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    /// Gets the untyped session
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Unwraps the untyped session, the typed state is dropped without being written back
+    pub fn into_inner(self) -> Session {
+        self.session
+    }
+
+    /// Checks if the stored state couldn't be read as a `T`, so `T::default()` was used
+    pub fn is_defaulted(&self) -> bool {
+        self.defaulted.load(Ordering::SeqCst)
+    }
+
+    /// Gets a clone of the state
+    pub fn get(&self) -> Result<T>
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    /// Reads the state
+    pub fn with<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let state = self.load()?;
+        Ok(f(state.as_ref().expect("state is loaded")))
+    }
+
+    /// Modifies the state, it's written back to the session on save
+    pub fn with_mut<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut state = self.load()?;
+        self.changed.store(true, Ordering::SeqCst);
+        Ok(f(state.as_mut().expect("state is loaded")))
+    }
+
+    /// Writes the state back to the session and saves it to the store
+    pub async fn save(&self) -> Result<()> {
+        if self.changed.swap(false, Ordering::SeqCst) {
+            let state = self.load()?;
+            self.session.extend(state.as_ref())?;
+        }
+        self.session.save().await
+    }
+
+    fn load(&self) -> Result<MutexGuard<'_, Option<T>>> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.is_none() {
+            *state = Some(if self.session.is_empty() {
+                T::default()
+            } else {
+                from_value(Value::Object(self.session.data()?)).unwrap_or_else(|_| {
+                    self.defaulted.store(true, Ordering::SeqCst);
+                    T::default()
+                })
+            });
+        }
+        Ok(state)
+    }
+}
+
+impl<T> fmt::Debug for TypedSession<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedSession")
+            .field("session", &self.session)
+            .field("defaulted", &self.defaulted)
+            .field("changed", &self.changed)
+            .finish()
+    }
+}
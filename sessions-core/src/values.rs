@@ -0,0 +1,43 @@
+use crate::{
+    data::{from_value, DeserializeOwned},
+    Data,
+};
+
+/// A tuple of types read from the session data at once, see [`Session::get_all`]
+///
+/// [`Session::get_all`]: crate::Session::get_all
+pub trait Values<'a> {
+    /// A tuple of keys, one per type
+    type Keys;
+    /// A tuple of optional values, `None` for a missing or wrong-typed value
+    type Output;
+
+    /// Reads the values of the keys from the data
+    fn read(data: &Data, keys: Self::Keys) -> Self::Output;
+}
+
+macro_rules! key {
+    ($T:ident) => { &'a str };
+}
+
+macro_rules! values {
+    ($($T:ident $k:ident),+) => {
+        impl<'a, $($T: DeserializeOwned),+> Values<'a> for ($($T,)+) {
+            type Keys = ($(key!($T),)+);
+            type Output = ($(Option<$T>,)+);
+
+            fn read(data: &Data, ($($k,)+): Self::Keys) -> Self::Output {
+                ($(data.get($k).cloned().and_then(|v| from_value::<$T>(v).ok()),)+)
+            }
+        }
+    };
+}
+
+values!(A a);
+values!(A a, B b);
+values!(A a, B b, C c);
+values!(A a, B b, C c, D d);
+values!(A a, B b, C c, D d, E e);
+values!(A a, B b, C c, D d, E e, F f);
+values!(A a, B b, C c, D d, E e, F f, G g);
+values!(A a, B b, C c, D d, E e, F f, G g, H h);
@@ -0,0 +1,19 @@
+use viz_core::{Error, FromRequest, IntoResponse, Request, StatusCode};
+
+use crate::Session;
+
+/// Extracts the session the `SessionTransform` loaded into the request,
+/// failing with `500 Internal Server Error` on routes without it
+impl FromRequest for Session {
+    type Error = Error;
+
+    async fn extract(req: &mut Request) -> Result<Self, Self::Error> {
+        req.extensions().get::<Session>().cloned().ok_or_else(|| {
+            let mut res =
+                "no session in the request, the `SessionTransform` isn't installed on this route"
+                    .into_response();
+            *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            Error::Responder(res)
+        })
+    }
+}
@@ -0,0 +1,187 @@
+//! Sessions Derive Macros
+
+#![forbid(unsafe_code, rust_2018_idioms)]
+#![deny(missing_debug_implementations, nonstandard_style)]
+#![warn(missing_docs, unreachable_pub)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta, Result,
+};
+
+/// Derives `SessionData` for a struct with named fields
+///
+/// Each field is stored under its name as a top-level key of the session data.
+/// The fields accept `#[session(...)]` attributes:
+///
+/// * `key = "..."` stores the field under another key
+/// * `skip` doesn't store the field, it's `Default::default()` on load
+/// * `flash` removes the key from the session when it's loaded, the value is stored
+///   again unless it's taken out of the struct
+///
+/// An accessor `field(&Session) -> Result<Option<T>>` and a setter
+/// `set_field(&Session, T) -> Result<Option<T>>` are generated for every stored field.
+#[proc_macro_derive(SessionData, attributes(session))]
+pub fn derive_session_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Field {
+    ident: syn::Ident,
+    ty: syn::Type,
+    key: String,
+    skip: bool,
+    flash: bool,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "SessionData can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "SessionData can only be derived for structs",
+            ))
+        }
+    };
+
+    let fields = named
+        .iter()
+        .map(|field| {
+            let ident = field.ident.clone().expect("named field");
+            let mut parsed = Field {
+                key: ident.to_string(),
+                ident,
+                ty: field.ty.clone(),
+                skip: false,
+                flash: false,
+            };
+            for attr in field.attrs.iter().filter(|a| a.path.is_ident("session")) {
+                parse_attr(attr, &mut parsed)?;
+            }
+            Ok(parsed)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let loads = fields.iter().map(
+        |Field {
+             ident,
+             key,
+             skip,
+             flash,
+             ..
+         }| {
+            if *skip {
+                quote! { #ident: ::core::default::Default::default() }
+            } else {
+                let raw = if *flash {
+                    quote! { session.take_raw(#key)? }
+                } else {
+                    quote! { session.get_many(&[#key]).pop().flatten() }
+                };
+                quote! {
+                    #ident: match #raw {
+                        ::core::option::Option::Some(val) => ::sessions::data::from_value(val)?,
+                        ::core::option::Option::None => ::core::default::Default::default(),
+                    }
+                }
+            }
+        },
+    );
+
+    let stores = fields
+        .iter()
+        .filter(|f| !f.skip)
+        .map(|Field { ident, key, .. }| {
+            quote! {
+                (#key.to_string(), ::sessions::data::to_value(&self.#ident)?)
+            }
+        });
+
+    let accessors = fields.iter().filter(|f| !f.skip).map(|Field { ident, ty, key, flash, .. }| {
+        let setter = format_ident!("set_{}", ident);
+        let get = if *flash {
+            quote! { session.take(#key) }
+        } else {
+            quote! { session.get(#key) }
+        };
+        let doc = format!("Gets the `{}` value of the session", key);
+        let set_doc = format!("Sets the `{}` value of the session", key);
+        quote! {
+            #[doc = #doc]
+            #vis fn #ident(session: &::sessions::Session) -> ::sessions::Result<::core::option::Option<#ty>> {
+                #get
+            }
+
+            #[doc = #set_doc]
+            #vis fn #setter(session: &::sessions::Session, val: #ty) -> ::sessions::Result<::core::option::Option<#ty>> {
+                session.replace(#key, val)
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::sessions::SessionData for #name #ty_generics #where_clause {
+            fn load(session: &::sessions::Session) -> ::sessions::Result<Self> {
+                ::core::result::Result::Ok(Self {
+                    #(#loads,)*
+                })
+            }
+
+            fn store(&self, session: &::sessions::Session) -> ::sessions::Result<()> {
+                session.extend_pairs(::std::vec![#(#stores),*])
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#accessors)*
+        }
+    })
+}
+
+fn parse_attr(attr: &Attribute, field: &mut Field) -> Result<()> {
+    let list = match attr.parse_meta()? {
+        Meta::List(list) => list,
+        meta => return Err(Error::new_spanned(meta, "expected `#[session(...)]`")),
+    };
+    for nested in list.nested {
+        match nested {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => field.skip = true,
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("flash") => field.flash = true,
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("key") => match nv.lit {
+                Lit::Str(key) => field.key = key.value(),
+                lit => return Err(Error::new_spanned(lit, "expected `key = \"...\"`")),
+            },
+            nested => {
+                return Err(Error::new_spanned(
+                    nested,
+                    "unknown session attribute, expected `key`, `skip` or `flash`",
+                ))
+            }
+        }
+    }
+    if field.skip && field.flash {
+        return Err(Error::new_spanned(
+            attr,
+            "a field can't be both `skip` and `flash`",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use hyper::{service::Service, Request, Response, StatusCode};
+use sessions_core::{apply_session, extract_session, Config, Result};
+
+/// Wraps a hyper service, loading the [`Session`] of each request into its
+/// extensions, then saving it and setting the cookie once the service responded
+///
+/// Each request goes through: the `Cookie` header is parsed and the session id
+/// verified, the session is loaded or created with [`extract_session`], the inner
+/// service is called, a changed session is saved and the cookie emitted with
+/// [`apply_session`].
+///
+/// The inner service owns the request and its extensions, the session it gets
+/// is a clone sharing its state with the one kept here, so the changes of the
+/// handler are saved even if it drops the request. A session which can't be
+/// loaded or saved answers `500 Internal Server Error`.
+///
+/// [`Session`]: sessions_core::Session
+#[derive(Clone, Debug)]
+pub struct SessionService<S> {
+    inner: S,
+    config: Arc<Config>,
+}
+
+impl<S> SessionService<S> {
+    /// Creates new `SessionService` over the service with the `Config`
+    pub fn new(inner: S, config: Arc<Config>) -> Self {
+        Self { inner, config }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SessionService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn call(&self, mut req: Request<ReqBody>) -> Self::Future {
+        let inner = self.inner.clone();
+        let session = extract_session(&req, &self.config);
+
+        Box::pin(async move {
+            let session = match session.await {
+                Ok(session) => session,
+                Err(e) => {
+                    log::error!("failed to load session: {}", e);
+                    return Ok(internal_error());
+                }
+            };
+            req.extensions_mut().insert(session.clone());
+
+            let mut res = inner.call(req).await?;
+
+            if let Err(e) = apply_session(&mut res, &session).await {
+                log::error!("failed to save session: {}", e);
+                return Ok(internal_error());
+            }
+            Ok(res)
+        })
+    }
+}
+
+fn internal_error<B: Default>() -> Response<B> {
+    let mut res = Response::new(B::default());
+    *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    res
+}
@@ -1,10 +1,12 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
     time::{Duration, Instant},
 };
 
-use sessions_core::{anyhow, async_trait, Data, Result, Storage};
+use sessions_core::{
+    anyhow, async_trait, data_version, keys, Conflict, Data, Metadata, Payload, Result, Storage,
+};
 
 #[derive(Clone, Debug)]
 struct State(Instant, Data);
@@ -13,23 +15,65 @@ impl State {
     fn new(i: Instant, d: Data) -> Self {
         Self(i, d)
     }
+
+    fn user(&self) -> Option<&str> {
+        self.1.get(keys::USER)?.as_str()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    states: HashMap<String, State>,
+    /// Session ids indexed by the bound user id
+    users: HashMap<String, HashSet<String>>,
+    /// Lock holder tokens and expiry by the locked key
+    locks: HashMap<String, (String, Instant)>,
+}
+
+impl Inner {
+    fn insert(&mut self, key: &str, state: State) {
+        self.remove(key);
+        if let Some(user) = state.user() {
+            self.users
+                .entry(user.to_string())
+                .or_default()
+                .insert(key.to_string());
+        }
+        self.states.insert(key.to_string(), state);
+    }
+
+    fn remove(&mut self, key: &str) -> Option<State> {
+        let state = self.states.remove(key)?;
+        if let Some(user) = state.user() {
+            if let Some(ids) = self.users.get_mut(user) {
+                ids.remove(key);
+                if ids.is_empty() {
+                    self.users.remove(user);
+                }
+            }
+        }
+        Some(state)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct MemoryStorage {
-    inner: Arc<RwLock<HashMap<String, State>>>,
+    inner: Arc<RwLock<Inner>>,
 }
 
 impl MemoryStorage {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Self { inner: Arc::default() }
+        Self {
+            inner: Arc::default(),
+        }
     }
 
-    fn read(&self) -> Result<RwLockReadGuard<'_, HashMap<String, State>>> {
+    fn read(&self) -> Result<RwLockReadGuard<'_, Inner>> {
         self.inner.read().map_err(|e| anyhow!(e.to_string()))
     }
 
-    fn write(&self) -> Result<RwLockWriteGuard<'_, HashMap<String, State>>> {
+    fn write(&self) -> Result<RwLockWriteGuard<'_, Inner>> {
         self.inner.write().map_err(|e| anyhow!(e.to_string()))
     }
 }
@@ -37,7 +81,7 @@ impl MemoryStorage {
 #[async_trait]
 impl Storage for MemoryStorage {
     async fn get(&self, key: &str) -> Result<Option<Data>> {
-        let state = self.read()?.get(key).cloned();
+        let state = self.read()?.states.get(key).cloned();
         if let Some(State(time, data)) = state {
             if time >= Instant::now() {
                 return Ok(Some(data));
@@ -49,9 +93,44 @@ impl Storage for MemoryStorage {
         Ok(None)
     }
 
+    async fn metadata(&self, key: &str) -> Result<Option<Metadata>> {
+        let now = Instant::now();
+        Ok(self
+            .read()?
+            .states
+            .get(key)
+            .filter(|State(time, _)| *time >= now)
+            .map(|State(_, data)| Metadata::from_data(data)))
+    }
+
     async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
         self.write()?
-            .insert(key.to_string(), State::new(Instant::now() + exp, val));
+            .insert(key, State::new(Instant::now() + exp, val));
+        Ok(())
+    }
+
+    async fn set_if_version(
+        &self,
+        key: &str,
+        val: Data,
+        exp: Duration,
+        version: u64,
+    ) -> Result<()> {
+        let now = Instant::now();
+        let mut inner = self.write()?;
+        let found = inner
+            .states
+            .get(key)
+            .filter(|State(time, _)| *time >= now)
+            .map_or(0, |State(_, data)| data_version(data));
+        if found != version {
+            return Err(Conflict {
+                expected: version,
+                found,
+            }
+            .into());
+        }
+        inner.insert(key, State::new(now + exp, val));
         Ok(())
     }
 
@@ -60,7 +139,103 @@ impl Storage for MemoryStorage {
         Ok(())
     }
 
+    async fn touch(&self, key: &str, accessed: u64, exp: Duration) -> Result<bool> {
+        let now = Instant::now();
+        match self.write()?.states.get_mut(key) {
+            Some(State(time, data)) if *time >= now => {
+                *time = now + exp;
+                data.set_accessed(accessed);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn rename(&self, old: &str, new: &str) -> Result<()> {
+        let now = Instant::now();
+        let mut inner = self.write()?;
+        if matches!(inner.states.get(new), Some(State(time, _)) if *time >= now) {
+            return Err(anyhow!("can't rename a session onto an existing id"));
+        }
+        if let Some(state) = inner.remove(old).filter(|State(time, _)| *time >= now) {
+            inner.insert(new, state);
+        }
+        Ok(())
+    }
+
+    async fn sessions_for(&self, user_id: &str) -> Result<Vec<String>> {
+        let now = Instant::now();
+        let inner = self.read()?;
+        Ok(inner
+            .users
+            .get(user_id)
+            .into_iter()
+            .flatten()
+            .filter(|key| matches!(inner.states.get(*key), Some(State(time, _)) if *time >= now))
+            .cloned()
+            .collect())
+    }
+
+    async fn destroy_for(&self, user_id: &str) -> Result<u64> {
+        let now = Instant::now();
+        let mut inner = self.write()?;
+        let keys = inner.users.remove(user_id).unwrap_or_default();
+        Ok(keys
+            .iter()
+            .filter_map(|key| inner.states.remove(key))
+            .filter(|State(time, _)| *time >= now)
+            .count() as u64)
+    }
+
+    async fn try_lock(&self, key: &str, token: &str, ttl: Duration) -> Result<bool> {
+        let now = Instant::now();
+        let mut inner = self.write()?;
+        if matches!(inner.locks.get(key), Some((_, time)) if *time >= now) {
+            return Ok(false);
+        }
+        inner
+            .locks
+            .insert(key.to_string(), (token.to_string(), now + ttl));
+        Ok(true)
+    }
+
+    async fn unlock(&self, key: &str, token: &str) -> Result<bool> {
+        let mut inner = self.write()?;
+        match inner.locks.get(key) {
+            Some((holder, time)) if holder == token => {
+                let held = *time >= Instant::now();
+                inner.locks.remove(key);
+                Ok(held)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn cleanup(&self) -> Result<u64> {
+        let now = Instant::now();
+        let mut inner = self.write()?;
+        let expired = inner
+            .states
+            .iter()
+            .filter(|(_, State(time, _))| *time < now)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in &expired {
+            inner.remove(key);
+        }
+        inner.locks.retain(|_, (_, time)| *time >= now);
+        Ok(expired.len() as u64)
+    }
+
     async fn reset(&self) -> Result<()> {
-        Ok(self.write()?.clear())
+        let mut inner = self.write()?;
+        inner.states.clear();
+        inner.users.clear();
+        inner.locks.clear();
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.cleanup().await.map(|_| ())
     }
 }
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use ntex::{
+    http::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE},
+    service::{Middleware, Service, ServiceCtx},
+    web::{ErrorRenderer, HttpRequest, HttpResponse, WebRequest, WebResponse},
+};
+use sessions_core::{
+    finish_session, join_cookie_headers, load_request_session, Config, Result, Session,
+};
+
+/// Loads the [`Session`] of each request into its extensions, then saves it
+/// and sets the cookie once the service responded
+///
+/// The session id is read from the `Cookie` header with the [`CookieOptions`] of
+/// the config, a new session is created without a valid one. Changed sessions are
+/// saved, unchanged ones [rolled](Session::roll), and the response gets the
+/// [`set_cookies`](Session::set_cookies) of the session status, see [`finish_session`].
+/// A session which can't be loaded or saved answers `500 Internal Server Error`.
+///
+/// Handlers take the session as an argument or get it with [`SessionExt`].
+///
+/// [`CookieOptions`]: sessions_core::CookieOptions
+#[derive(Clone, Debug)]
+pub struct SessionMiddleware {
+    config: Arc<Config>,
+}
+
+impl SessionMiddleware {
+    /// Creates new `SessionMiddleware` with the `Config`
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Middleware<S> for SessionMiddleware {
+    type Service = SessionMiddlewareService<S>;
+
+    fn create(&self, service: S) -> Self::Service {
+        SessionMiddlewareService {
+            service,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// The service of the [`SessionMiddleware`]
+#[derive(Debug)]
+pub struct SessionMiddlewareService<S> {
+    service: S,
+    config: Arc<Config>,
+}
+
+impl<S, Err> Service<WebRequest<Err>> for SessionMiddlewareService<S>
+where
+    S: Service<WebRequest<Err>, Response = WebResponse>,
+    Err: ErrorRenderer,
+{
+    type Response = WebResponse;
+    type Error = S::Error;
+
+    ntex::forward_poll!(service);
+    ntex::forward_ready!(service);
+    ntex::forward_shutdown!(service);
+
+    async fn call(
+        &self,
+        req: WebRequest<Err>,
+        ctx: ServiceCtx<'_, Self>,
+    ) -> Result<Self::Response, Self::Error> {
+        let cookies = cookie_header(req.headers());
+        let session = match load_request_session(&self.config, &cookies).await {
+            Ok(session) => session,
+            Err(e) => {
+                log::error!("failed to load session: {}", e);
+                return Ok(req.into_response(HttpResponse::InternalServerError().finish()));
+            }
+        };
+        req.extensions_mut().insert(session.clone());
+
+        let mut res = ctx.call(&self.service, req).await?;
+
+        match finish(&session).await {
+            Ok(cookies) => {
+                for cookie in cookies {
+                    res.headers_mut().append(SET_COOKIE, cookie);
+                }
+                Ok(res)
+            }
+            Err(e) => {
+                log::error!("failed to save session: {}", e);
+                Ok(res.into_response(HttpResponse::InternalServerError().finish()))
+            }
+        }
+    }
+}
+
+/// Gets the [`Session`] the [`SessionMiddleware`] loaded into a request
+pub trait SessionExt {
+    /// Gets the session, `None` on routes without the middleware
+    fn session(&self) -> Option<Session>;
+}
+
+impl SessionExt for HttpRequest {
+    fn session(&self) -> Option<Session> {
+        self.extensions().get::<Session>().cloned()
+    }
+}
+
+impl<Err> SessionExt for WebRequest<Err> {
+    fn session(&self) -> Option<Session> {
+        self.extensions().get::<Session>().cloned()
+    }
+}
+
+/// Joins the `Cookie` headers
+fn cookie_header(headers: &HeaderMap) -> String {
+    join_cookie_headers(headers.get_all(COOKIE).filter_map(|val| val.to_str().ok()))
+}
+
+/// Finishes the session, getting the `Set-Cookie` values of its status
+async fn finish(session: &Session) -> Result<Vec<HeaderValue>> {
+    finish_session(session)
+        .await?
+        .iter()
+        .map(|cookie| Ok(HeaderValue::from_str(cookie)?))
+        .collect()
+}
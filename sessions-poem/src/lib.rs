@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use poem::{
+    http::StatusCode,
+    middleware::{CookieJarManager, CookieJarManagerEndpoint},
+    Endpoint, Error, IntoResponse, Middleware, Request, Response, Result,
+};
+use sessions_core::{finish_session_cookies, load_cookie_session, Config};
+
+/// Wraps endpoints in a [`SessionEndpoint`], behind the `CookieJarManager` of poem
+#[derive(Clone, Debug)]
+pub struct SessionMiddleware {
+    config: Arc<Config>,
+}
+
+impl SessionMiddleware {
+    /// Creates new `SessionMiddleware` with the `Config`
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for SessionMiddleware {
+    type Output = CookieJarManagerEndpoint<SessionEndpoint<E>>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        CookieJarManager::new().transform(SessionEndpoint {
+            inner: ep,
+            config: self.config.clone(),
+        })
+    }
+}
+
+/// Loads the [`Session`] of each request into its extensions, then saves it
+/// and sets the cookie once the endpoint responded
+///
+/// The cookies go through the cookie jar of the request: the session id is read
+/// from it with [`load_cookie_session`], the cookies of the session status are
+/// added to it with [`finish_session_cookies`], and the `CookieJarManager` sends
+/// them. A cookie longer than the `chunk_size` of the [`CookieOptions`] is split.
+/// A session which can't be loaded or saved fails the request with
+/// `500 Internal Server Error`.
+///
+/// Endpoints take the session as an argument.
+///
+/// [`Session`]: sessions_core::Session
+/// [`CookieOptions`]: sessions_core::CookieOptions
+#[derive(Debug)]
+pub struct SessionEndpoint<E> {
+    inner: E,
+    config: Arc<Config>,
+}
+
+impl<E: Endpoint> Endpoint for SessionEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let jar = req.cookie().clone();
+        let cookies = jar.with_cookies(|cookies| {
+            cookies
+                .map(|cookie| (cookie.name().to_string(), cookie.value().to_string()))
+                .collect::<Vec<_>>()
+        });
+        let cookies = cookies.iter().map(|(name, value)| (&**name, &**value));
+        let session = load_cookie_session(&self.config, cookies)
+            .await
+            .map_err(|e| {
+                log::error!("failed to load session: {}", e);
+                Error::from_status(StatusCode::INTERNAL_SERVER_ERROR)
+            })?;
+        req.extensions_mut().insert(session.clone());
+
+        let res = self.inner.call(req).await?.into_response();
+
+        let cookies = finish_session_cookies(&session).await.map_err(|e| {
+            log::error!("failed to save session: {}", e);
+            Error::from_status(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+        for cookie in cookies {
+            jar.add(cookie.into());
+        }
+        Ok(res)
+    }
+}
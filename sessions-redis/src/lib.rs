@@ -1,11 +1,12 @@
-use std::time::Duration;
+use std::{convert::TryFrom, time::Duration};
 
-use sessions_core::{anyhow, async_trait, Data, Result, Storage};
+use sessions_core::{anyhow, async_trait, record, Conflict, Metadata, Payload, Result, Storage};
 
-use redis::{aio::Connection, AsyncCommands};
+use redis::{aio::Connection, AsyncCommands, Pipeline};
 
 pub use redis::Client;
 
+/// A byte-oriented Redis storage, wrap it in `Serialized` to use it with `Config`
 #[derive(Clone, Debug)]
 pub struct RedisStorage {
     inner: Client,
@@ -22,35 +23,261 @@ impl RedisStorage {
             .await
             .map_err(|e| anyhow!(e.to_string()))
     }
+
+    async fn load(con: &mut Connection, key: &str) -> Result<Option<Vec<u8>>> {
+        let buf = con
+            .get::<&str, Vec<u8>>(key)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        Ok(if buf.is_empty() { None } else { Some(buf) })
+    }
+
+    /// Builds an atomic pipeline which sets the record and indexes its user
+    fn pipe(key: &str, val: &Vec<u8>, exp: Duration) -> Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .pset_ex(key, val.as_slice(), millis(exp) as usize)
+            .ignore();
+        if let Some(user_id) = val.user() {
+            pipe.sadd(user_key(user_id), key).ignore();
+        }
+        pipe
+    }
+}
+
+fn version(val: Option<Vec<u8>>) -> u64 {
+    val.map_or(0, |val| val.metadata().version)
+}
+
+/// Gets the expiry in milliseconds, at least one as Redis rejects a zero expiry
+fn millis(exp: Duration) -> u64 {
+    u64::try_from(exp.as_millis()).unwrap_or(u64::MAX).max(1)
+}
+
+fn user_key(user_id: &str) -> String {
+    format!("user:{}", user_id)
+}
+
+fn lock_key(key: &str) -> String {
+    format!("lock:{}", key)
 }
 
+/// Renames the key unless the new key exists, `-1` if it does and `0` if the key is missing
+const RENAME_SCRIPT: &str = r#"
+if redis.call("EXISTS", KEYS[2]) == 1 then
+    return -1
+elseif redis.call("EXISTS", KEYS[1]) == 0 then
+    return 0
+end
+redis.call("RENAME", KEYS[1], KEYS[2])
+return 1
+"#;
+
+/// Deletes the lock only if it's still held by the token
+const UNLOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+else
+    return 0
+end
+"#;
+
+/// Extends the expiry and overwrites the last accessed time of a record, `0` if the key is missing
+const TOUCH_SCRIPT: &str = r#"
+local format = redis.call("GETRANGE", KEYS[1], 0, 0)
+if format == "" then
+    return 0
+end
+if string.byte(format) == tonumber(ARGV[1]) then
+    redis.call("SETRANGE", KEYS[1], ARGV[2], ARGV[3])
+end
+return redis.call("PEXPIRE", KEYS[1], ARGV[4])
+"#;
+
 #[async_trait]
-impl Storage for RedisStorage {
-    async fn get(&self, key: &str) -> Result<Option<Data>> {
-        Ok(serde_json::from_slice(
-            &self
-                .con()
-                .await?
-                .get::<&str, Vec<u8>>(key)
-                .await
-                .map_err(|e| anyhow!(e.to_string()))?,
-        )
-        .ok())
+impl Storage<Vec<u8>> for RedisStorage {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Self::load(&mut self.con().await?, key).await
     }
 
-    async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
-        self.con()
+    async fn metadata(&self, key: &str) -> Result<Option<Metadata>> {
+        let header = self
+            .con()
             .await?
-            .set_ex(key, serde_json::to_vec(&val)?, exp.as_secs() as usize)
+            .getrange::<_, Vec<u8>>(key, 0, record::HEADER_LEN as isize - 1)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        Ok(record::decode_metadata(&header))
+    }
+
+    async fn set(&self, key: &str, val: Vec<u8>, exp: Duration) -> Result<()> {
+        Self::pipe(key, &val, exp)
+            .query_async(&mut self.con().await?)
             .await
             .map_err(|e| anyhow!(e.to_string()))
     }
 
+    async fn set_if_version(
+        &self,
+        key: &str,
+        val: Vec<u8>,
+        exp: Duration,
+        version: u64,
+    ) -> Result<()> {
+        let mut con = self.con().await?;
+        redis::cmd("WATCH")
+            .arg(key)
+            .query_async::<_, ()>(&mut con)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        if self::version(Self::load(&mut con, key).await?) == version {
+            // `EXEC` returns nil when the key was touched after `WATCH`
+            if Self::pipe(key, &val, exp)
+                .query_async::<_, Option<()>>(&mut con)
+                .await
+                .map_err(|e| anyhow!(e.to_string()))?
+                .is_some()
+            {
+                return Ok(());
+            }
+        } else {
+            redis::cmd("UNWATCH")
+                .query_async::<_, ()>(&mut con)
+                .await
+                .map_err(|e| anyhow!(e.to_string()))?;
+        }
+        Err(Conflict {
+            expected: version,
+            found: self::version(Self::load(&mut con, key).await?),
+        }
+        .into())
+    }
+
     async fn remove(&self, key: &str) -> Result<()> {
-        self.con()
-            .await?
-            .del(key)
+        let mut con = self.con().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(key).ignore();
+        if let Some(val) = Self::load(&mut con, key).await? {
+            if let Some(user_id) = val.user() {
+                pipe.srem(user_key(user_id), key).ignore();
+            }
+        }
+        pipe.query_async(&mut con)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    async fn touch(&self, key: &str, accessed: u64, exp: Duration) -> Result<bool> {
+        redis::cmd("EVAL")
+            .arg(TOUCH_SCRIPT)
+            .arg(1)
+            .arg(key)
+            .arg(record::FORMAT)
+            .arg(record::ACCESSED_OFFSET)
+            .arg(&accessed.to_be_bytes()[..])
+            .arg(millis(exp))
+            .query_async::<_, i64>(&mut self.con().await?)
+            .await
+            .map(|touched| touched > 0)
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    async fn rename(&self, old: &str, new: &str) -> Result<()> {
+        let mut con = self.con().await?;
+        let renamed = redis::cmd("EVAL")
+            .arg(RENAME_SCRIPT)
+            .arg(2)
+            .arg(old)
+            .arg(new)
+            .query_async::<_, i64>(&mut con)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        if renamed < 0 {
+            return Err(anyhow!("can't rename a session onto an existing id"));
+        }
+        if renamed > 0 {
+            if let Some(val) = Self::load(&mut con, new).await? {
+                if let Some(user_id) = val.user() {
+                    redis::pipe()
+                        .atomic()
+                        .srem(user_key(user_id), old)
+                        .ignore()
+                        .sadd(user_key(user_id), new)
+                        .ignore()
+                        .query_async::<_, ()>(&mut con)
+                        .await
+                        .map_err(|e| anyhow!(e.to_string()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn sessions_for(&self, user_id: &str) -> Result<Vec<String>> {
+        let mut con = self.con().await?;
+        let users = user_key(user_id);
+        let ids: Vec<String> = con
+            .smembers(&users)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let mut alive = Vec::with_capacity(ids.len());
+        for id in ids {
+            match Self::load(&mut con, &id).await? {
+                // Drops expired sessions or sessions bound to another user since
+                Some(val) if val.user() == Some(user_id) => alive.push(id),
+                _ => con
+                    .srem::<_, _, ()>(&users, &id)
+                    .await
+                    .map_err(|e| anyhow!(e.to_string()))?,
+            }
+        }
+        Ok(alive)
+    }
+
+    async fn destroy_for(&self, user_id: &str) -> Result<u64> {
+        let ids = self.sessions_for(user_id).await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for id in &ids {
+            pipe.del(id).ignore();
+        }
+        pipe.del(user_key(user_id)).ignore();
+        pipe.query_async::<_, ()>(&mut self.con().await?)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        Ok(ids.len() as u64)
+    }
+
+    async fn try_lock(&self, key: &str, token: &str, ttl: Duration) -> Result<bool> {
+        redis::cmd("SET")
+            .arg(lock_key(key))
+            .arg(token)
+            .arg("NX")
+            .arg("PX")
+            .arg(millis(ttl))
+            .query_async::<_, Option<String>>(&mut self.con().await?)
+            .await
+            .map(|res| res.is_some())
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    async fn unlock(&self, key: &str, token: &str) -> Result<bool> {
+        redis::cmd("EVAL")
+            .arg(UNLOCK_SCRIPT)
+            .arg(1)
+            .arg(lock_key(key))
+            .arg(token)
+            .query_async::<_, i64>(&mut self.con().await?)
+            .await
+            .map(|n| n == 1)
+            .map_err(|e| anyhow!(e.to_string()))
+    }
+
+    async fn ping(&self) -> Result<()> {
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut self.con().await?)
             .await
+            .map(|_| ())
             .map_err(|e| anyhow!(e.to_string()))
     }
 
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use rocket::{
+    async_trait,
+    fairing::{self, Fairing, Info, Kind},
+    http::Status,
+    Build, Data, Request, Response, Rocket,
+};
+use sessions_core::{finish_session_cookies, load_cookie_session, Config, Session};
+
+/// Loads the [`Session`] of each request, then saves it and sets the cookie
+/// once the route responded
+///
+/// The `Config` is put in the managed state on ignition, routes can get it as
+/// `&State<Arc<Config>>`. They take the session as a request guard.
+///
+/// The session id is read from the cookie jar of the request with
+/// [`load_cookie_session`], the cookies of the session status are added to the
+/// response with [`finish_session_cookies`], split by the `chunk_size` of the
+/// [`CookieOptions`]. Rocket doesn't lend the secret
+/// key of its private cookies to fairings: seal the session cookie with the
+/// signing or encryption keys of the [`CookieOptions`] instead, its other
+/// attributes replace the defaults of the jar too. A session which can't be
+/// loaded fails its guard, one which can't be saved answers
+/// `500 Internal Server Error`.
+///
+/// [`CookieOptions`]: sessions_core::CookieOptions
+#[derive(Clone, Debug)]
+pub struct SessionFairing {
+    config: Arc<Config>,
+}
+
+impl SessionFairing {
+    /// Creates new `SessionFairing` with the `Config`
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl Fairing for SessionFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Sessions",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        Ok(rocket.manage(self.config.clone()))
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let cookies = req
+            .cookies()
+            .iter()
+            .map(|cookie| (cookie.name().to_string(), cookie.value().to_string()))
+            .collect::<Vec<_>>();
+        let cookies = cookies.iter().map(|(name, value)| (&**name, &**value));
+        match load_cookie_session(&self.config, cookies).await {
+            Ok(session) => {
+                req.local_cache(|| Some(session));
+            }
+            Err(e) => log::error!("failed to load session: {}", e),
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let session = match req.local_cache(|| None::<Session>) {
+            Some(session) => session,
+            None => return,
+        };
+        match finish_session_cookies(session).await {
+            Ok(cookies) => {
+                for cookie in cookies {
+                    res.adjoin_header(cookie);
+                }
+            }
+            Err(e) => {
+                log::error!("failed to save session: {}", e);
+                *res = Response::build()
+                    .status(Status::InternalServerError)
+                    .finalize();
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use salvo_core::{async_trait, http::StatusError, Depot, FlowCtrl, Handler, Request, Response};
+use sessions_core::{finish_session_cookies, load_cookie_session, Config, Session};
+
+/// Loads the [`Session`] of each request into the `Depot`, then saves it and
+/// sets the cookie once the rest of the router ran, as a hoop
+///
+/// The `Config` and its [`CookieOptions`] are set once, when the router is built.
+/// The session id is read from the cookies of the request with
+/// [`load_cookie_session`], the cookies of the session status are added to the
+/// response with [`finish_session_cookies`], split by the `chunk_size` of the
+/// [`CookieOptions`], the removal cookie once destroyed.
+/// A session which can't be loaded or saved answers `500 Internal Server Error`.
+///
+/// Handlers get the session with [`SessionDepotExt`].
+///
+/// [`CookieOptions`]: sessions_core::CookieOptions
+#[derive(Clone, Debug)]
+pub struct SessionHandler {
+    config: Arc<Config>,
+}
+
+impl SessionHandler {
+    /// Creates new `SessionHandler` with the `Config`
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl Handler for SessionHandler {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let cookies = req
+            .cookies()
+            .iter()
+            .map(|cookie| (cookie.name().to_string(), cookie.value().to_string()))
+            .collect::<Vec<_>>();
+        let cookies = cookies.iter().map(|(name, value)| (&**name, &**value));
+        let session = match load_cookie_session(&self.config, cookies).await {
+            Ok(session) => session,
+            Err(e) => {
+                log::error!("failed to load session: {}", e);
+                res.render(StatusError::internal_server_error());
+                ctrl.skip_rest();
+                return;
+            }
+        };
+        depot.insert_typed(session.clone());
+
+        ctrl.call_next(req, depot, res).await;
+
+        match finish_session_cookies(&session).await {
+            Ok(cookies) => {
+                for cookie in cookies {
+                    res.add_cookie(cookie);
+                }
+            }
+            Err(e) => {
+                log::error!("failed to save session: {}", e);
+                res.render(StatusError::internal_server_error());
+            }
+        }
+    }
+}
+
+/// Gets the [`Session`] the [`SessionHandler`] loaded into a `Depot`
+pub trait SessionDepotExt {
+    /// Gets the session, `None` on routes without the handler
+    fn session(&self) -> Option<&Session>;
+}
+
+impl SessionDepotExt for Depot {
+    fn session(&self) -> Option<&Session> {
+        self.get_typed::<Session>().ok()
+    }
+}
@@ -1,59 +1,150 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
-    time::{Duration, Instant},
+    convert::TryInto,
+    path::Path,
+    time::{Duration, SystemTime},
 };
 
-use sessions_core::{anyhow, async_trait, Data, Result, Storage};
+use sessions_core::{anyhow, async_trait, record, to_millis, Result, Storage};
+use sled::transaction::{abort, TransactionError};
 
-#[derive(Clone, Debug)]
-struct State(Instant, Data);
+/// Length of the expiry prepended to every stored record
+const EXPIRES_LEN: usize = 8;
 
-impl State {
-    fn new(i: Instant, d: Data) -> Self {
-        Self(i, d)
-    }
+fn now() -> u64 {
+    to_millis(SystemTime::now())
+}
+
+fn expires_at(buf: &[u8]) -> u64 {
+    buf.get(..EXPIRES_LEN)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or(0)
 }
 
-#[derive(Debug, Default)]
-pub struct MemoryStorage {
-    inner: Arc<RwLock<HashMap<String, State>>>,
+fn user(buf: &[u8]) -> Option<&str> {
+    record::decode(&buf[EXPIRES_LEN..])?.user
 }
 
-impl MemoryStorage {
-    fn read(&self) -> Result<RwLockReadGuard<'_, HashMap<String, State>>> {
-        self.inner.read().map_err(|e| anyhow!(e.to_string()))
+/// Stores the sessions on disk, as `record`s prefixed with their expiry
+#[derive(Clone, Debug)]
+pub struct SledStorage {
+    db: sled::Db,
+}
+
+impl SledStorage {
+    pub fn new(db: sled::Db) -> Self {
+        Self { db }
+    }
+
+    /// Opens the database at the path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(sled::open(path)?))
     }
 
-    fn write(&self) -> Result<RwLockWriteGuard<'_, HashMap<String, State>>> {
-        self.inner.write().map_err(|e| anyhow!(e.to_string()))
+    fn load(&self, key: &str) -> Result<Option<sled::IVec>> {
+        Ok(self.db.get(key)?.filter(|buf| expires_at(buf) >= now()))
     }
 }
 
 #[async_trait]
-impl Storage for MemoryStorage {
-    async fn get(&self, key: &str) -> Result<Option<Data>> {
-        if let Some(State(time, data)) = self.read()?.get(key).cloned() {
-            if time >= Instant::now() {
-                return Ok(Some(data));
-            }
-        }
-
-        Ok(None)
+impl Storage<Vec<u8>> for SledStorage {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.load(key)?.map(|buf| buf[EXPIRES_LEN..].to_vec()))
     }
 
-    async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
-        self.write()?
-            .insert(key.to_string(), State::new(Instant::now() + exp, val));
+    async fn set(&self, key: &str, val: Vec<u8>, exp: Duration) -> Result<()> {
+        let expires = now().saturating_add(exp.as_millis() as u64);
+        let mut buf = Vec::with_capacity(EXPIRES_LEN + val.len());
+        buf.extend_from_slice(&expires.to_be_bytes());
+        buf.extend_from_slice(&val);
+        self.db.insert(key, buf)?;
         Ok(())
     }
 
     async fn remove(&self, key: &str) -> Result<()> {
-        self.write()?.remove(key);
+        self.db.remove(key)?;
         Ok(())
     }
 
+    async fn touch(&self, key: &str, accessed: u64, exp: Duration) -> Result<bool> {
+        let old = match self.load(key)? {
+            Some(old) => old,
+            None => return Ok(false),
+        };
+        let mut buf = old.to_vec();
+        let expires = now().saturating_add(exp.as_millis() as u64);
+        buf[..EXPIRES_LEN].copy_from_slice(&expires.to_be_bytes());
+        record::set_accessed(&mut buf[EXPIRES_LEN..], accessed);
+        // Changed since it was loaded, the save that changed it wins
+        Ok(self.db.compare_and_swap(key, Some(old), Some(buf))?.is_ok())
+    }
+
+    async fn rename(&self, old: &str, new: &str) -> Result<()> {
+        let now = now();
+        self.db
+            .transaction(|tx| {
+                if matches!(tx.get(new)?, Some(buf) if expires_at(&buf) >= now) {
+                    return abort(());
+                }
+                if let Some(buf) = tx.remove(old)?.filter(|buf| expires_at(buf) >= now) {
+                    tx.insert(new, buf)?;
+                }
+                Ok(())
+            })
+            .map_err(|e| match e {
+                TransactionError::Abort(()) => {
+                    anyhow!("can't rename a session onto an existing id")
+                }
+                TransactionError::Storage(e) => e.into(),
+            })
+    }
+
+    async fn sessions_for(&self, user_id: &str) -> Result<Vec<String>> {
+        let now = now();
+        let mut ids = Vec::new();
+        for entry in self.db.iter() {
+            let (key, buf) = entry?;
+            if expires_at(&buf) >= now && user(&buf) == Some(user_id) {
+                ids.push(String::from_utf8_lossy(&key).into_owned());
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn destroy_for(&self, user_id: &str) -> Result<u64> {
+        let ids = self.sessions_for(user_id).await?;
+        for id in &ids {
+            self.db.remove(id)?;
+        }
+        Ok(ids.len() as u64)
+    }
+
+    async fn cleanup(&self) -> Result<u64> {
+        let now = now();
+        let mut n = 0;
+        for entry in self.db.iter() {
+            let (key, buf) = entry?;
+            // Left alone if it was saved again since it was read
+            if expires_at(&buf) < now
+                && self
+                    .db
+                    .compare_and_swap(key, Some(buf), None::<&[u8]>)?
+                    .is_ok()
+            {
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+
     async fn reset(&self) -> Result<()> {
-        Ok(self.write()?.clear())
+        self.db.clear()?;
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.cleanup().await?;
+        self.db.flush_async().await?;
+        Ok(())
     }
 }
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use sessions_core::{
+    async_trait, finish_session, join_cookie_headers, load_request_session, Config,
+};
+use tide::{
+    http::headers::{COOKIE, SET_COOKIE},
+    Middleware, Next, Request, Response, StatusCode,
+};
+
+/// Loads the [`Session`] of each request into its extensions, then saves it
+/// and sets the cookie once the endpoint responded
+///
+/// The session id is read from the `Cookie` header with the [`CookieOptions`] of
+/// the config, a new session is created without a valid one. Endpoints get the
+/// session with `req.ext::<Session>()`. Changed sessions are saved, unchanged ones
+/// [rolled], and the response gets the [`set_cookies`] of the session status, see
+/// [`finish_session`]. A session which can't be loaded or saved answers
+/// `500 Internal Server Error`.
+///
+/// Nothing here needs a tokio runtime, the storage has to do without one too.
+///
+/// [`Session`]: sessions_core::Session
+/// [rolled]: sessions_core::Session::roll
+/// [`set_cookies`]: sessions_core::Session::set_cookies
+/// [`CookieOptions`]: sessions_core::CookieOptions
+#[derive(Clone, Debug)]
+pub struct SessionMiddleware {
+    config: Arc<Config>,
+}
+
+impl SessionMiddleware {
+    /// Creates new `SessionMiddleware` with the `Config`
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl<State> Middleware<State> for SessionMiddleware
+where
+    State: Clone + Send + Sync + 'static,
+{
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let cookies = join_cookie_headers(
+            req.header(COOKIE)
+                .into_iter()
+                .flat_map(|vals| vals.iter().map(|val| val.as_str())),
+        );
+        let session = load_request_session(&self.config, &cookies)
+            .await
+            .map_err(|e| {
+                log::error!("failed to load session: {}", e);
+                tide::Error::new(StatusCode::InternalServerError, e)
+            })?;
+        req.set_ext(session.clone());
+
+        let mut res = next.run(req).await;
+
+        match finish_session(&session).await {
+            Ok(cookies) => {
+                for cookie in cookies {
+                    res.append_header(SET_COOKIE, cookie);
+                }
+            }
+            Err(e) => {
+                log::error!("failed to save session: {}", e);
+                return Ok(Response::new(StatusCode::InternalServerError));
+            }
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{
+    header::{HeaderName, COOKIE},
+    HeaderMap, HeaderValue, Request, Response,
+};
+use sessions_core::{
+    apply_session, extract_session, request_session_id, Config, Result, Session, SessionGuard,
+    SessionStatus,
+};
+use tonic::Status;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Wraps gRPC services in a [`SessionService`], e.g. with `Server::builder().layer`
+///
+/// The session id is read from the `cookie` metadata by default, like browsers
+/// send it, or from the raw value of another key with [`with_key`](SessionLayer::with_key).
+#[derive(Clone, Debug)]
+pub struct SessionLayer {
+    config: Arc<Config>,
+    key: HeaderName,
+    required: bool,
+}
+
+impl SessionLayer {
+    /// Creates new `SessionLayer` with the `Config`, reading the `cookie` metadata
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            key: COOKIE,
+            required: false,
+        }
+    }
+
+    /// Creates new `SessionLayer` reading the session id from the metadata `key`,
+    /// e.g. `x-session-id`, and writing it back under the same key
+    pub fn with_key(mut self, key: HeaderName) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Creates new `SessionLayer` answering `Unauthenticated` to the calls without
+    /// a stored session, instead of giving them a new anonymous one
+    pub fn with_required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+}
+
+impl<S> Layer<S> for SessionLayer {
+    type Service = SessionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SessionService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Loads the [`Session`] of each call into the request extensions, where the
+/// service impl finds it with `request.extensions().get::<Session>()`, then
+/// writes it back with [`apply_session_metadata`] once the service responded
+///
+/// Calls whose session can't be loaded or saved fail with `Internal`.
+#[derive(Clone, Debug)]
+pub struct SessionService<S> {
+    inner: S,
+    layer: SessionLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SessionService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // The polled service handles the request, a clone is polled next time
+        let clone = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, clone);
+        let SessionLayer {
+            config,
+            key,
+            required,
+        } = self.layer.clone();
+        // The cookie is loaded like the http helpers do, other keys by their raw id
+        let load: Pin<Box<dyn Future<Output = Result<Session>> + Send>> = if key == COOKIE {
+            Box::pin(extract_session(&req, &config))
+        } else {
+            let sid = metadata_session_id(req.headers(), &config, &key).unwrap_or_default();
+            let config = config.clone();
+            Box::pin(async move { config.load_session(&sid).await })
+        };
+
+        Box::pin(async move {
+            let session = match load.await {
+                Ok(session) => session,
+                Err(e) => {
+                    log::error!("failed to load session: {}", e);
+                    return Ok(Status::internal("failed to load session").into_http());
+                }
+            };
+            if required && session.is_new() {
+                return Ok(Status::unauthenticated("no session").into_http());
+            }
+            req.extensions_mut().insert(session.clone());
+
+            let mut res = inner.call(req).await?;
+
+            if let Err(e) = apply_session_metadata(&mut res, &session, &key).await {
+                log::error!("failed to save session: {}", e);
+                return Ok(Status::internal("failed to save session").into_http());
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Finds the session id in the metadata `key` of a call, verified by the config
+///
+/// It's the session cookie of the `cookie` metadata, the raw value of other keys.
+pub fn metadata_session_id(
+    headers: &HeaderMap,
+    config: &Config,
+    key: &HeaderName,
+) -> Option<String> {
+    if key == COOKIE {
+        return request_session_id(headers, config);
+    }
+    headers
+        .get(key)?
+        .to_str()
+        .ok()
+        .filter(|sid| config.verify(sid))
+        .map(Into::into)
+}
+
+/// Saves the session if it changed, rolls it otherwise, then writes it back in
+/// the response metadata `key`
+///
+/// For the `cookie` key, the `set-cookie` metadata gets the cookie with
+/// [`apply_session`]. Other keys get the session id once it was saved, renewed,
+/// rolled or [reissued](Session::reissue_cookie), an empty value once it was
+/// destroyed, nothing if it's unchanged.
+pub async fn apply_session_metadata<B>(
+    res: &mut Response<B>,
+    session: &Session,
+    key: &HeaderName,
+) -> Result<()> {
+    if key == COOKIE {
+        return apply_session(res, session).await;
+    }
+    SessionGuard::new(session.clone()).finish().await?;
+    let sid = match session.status() {
+        SessionStatus::Unchanged if !session.is_reissued() => return Ok(()),
+        SessionStatus::Unchanged | SessionStatus::Saved | SessionStatus::Renewed => session.id()?,
+        SessionStatus::Destroyed | SessionStatus::Purged => String::new(),
+    };
+    res.headers_mut()
+        .insert(key.clone(), HeaderValue::from_str(&sid)?);
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::{
+    convert::TryFrom,
+    time::{Duration, SystemTime},
+};
+
+use sessions_core::{async_trait, from_millis, keys, to_millis, Data, Result, Storage};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store::{self, Error},
+    SessionStore,
+};
+
+/// How long a record lives without a stored expiry date, as a new tower-sessions session
+const DEFAULT_EXPIRY: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Implements the [`SessionStore`] of tower-sessions over a [`Storage`]
+///
+/// Records are stored under the string form of their [`Id`], until their expiry
+/// date. The date is also written to the data under [`keys::EXPIRY_DATE`], to load
+/// the record back as it was saved. Data saved without it, by a [`Session`] of
+/// this crate, expires two weeks after it's loaded.
+///
+/// [`Session`]: sessions_core::Session
+#[derive(Debug)]
+pub struct TowerSessionStoreAdapter<S> {
+    storage: S,
+}
+
+impl<S> TowerSessionStoreAdapter<S> {
+    /// Creates new `TowerSessionStoreAdapter` over the storage
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Gets the storage
+    pub fn inner(&self) -> &S {
+        &self.storage
+    }
+}
+
+#[async_trait]
+impl<S: Storage> SessionStore for TowerSessionStoreAdapter<S> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        while self
+            .storage
+            .get(&record.id.to_string())
+            .await
+            .map_err(backend)?
+            .is_some()
+        {
+            record.id = Id::default();
+        }
+        self.save(record).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let key = record.id.to_string();
+        let exp = match remaining(record.expiry_date) {
+            Some(exp) => exp,
+            None => return self.storage.remove(&key).await.map_err(backend),
+        };
+        let mut data = record.data.clone().into_iter().collect::<Data>();
+        data.insert(
+            keys::EXPIRY_DATE.into(),
+            to_millis(SystemTime::from(record.expiry_date)).into(),
+        );
+        self.storage.set(&key, data, exp).await.map_err(backend)
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let mut data = match self
+            .storage
+            .get(&session_id.to_string())
+            .await
+            .map_err(backend)?
+        {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let expiry_date = match data
+            .remove(keys::EXPIRY_DATE)
+            .and_then(|val| val.as_u64())
+            .and_then(from_millis)
+        {
+            Some(time) => OffsetDateTime::from(time),
+            None => OffsetDateTime::now_utc() + DEFAULT_EXPIRY,
+        };
+        if remaining(expiry_date).is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Record {
+            id: *session_id,
+            data: data.into_iter().collect(),
+            expiry_date,
+        }))
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.storage
+            .remove(&session_id.to_string())
+            .await
+            .map_err(backend)
+    }
+}
+
+/// Implements [`Storage`] over a [`SessionStore`] of tower-sessions
+///
+/// The expiry of the data becomes the expiry date of its record. A key which is
+/// the string form of an [`Id`], as in the cookies of tower-sessions, is the id
+/// of the record, other keys are hashed into one.
+#[derive(Debug)]
+pub struct StorageAdapter<T> {
+    store: T,
+}
+
+impl<T> StorageAdapter<T> {
+    /// Creates new `StorageAdapter` over the session store
+    pub fn new(store: T) -> Self {
+        Self { store }
+    }
+
+    /// Gets the session store
+    pub fn inner(&self) -> &T {
+        &self.store
+    }
+}
+
+#[async_trait]
+impl<T: SessionStore> Storage for StorageAdapter<T> {
+    async fn get(&self, key: &str) -> Result<Option<Data>> {
+        let record = self.store.load(&record_id(key)).await?;
+        Ok(record
+            .filter(|record| remaining(record.expiry_date).is_some())
+            .map(|record| record.data.into_iter().collect()))
+    }
+
+    async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
+        let record = Record {
+            id: record_id(key),
+            data: val.into_iter().collect(),
+            expiry_date: OffsetDateTime::now_utc() + exp,
+        };
+        self.store.save(&record).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.store.delete(&record_id(key)).await?;
+        Ok(())
+    }
+}
+
+/// Gets the time left until the expiry date, `None` once expired
+fn remaining(expiry_date: OffsetDateTime) -> Option<Duration> {
+    let left = expiry_date - OffsetDateTime::now_utc();
+    if left.is_positive() {
+        Duration::try_from(left).ok()
+    } else {
+        None
+    }
+}
+
+/// Gets the id the key is the string form of, or the hash of the key
+fn record_id(key: &str) -> Id {
+    match key.parse::<Id>() {
+        Ok(id) if id.to_string() == key => id,
+        _ => {
+            let mut bytes = [0; 16];
+            bytes.copy_from_slice(&Sha256::digest(key.as_bytes())[..16]);
+            Id(i128::from_le_bytes(bytes))
+        }
+    }
+}
+
+fn backend(e: sessions_core::Error) -> Error {
+    Error::Backend(e.to_string())
+}
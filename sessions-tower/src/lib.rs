@@ -0,0 +1,97 @@
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{Request, Response, StatusCode};
+use sessions_core::{apply_session, extract_session, Config, Result};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Wraps services in a [`SessionService`]
+#[derive(Clone, Debug)]
+pub struct SessionLayer {
+    config: Arc<Config>,
+}
+
+impl SessionLayer {
+    /// Creates new `SessionLayer` with the `Config`, whatever storage it has
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Layer<S> for SessionLayer {
+    type Service = SessionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SessionService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Loads the [`Session`] of each request into its extensions with [`extract_session`],
+/// then saves it and sets the cookie with [`apply_session`] once the inner service
+/// responded
+///
+/// A session which can't be loaded or saved answers `500 Internal Server Error`.
+///
+/// [`Session`]: sessions_core::Session
+#[derive(Clone, Debug)]
+pub struct SessionService<S> {
+    inner: S,
+    config: Arc<Config>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SessionService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // The polled service handles the request, a clone is polled next time
+        let clone = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, clone);
+        let session = extract_session(&req, &self.config);
+
+        Box::pin(async move {
+            let session = match session.await {
+                Ok(session) => session,
+                Err(e) => {
+                    log::error!("failed to load session: {}", e);
+                    return Ok(internal_error());
+                }
+            };
+            req.extensions_mut().insert(session.clone());
+
+            let mut res = inner.call(req).await?;
+
+            if let Err(e) = apply_session(&mut res, &session).await {
+                log::error!("failed to save session: {}", e);
+                return Ok(internal_error());
+            }
+            Ok(res)
+        })
+    }
+}
+
+fn internal_error<B: Default>() -> Response<B> {
+    let mut res = Response::new(B::default());
+    *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    res
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use sessions_core::{apply_session, extract_session, Config};
+use viz_core::{
+    async_trait, Error, Handler, IntoResponse, Request, Response, Result, StatusCode, Transform,
+};
+
+/// Wraps viz handlers in a [`SessionHandler`], with `Router::with` or `Handler::with`
+#[derive(Clone, Debug)]
+pub struct SessionTransform {
+    config: Arc<Config>,
+}
+
+impl SessionTransform {
+    /// Creates new `SessionTransform` with the `Config`
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+impl<H> Transform<H> for SessionTransform {
+    type Output = SessionHandler<H>;
+
+    fn transform(&self, h: H) -> Self::Output {
+        SessionHandler {
+            h,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Loads the [`Session`] of each request into its extensions with [`extract_session`],
+/// then saves it and sets the cookie with [`apply_session`] once the handler
+/// responded
+///
+/// Handlers take the session as an argument, it's extracted from the request.
+/// A session which can't be loaded or saved answers `500 Internal Server Error`.
+///
+/// [`Session`]: sessions_core::Session
+#[derive(Clone, Debug)]
+pub struct SessionHandler<H> {
+    h: H,
+    config: Arc<Config>,
+}
+
+#[async_trait]
+impl<H, O> Handler<Request> for SessionHandler<H>
+where
+    H: Handler<Request, Output = Result<O>>,
+    O: IntoResponse,
+{
+    type Output = Result<Response>;
+
+    async fn call(&self, mut req: Request) -> Self::Output {
+        let session = extract_session(&req, &self.config).await.map_err(|e| {
+            log::error!("failed to load session: {}", e);
+            internal_error()
+        })?;
+        req.extensions_mut().insert(session.clone());
+
+        let mut res = self.h.call(req).await.map(IntoResponse::into_response)?;
+
+        apply_session(&mut res, &session).await.map_err(|e| {
+            log::error!("failed to save session: {}", e);
+            internal_error()
+        })?;
+        Ok(res)
+    }
+}
+
+fn internal_error() -> Error {
+    StatusCode::INTERNAL_SERVER_ERROR.into_error()
+}
@@ -0,0 +1,66 @@
+//! Logs in, reads a protected page and logs out with an axum router
+//!
+//! ```sh
+//! cargo run --example axum --features axum,memory
+//! curl -i -X POST localhost:3000/login
+//! curl -i -b 'viz.sid=...' localhost:3000/me
+//! ```
+
+use std::sync::Arc;
+
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use sessions::*;
+
+async fn login(session: Session) -> Result<StatusCode, StatusCode> {
+    session
+        .set("user", "fundon")
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // A new id against session fixation
+    session
+        .renew_keeping_data()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn me(session: Session) -> Result<String, StatusCode> {
+    session
+        .get::<String>("user")
+        .ok()
+        .flatten()
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+async fn logout(session: Session) -> Result<StatusCode, StatusCode> {
+    session
+        .destroy()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .cookie(CookieOptions::development())
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()?,
+    );
+
+    let app = Router::new()
+        .route("/login", post(login))
+        .route("/me", get(me))
+        .route("/logout", post(logout))
+        .layer(SessionLayer::new(config));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
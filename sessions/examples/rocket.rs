@@ -0,0 +1,43 @@
+//! Counts the visits of each browser with a rocket server
+//!
+//! ```sh
+//! cargo run --example rocket --features rocket,memory
+//! curl -i localhost:8000
+//! ```
+
+use std::sync::Arc;
+
+use rocket::{get, http::Status, routes};
+use sessions::*;
+
+#[get("/")]
+fn count(session: Session) -> Result<String, Status> {
+    let count = session
+        .get::<u64>("count")
+        .map_err(|_| Status::InternalServerError)?
+        .unwrap_or_default()
+        + 1;
+    session
+        .set("count", count)
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(format!("{} visits", count))
+}
+
+#[rocket::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .cookie(CookieOptions::development())
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()?,
+    );
+
+    rocket::build()
+        .attach(SessionFairing::new(config))
+        .mount("/", routes![count])
+        .launch()
+        .await?;
+    Ok(())
+}
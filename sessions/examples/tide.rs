@@ -0,0 +1,35 @@
+//! Counts the visits of each browser with a tide server on async-std
+//!
+//! ```sh
+//! cargo run --example tide --features tide,memory
+//! curl -i localhost:8080
+//! ```
+
+use std::sync::Arc;
+
+use sessions::*;
+
+async fn count(req: tide::Request<()>) -> tide::Result<String> {
+    let session = req.ext::<Session>().expect("session middleware");
+    let count = session.get::<u64>("count")?.unwrap_or_default() + 1;
+    session.set("count", count)?;
+    Ok(format!("{} visits", count))
+}
+
+#[async_std::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .cookie(CookieOptions::development())
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()?,
+    );
+
+    let mut app = tide::new();
+    app.with(TideSessionMiddleware::new(config));
+    app.at("/").get(count);
+    app.listen("127.0.0.1:8080").await?;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Counts the visits of each browser with a viz router
+//!
+//! ```sh
+//! cargo run --example viz --features viz,memory
+//! curl -i localhost:3000
+//! ```
+
+use std::sync::Arc;
+
+use sessions::*;
+use tokio::net::TcpListener;
+use viz::{serve, Error, IntoHandler, Router};
+
+async fn count(session: Session) -> viz::Result<String> {
+    let count = session
+        .get::<u64>("count")
+        .map_err(Error::boxed)?
+        .unwrap_or_default()
+        + 1;
+    session.set("count", count).map_err(Error::boxed)?;
+    Ok(format!("{} visits", count))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .cookie(CookieOptions::development())
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()?,
+    );
+
+    let app = Router::new()
+        .get("/", count.into_handler())
+        .with(SessionTransform::new(config));
+    let listener = TcpListener::bind("127.0.0.1:3000").await?;
+    serve(listener, app).await?;
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Greets the logged in user of each WebSocket message with tokio-tungstenite,
+//! closing the socket once the session is destroyed elsewhere
+//!
+//! ```sh
+//! cargo run --example websocket --features http,memory
+//! ```
+//!
+//! The example stores a session and prints its cookie, pass it to a client,
+//! e.g. `websocat -H 'Cookie: viz.sid=...' ws://localhost:3000`.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::{SinkExt, StreamExt};
+use http::HeaderMap;
+use sessions::*;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::interval,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{Request, Response},
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+
+// The `Err` of the handshake callback is a whole response, as tungstenite wants
+#[allow(clippy::result_large_err)]
+async fn socket(stream: TcpStream, config: Arc<Config>) -> anyhow::Result<()> {
+    // The handshake callback isn't async, the session is loaded after it
+    let headers = Arc::new(Mutex::new(HeaderMap::new()));
+    let upgrade = headers.clone();
+    let mut ws =
+        tokio_tungstenite::accept_hdr_async(stream, move |req: &Request, res: Response| {
+            *upgrade.lock().unwrap() = req.headers().clone();
+            Ok(res)
+        })
+        .await?;
+
+    let headers = headers.lock().unwrap().clone();
+    let handle = match SessionHandle::load(&headers, &config).await? {
+        Some(handle) => handle,
+        None => {
+            ws.close(Some(CloseFrame {
+                code: CloseCode::Policy,
+                reason: "no session".into(),
+            }))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut revalidate = interval(Duration::from_secs(5));
+    loop {
+        tokio::select! {
+            _ = revalidate.tick() => {
+                if !handle.revalidate().await? {
+                    ws.close(Some(CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "logged out".into(),
+                    }))
+                    .await?;
+                    return Ok(());
+                }
+            }
+            msg = ws.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let user = handle.session().get::<String>("user")?.unwrap_or_default();
+                    ws.send(Message::text(format!("{}: {}", user, text))).await?;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .cookie(CookieOptions::development())
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()?,
+    );
+
+    // A logged in session, as if a login route saved it
+    let session = config.load_session("").await?;
+    session.set("user", "fundon")?;
+    session.save().await?;
+    println!("Cookie: {}={}", config.cookie().name, session.id()?);
+
+    let listener = TcpListener::bind("127.0.0.1:3000").await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = socket(stream, config).await {
+                eprintln!("socket failed: {}", e);
+            }
+        });
+    }
+}
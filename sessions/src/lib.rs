@@ -1,7 +1,52 @@
 pub use sessions_core::*;
 
+#[cfg(feature = "actix")]
+pub use sessions_actix::{SessionExt, SessionMiddleware, SessionMiddlewareService};
+
+#[cfg(feature = "derive")]
+pub use sessions_derive::SessionData;
+
+#[cfg(feature = "hyper")]
+pub use sessions_hyper::SessionService as HyperSessionService;
+
 #[cfg(feature = "memory")]
 pub use sessions_memory::MemoryStorage;
 
+#[cfg(feature = "ntex")]
+pub use sessions_ntex::{
+    SessionExt as NtexSessionExt, SessionMiddleware as NtexSessionMiddleware,
+    SessionMiddlewareService as NtexSessionMiddlewareService,
+};
+
+#[cfg(feature = "poem")]
+pub use sessions_poem::{SessionEndpoint, SessionMiddleware as PoemSessionMiddleware};
+
 #[cfg(feature = "redis")]
 pub use sessions_redis::{RedisStorage, Client as RedisClient};
+
+#[cfg(feature = "rocket")]
+pub use sessions_rocket::SessionFairing;
+
+#[cfg(feature = "salvo")]
+pub use sessions_salvo::{SessionDepotExt, SessionHandler as SalvoSessionHandler};
+
+#[cfg(feature = "sled")]
+pub use sessions_sled::SledStorage;
+
+#[cfg(feature = "tide")]
+pub use sessions_tide::SessionMiddleware as TideSessionMiddleware;
+
+#[cfg(feature = "tonic")]
+pub use sessions_tonic::{
+    apply_session_metadata, metadata_session_id, SessionLayer as TonicSessionLayer,
+    SessionService as TonicSessionService,
+};
+
+#[cfg(feature = "tower")]
+pub use sessions_tower::{SessionLayer, SessionService};
+
+#[cfg(feature = "tower-sessions")]
+pub use sessions_tower_sessions::{StorageAdapter, TowerSessionStoreAdapter};
+
+#[cfg(feature = "viz")]
+pub use sessions_viz::{SessionHandler, SessionTransform};
@@ -0,0 +1,125 @@
+#![cfg(all(feature = "axum", feature = "memory"))]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    body::{to_bytes, Body},
+    http::{
+        header::{COOKIE, LOCATION, SET_COOKIE},
+        Request, StatusCode,
+    },
+    routing::{get, post},
+    Extension, Router,
+};
+use futures_executor::block_on;
+use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
+
+use sessions::*;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Profile {
+    user: Option<String>,
+}
+
+fn config() -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn login(session: Session) -> StatusCode {
+    session.set("user", "fundon").unwrap();
+    session.renew_keeping_data().await.unwrap();
+    StatusCode::NO_CONTENT
+}
+
+async fn me(profile: TypedSession<Profile>) -> Result<String, StatusCode> {
+    profile.get().unwrap().user.ok_or(StatusCode::UNAUTHORIZED)
+}
+
+async fn logout(session: Session) -> StatusCode {
+    session.destroy().await.unwrap();
+    StatusCode::NO_CONTENT
+}
+
+fn routes() -> Router {
+    Router::new()
+        .route("/login", post(login))
+        .route("/me", get(me))
+        .route("/logout", post(logout))
+}
+
+async fn send(
+    app: &Router,
+    method: &str,
+    path: &str,
+    cookie: Option<&str>,
+) -> Result<(StatusCode, String, Option<String>)> {
+    let mut req = Request::builder().method(method).uri(path);
+    if let Some(cookie) = cookie {
+        req = req.header(COOKIE, cookie);
+    }
+    let res = app.clone().oneshot(req.body(Body::empty())?).await?;
+    let status = res.status();
+    let set_cookie = res
+        .headers()
+        .get(SET_COOKIE)
+        .map(|val| val.to_str().unwrap().to_string());
+    let body = to_bytes(res.into_body(), usize::MAX).await?;
+    Ok((status, String::from_utf8(body.to_vec())?, set_cookie))
+}
+
+#[test]
+fn login_logout() -> Result<()> {
+    block_on(async {
+        let config = config();
+        let app = routes().layer(SessionLayer::new(config.clone()));
+
+        let (status, _, set_cookie) = send(&app, "GET", "/me", None).await?;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(set_cookie, None);
+
+        let (status, _, set_cookie) = send(&app, "POST", "/login", None).await?;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let set_cookie = set_cookie.unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        let (status, body, _) = send(&app, "GET", "/me", Some(&cookie)).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "fundon");
+
+        let (status, _, set_cookie) = send(&app, "POST", "/logout", Some(&cookie)).await?;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(set_cookie, Some(config.cookie().to_removal_cookie()));
+
+        let (status, _, _) = send(&app, "GET", "/me", Some(&cookie)).await?;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn missing_layer() -> Result<()> {
+    block_on(async {
+        let (status, body, _) = send(&routes(), "GET", "/me", None).await?;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("`SessionLayer`"));
+
+        let app = routes().layer(Extension(OnRejection::Redirect("/misconfigured".into())));
+        let res = app
+            .oneshot(Request::get("/me").body(Body::empty())?)
+            .await?;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(res.headers()[LOCATION], "/misconfigured");
+
+        Ok(())
+    })
+}
@@ -0,0 +1,792 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sessions::*;
+
+#[test]
+fn http_date() {
+    assert_eq!(to_http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+    assert_eq!(
+        to_http_date(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+        "Sun, 06 Nov 1994 08:49:37 GMT"
+    );
+    assert_eq!(
+        to_http_date(UNIX_EPOCH + Duration::from_secs(951_868_799)),
+        "Tue, 29 Feb 2000 23:59:59 GMT"
+    );
+    assert_eq!(
+        to_http_date(UNIX_EPOCH - Duration::from_secs(1)),
+        "Thu, 01 Jan 1970 00:00:00 GMT"
+    );
+}
+
+#[test]
+fn expires() {
+    let expires = SystemTime::now() + Duration::from_secs(3600);
+    let options = CookieOptions::new().with_expires(expires);
+    assert_eq!(options.expires, Some(expires));
+    assert_eq!(CookieOptions::new().expires, None);
+}
+
+#[test]
+fn set_cookie() -> Result<(), CookieError> {
+    assert_eq!(
+        CookieOptions::new().to_set_cookie("abc")?,
+        "viz.sid=abc; Max-Age=86400; Path=/; HttpOnly"
+    );
+
+    let options = CookieOptions::new()
+        .with_name("sid".into())
+        .with_max_age(Duration::from_secs(3600))
+        .with_expires(UNIX_EPOCH + Duration::from_secs(784_111_777))
+        .with_domain("example.com".into())
+        .with_path("/app".into())
+        .with_secure(true)
+        .with_http_only(true)
+        .with_same_site(SameSite::Lax);
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "sid=abc; Max-Age=3600; Expires=Sun, 06 Nov 1994 08:49:37 GMT; \
+         Domain=example.com; Path=/app; Secure; HttpOnly; SameSite=Lax"
+    );
+
+    let options = CookieOptions::new()
+        .with_name("__Host-sid".into())
+        .with_secure(true)
+        .with_http_only(true)
+        .with_same_site(SameSite::Strict);
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "__Host-sid=abc; Max-Age=86400; Path=/; Secure; HttpOnly; SameSite=Strict"
+    );
+
+    // `SameSite=None` is always secure
+    let options = CookieOptions::new()
+        .with_secure(false)
+        .with_http_only(false)
+        .with_same_site(SameSite::None);
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "viz.sid=abc; Max-Age=86400; Path=/; Secure; SameSite=None"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn presets() -> Result<(), CookieError> {
+    assert_eq!(
+        CookieOptions::strict().to_set_cookie("abc")?,
+        "viz.sid=abc; Max-Age=86400; Path=/; Secure; HttpOnly; SameSite=Strict"
+    );
+    assert_eq!(
+        CookieOptions::lax_secure().to_set_cookie("abc")?,
+        "viz.sid=abc; Max-Age=86400; Path=/; Secure; HttpOnly; SameSite=Lax"
+    );
+    assert_eq!(
+        CookieOptions::development().to_set_cookie("abc")?,
+        "viz.sid=abc; Max-Age=86400; Path=/; HttpOnly; SameSite=Lax"
+    );
+    assert_eq!(CookieOptions::new().http_only, Some(true));
+    for options in &[
+        CookieOptions::strict(),
+        CookieOptions::lax_secure(),
+        CookieOptions::development(),
+    ] {
+        assert_eq!(options.validate(), Ok(()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn browser_session_cookie() -> Result<(), CookieError> {
+    let options = CookieOptions::new().without_max_age();
+    assert_eq!(options.max_age, None);
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "viz.sid=abc; Path=/; HttpOnly"
+    );
+    assert_eq!(
+        options
+            .with_expires(UNIX_EPOCH + Duration::from_secs(784_111_777))
+            .to_set_cookie("abc")?,
+        "viz.sid=abc; Expires=Sun, 06 Nov 1994 08:49:37 GMT; Path=/; HttpOnly"
+    );
+    assert_eq!(
+        CookieOptions::new().without_max_age().to_removal_cookie(),
+        "viz.sid=; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Path=/; HttpOnly"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn set_cookie_encodes_value() -> Result<(), CookieError> {
+    assert_eq!(
+        CookieOptions::new().to_set_cookie("a b;c,d\"e\\f%g=h")?,
+        "viz.sid=a%20b%3Bc%2Cd%22e%5Cf%25g=h; Max-Age=86400; Path=/; HttpOnly"
+    );
+    assert_eq!(
+        CookieOptions::new().to_set_cookie("é")?,
+        "viz.sid=%C3%A9; Max-Age=86400; Path=/; HttpOnly"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn encoded_round_trip() -> Result<(), CookieError> {
+    for options in &[
+        CookieOptions::new(),
+        CookieOptions::new().with_signing_key([1; 32]),
+        CookieOptions::new().with_encryption_key([1; 32]),
+    ] {
+        for value in &["a;b", "a=b", "a b", "é🦀", "%41", "a,b\"c\\d"] {
+            let header = request_cookie(&options.to_set_cookie(value)?);
+            assert!(header[options.name.len() + 1..]
+                .bytes()
+                .all(|b| b != b';' && b != b' ' && b != b',' && b.is_ascii()));
+            assert_eq!(
+                options.session_id(&format!("a=1; {}; b=2", header)),
+                Some(value.to_string())
+            );
+        }
+    }
+
+    // Malformed encodings are no session
+    let options = CookieOptions::new();
+    assert_eq!(options.session_id("viz.sid=a%2"), None);
+    assert_eq!(options.session_id("viz.sid=a%zz"), None);
+    assert_eq!(options.session_id("viz.sid=a%+f"), None);
+    assert_eq!(options.session_id("viz.sid=%FF"), None);
+
+    // Without encoding, values are used as is
+    let options = CookieOptions::new().with_encode_value(false);
+    assert_eq!(
+        options.to_set_cookie("a%41")?,
+        "viz.sid=a%41; Max-Age=86400; Path=/; HttpOnly"
+    );
+    assert_eq!(options.session_id("viz.sid=a%41"), Some("a%41".to_string()));
+    assert_eq!(options.to_set_cookie("a;b"), Err(CookieError::InvalidValue));
+
+    Ok(())
+}
+
+#[test]
+fn removal_cookie() {
+    let options = CookieOptions::new()
+        .with_name("__Host-sid".into())
+        .with_expires(SystemTime::now())
+        .with_secure(true)
+        .with_http_only(true);
+    assert_eq!(
+        options.to_removal_cookie(),
+        "__Host-sid=; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Path=/; Secure; HttpOnly"
+    );
+}
+
+/// Gets the attributes scoping a `Set-Cookie` header value, which browsers
+/// match to replace a cookie
+fn scope(set_cookie: &str) -> Vec<String> {
+    let mut attributes = set_cookie.split("; ");
+    let name = attributes.next().unwrap().split('=').next().unwrap();
+    let mut scope = vec![name.to_string()];
+    scope.extend(
+        attributes
+            .filter(|attr| {
+                attr.starts_with("Domain=") || attr.starts_with("Path=") || *attr == "Partitioned"
+            })
+            .map(String::from),
+    );
+    scope
+}
+
+#[test]
+fn removal_cookie_scope() -> Result<(), CookieError> {
+    for options in &[
+        CookieOptions::new(),
+        CookieOptions::new()
+            .with_name("sid".into())
+            .with_domain("example.com".into())
+            .with_path("/app".into()),
+        CookieOptions::host_prefixed("sid").with_partitioned(true),
+        CookieOptions::secure_prefixed("sid")
+            .with_domain(".example.com".into())
+            .with_signing_key([1; 32]),
+    ] {
+        let live = options.to_set_cookie("abc")?;
+        let removal = options.to_removal_cookie();
+        assert_eq!(scope(&live), scope(&removal), "{}", removal);
+        assert!(removal.contains("; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn prefixes() -> Result<(), CookieError> {
+    let options = CookieOptions::host_prefixed("sid").with_http_only(true);
+    assert_eq!(options.name, "__Host-sid");
+    assert_eq!(options.validate(), Ok(()));
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "__Host-sid=abc; Max-Age=86400; Path=/; Secure; HttpOnly"
+    );
+    assert_eq!(options.find_in("sid=def; __Host-sid=abc"), Some("abc"));
+
+    let options = CookieOptions::secure_prefixed("sid")
+        .with_domain("example.com".into())
+        .with_path("/app".into());
+    assert_eq!(options.name, "__Secure-sid");
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "__Secure-sid=abc; Max-Age=86400; Domain=example.com; Path=/app; Secure; HttpOnly"
+    );
+    assert_eq!(options.find_in("__Secure-sid=abc"), Some("abc"));
+
+    // Attempted violations are refused
+    assert_eq!(
+        CookieOptions::host_prefixed("sid")
+            .with_domain("example.com".into())
+            .to_set_cookie("abc"),
+        Err(CookieError::HostPrefixDomain)
+    );
+    assert_eq!(
+        CookieOptions::host_prefixed("sid")
+            .with_path("/app".into())
+            .to_set_cookie("abc"),
+        Err(CookieError::HostPrefixPath)
+    );
+    assert_eq!(
+        CookieOptions::host_prefixed("sid")
+            .with_secure(false)
+            .to_set_cookie("abc"),
+        Err(CookieError::InsecurePrefix)
+    );
+    assert_eq!(
+        CookieOptions::secure_prefixed("sid")
+            .with_secure(false)
+            .to_set_cookie("abc"),
+        Err(CookieError::InsecurePrefix)
+    );
+    assert_eq!(
+        CookieError::HostPrefixDomain.to_string(),
+        "cookie with a `__Host-` name can't have a domain"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn partitioned() -> Result<(), CookieError> {
+    let options = CookieOptions::new()
+        .with_same_site(SameSite::None)
+        .with_partitioned(true);
+    assert_eq!(options.validate(), Ok(()));
+    assert!(format!("{:?}", options).contains("partitioned: Some(true)"));
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "viz.sid=abc; Max-Age=86400; Path=/; Secure; HttpOnly; SameSite=None; Partitioned"
+    );
+
+    let options = CookieOptions::host_prefixed("sid").with_partitioned(true);
+    assert_eq!(options.validate(), Ok(()));
+    assert_eq!(
+        options.to_set_cookie("abc")?,
+        "__Host-sid=abc; Max-Age=86400; Path=/; Secure; HttpOnly; Partitioned"
+    );
+
+    assert_eq!(
+        CookieOptions::new().with_partitioned(true).validate(),
+        Err(CookieError::InsecurePartitioned)
+    );
+    assert_eq!(
+        CookieOptions::new()
+            .with_secure(false)
+            .with_partitioned(true)
+            .validate(),
+        Err(CookieError::InsecurePartitioned)
+    );
+    assert_eq!(
+        CookieOptions::new()
+            .with_secure(false)
+            .with_partitioned(false)
+            .validate(),
+        Ok(())
+    );
+    assert!(!CookieOptions::new()
+        .with_partitioned(false)
+        .to_set_cookie("abc")?
+        .contains("Partitioned"));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_chars() {
+    for name in &["viz;sid", "viz sid", "viz=sid", "viz\tsid", "viz,sid", "é"] {
+        let options = CookieOptions::new().with_name(name.to_string());
+        assert_eq!(
+            options.validate(),
+            Err(CookieError::InvalidName),
+            "{}",
+            name
+        );
+        assert_eq!(options.to_set_cookie("abc"), Err(CookieError::InvalidName));
+    }
+    for domain in &["example.com;", "example .com", "example.com/"] {
+        let options = CookieOptions::new().with_domain(domain.to_string());
+        assert_eq!(
+            options.to_set_cookie("abc"),
+            Err(CookieError::InvalidDomain)
+        );
+    }
+    assert_eq!(
+        CookieOptions::new()
+            .with_path("/a;b".into())
+            .to_set_cookie("abc"),
+        Err(CookieError::InvalidPath)
+    );
+    assert_eq!(
+        CookieOptions::new()
+            .with_domain(".example-1.com".into())
+            .validate(),
+        Ok(())
+    );
+
+    // Values are encoded instead
+    assert_eq!(
+        CookieOptions::new().to_set_cookie("a,b"),
+        Ok("viz.sid=a%2Cb; Max-Age=86400; Path=/; HttpOnly".to_string())
+    );
+}
+
+#[test]
+fn find_in() {
+    let options = CookieOptions::new();
+    assert_eq!(options.find_in("viz.sid=abc"), Some("abc"));
+    assert_eq!(options.find_in("a=1; viz.sid=abc; b=2"), Some("abc"));
+    assert_eq!(options.find_in("a=1;viz.sid=abc;b=2"), Some("abc"));
+    assert_eq!(
+        options.find_in("  a=1 ;  viz.sid = abc  ; b=2"),
+        Some("abc")
+    );
+    assert_eq!(options.find_in(r#"viz.sid="abc""#), Some("abc"));
+    assert_eq!(options.find_in("viz.sid=abc; viz.sid=def"), Some("abc"));
+    assert_eq!(options.find_in("viz.sid=; a=1"), Some(""));
+    assert_eq!(options.find_in("viz.sid=a=b"), Some("a=b"));
+    assert_eq!(options.find_in("xviz.sid=abc; viz.sidx=def"), None);
+    assert_eq!(options.find_in("a=1; b=2"), None);
+}
+
+#[test]
+fn find_in_malformed() {
+    let options = CookieOptions::new();
+    for header in &[
+        "",
+        ";",
+        ";;;",
+        "=",
+        "viz.sid",
+        "viz.sid;",
+        "=abc; viz.sid",
+        "\"",
+        "viz.sid=\"",
+        "viz.sid=\"abc",
+        "é=é; viz.sid",
+    ] {
+        let _ = options.find_in(header);
+    }
+    assert_eq!(options.find_in("viz.sid; viz.sid=abc"), Some("abc"));
+    assert_eq!(options.find_in("viz.sid=\""), Some("\""));
+    assert_eq!(options.find_in("viz.sid=\"abc"), Some("\"abc"));
+}
+
+#[test]
+fn validate() {
+    assert_eq!(CookieOptions::default(), CookieOptions::new());
+    assert_eq!(CookieOptions::new().validate(), Ok(()));
+    assert_eq!(
+        CookieOptions::new()
+            .with_name("__Host-sid".into())
+            .with_secure(true)
+            .validate(),
+        Ok(())
+    );
+    assert_eq!(
+        CookieOptions::new()
+            .with_same_site(SameSite::None)
+            .validate(),
+        Ok(())
+    );
+
+    let cases = vec![
+        (
+            CookieOptions::new().with_name(String::new()),
+            CookieError::EmptyName,
+        ),
+        (
+            CookieOptions::new().with_name("viz sid".into()),
+            CookieError::InvalidName,
+        ),
+        (
+            CookieOptions::new().with_name("viz;sid".into()),
+            CookieError::InvalidName,
+        ),
+        (
+            CookieOptions::new().with_domain("example.com;".into()),
+            CookieError::InvalidDomain,
+        ),
+        (
+            CookieOptions::new().with_path("/\n".into()),
+            CookieError::InvalidPath,
+        ),
+        (
+            CookieOptions::new()
+                .with_secure(false)
+                .with_same_site(SameSite::None),
+            CookieError::InsecureSameSiteNone,
+        ),
+        (
+            CookieOptions::new().with_name("__Secure-sid".into()),
+            CookieError::InsecurePrefix,
+        ),
+        (
+            CookieOptions::new().with_name("__Host-sid".into()),
+            CookieError::InsecurePrefix,
+        ),
+        (
+            CookieOptions::new()
+                .with_name("__Host-sid".into())
+                .with_secure(true)
+                .with_domain("example.com".into()),
+            CookieError::HostPrefixDomain,
+        ),
+        (
+            CookieOptions::new()
+                .with_name("__Host-sid".into())
+                .with_secure(true)
+                .with_path("/app".into()),
+            CookieError::HostPrefixPath,
+        ),
+    ];
+    for (options, err) in cases {
+        assert_eq!(options.validate(), Err(err), "{:?}", options);
+    }
+}
+
+/// Turns a `Set-Cookie` header value into a `Cookie` request header
+fn request_cookie(set_cookie: &str) -> String {
+    set_cookie.split(';').next().unwrap().to_string()
+}
+
+#[test]
+fn signed() -> Result<(), CookieError> {
+    let options = CookieOptions::new().with_signing_key([1; 32]);
+    let header = request_cookie(&options.to_set_cookie("abc")?);
+    assert!(header.starts_with("viz.sid=abc."));
+    assert_eq!(options.session_id(&header), Some("abc".to_string()));
+    assert_eq!(
+        options.session_id(&format!("a=1; {}; b=2", header)),
+        Some("abc".to_string())
+    );
+    assert_eq!(options.session_id("a=1"), None);
+
+    // Truncated
+    assert_eq!(options.session_id(&header[..header.len() - 1]), None);
+    assert_eq!(options.session_id("viz.sid=abc."), None);
+    assert_eq!(options.session_id("viz.sid=abc"), None);
+
+    // Flipped bit
+    let mut bytes = header.clone().into_bytes();
+    bytes["viz.sid=".len()] ^= 1;
+    assert_eq!(options.session_id(&String::from_utf8(bytes).unwrap()), None);
+    let mut bytes = header.clone().into_bytes();
+    *bytes.last_mut().unwrap() ^= 1;
+    assert_eq!(options.session_id(&String::from_utf8(bytes).unwrap()), None);
+
+    // Wrong key
+    let other = CookieOptions::new().with_signing_key([2; 32]);
+    assert_eq!(other.session_id(&header), None);
+
+    // Unsigned by default
+    assert_eq!(
+        CookieOptions::new().session_id(&header),
+        Some(header["viz.sid=".len()..].to_string())
+    );
+    assert_eq!(format!("{:?}", Key::from([1; 32])), "Key(..)");
+    assert_ne!(Key::generate().unwrap(), Key::generate().unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn encrypted() -> Result<(), CookieError> {
+    let sid = "0123456789abcdef";
+    let options = CookieOptions::new().with_encryption_key([1; 32]);
+    let header = request_cookie(&options.to_set_cookie(sid)?);
+    assert!(header.starts_with("viz.sid=v1."));
+    assert!(!header.contains(sid));
+    assert_ne!(header, request_cookie(&options.to_set_cookie(sid)?));
+
+    // Another instance with the same key
+    let other = CookieOptions::new().with_encryption_key([1; 32]);
+    assert_eq!(other.session_id(&header), Some(sid.to_string()));
+
+    // Failures are no session
+    let wrong = CookieOptions::new().with_encryption_key([2; 32]);
+    assert_eq!(wrong.session_id(&header), None);
+    assert_eq!(options.session_id(&header[..header.len() - 2]), None);
+    assert_eq!(options.session_id(&header.replace("v1.", "v2.")), None);
+    assert_eq!(options.session_id("viz.sid=v1.abc"), None);
+    assert_eq!(options.session_id("viz.sid=v1.."), None);
+    assert_eq!(options.session_id(&format!("viz.sid={}", sid)), None);
+    let renamed = CookieOptions::new()
+        .with_name("sid".into())
+        .with_encryption_key([1; 32]);
+    assert_eq!(
+        renamed.session_id(&header.replacen("viz.sid", "sid", 1)),
+        None
+    );
+
+    // Encrypted and signed
+    let options = options.with_signing_key([3; 32]);
+    let header = request_cookie(&options.to_set_cookie(sid)?);
+    assert_eq!(options.session_id(&header), Some(sid.to_string()));
+    assert_eq!(other.session_id(&header), None);
+
+    Ok(())
+}
+
+#[test]
+fn key_rotation() -> Result<(), CookieError> {
+    let a = Key::from([1; 32]);
+    let b = Key::from([2; 32]);
+
+    // Issued under key A
+    let options = CookieOptions::new().with_keys(a.clone(), vec![]);
+    let header = request_cookie(&options.to_set_cookie("abc")?);
+    assert_eq!(
+        options.verify(&header),
+        Some(VerifiedCookie {
+            session_id: "abc".into(),
+            reissue: false
+        })
+    );
+
+    // Rotated to key B, keeping A as previous
+    let options = CookieOptions::new().with_keys(b.clone(), vec![Key::from([3; 32]), a]);
+    let verified = options.verify(&header).unwrap();
+    assert_eq!(verified.session_id, "abc");
+    assert!(verified.reissue);
+
+    // The reissued cookie is signed with B
+    let reissued = request_cookie(&options.to_set_cookie(&verified.session_id)?);
+    assert_ne!(reissued, header);
+    assert_eq!(options.verify(&reissued).map(|v| v.reissue), Some(false));
+    let only_b = CookieOptions::new().with_keys(b, vec![]);
+    assert_eq!(only_b.session_id(&reissued), Some("abc".to_string()));
+    assert_eq!(only_b.session_id(&header), None);
+
+    // Same for encryption
+    let options = CookieOptions::new().with_encryption_keys([1; 32], vec![]);
+    let header = request_cookie(&options.to_set_cookie("abc")?);
+    let options = CookieOptions::new().with_encryption_keys([2; 32], vec![Key::from([1; 32])]);
+    let verified = options.verify(&header).unwrap();
+    assert_eq!(verified.session_id, "abc");
+    assert!(verified.reissue);
+    let reissued = request_cookie(&options.to_set_cookie(&verified.session_id)?);
+    assert_eq!(options.verify(&reissued).map(|v| v.reissue), Some(false));
+    assert_eq!(
+        CookieOptions::new()
+            .with_encryption_key([3; 32])
+            .session_id(&header),
+        None
+    );
+
+    Ok(())
+}
+
+fn request_cookies(set_cookies: &[String]) -> String {
+    set_cookies
+        .iter()
+        .map(|set_cookie| request_cookie(set_cookie))
+        .filter(|pair| !pair.ends_with('='))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[test]
+fn chunked() -> Result<(), CookieError> {
+    let options = CookieOptions::new()
+        .with_encryption_key([1; 32])
+        .with_signing_key([2; 32])
+        .with_chunk_size(16);
+    let sid = "0123456789abcdef";
+
+    // Short enough values keep the single cookie
+    let unchunked = CookieOptions::new().with_chunk_size(16);
+    let set_cookies = unchunked.to_set_cookies("abc", "")?;
+    assert_eq!(set_cookies, vec![unchunked.to_set_cookie("abc")?]);
+    assert_eq!(
+        unchunked.session_id(&request_cookies(&set_cookies)),
+        Some("abc".to_string())
+    );
+
+    // Growth: the single cookie is removed
+    let set_cookies = options.to_set_cookies(sid, "viz.sid=abc")?;
+    let header = request_cookies(&set_cookies);
+    let count = set_cookies.len() - 1;
+    assert!(count > 2);
+    for (index, set_cookie) in set_cookies[..count].iter().enumerate() {
+        assert!(set_cookie.starts_with(&format!("viz.sid.{}=", index)));
+        assert!(request_cookie(set_cookie).len() <= "viz.sid.0=".len() + 16);
+    }
+    assert_eq!(set_cookies[count], options.to_removal_cookie());
+    assert_eq!(options.session_id(&header), Some(sid.to_string()));
+    assert_eq!(
+        options.session_id(&format!("a=1; {}; b=2", header)),
+        Some(sid.to_string())
+    );
+
+    // Shrinkage: the stale chunks are removed
+    let shorter = options.clone().with_chunk_size(32);
+    let set_cookies = shorter.to_set_cookies(sid, &header)?;
+    let shrunk = set_cookies
+        .iter()
+        .filter(|set_cookie| !set_cookie.contains("Max-Age=0"))
+        .count();
+    assert!(shrunk < count);
+    let removed = set_cookies[shrunk..]
+        .iter()
+        .map(|set_cookie| request_cookie(set_cookie))
+        .collect::<Vec<_>>();
+    let expected = (shrunk..count)
+        .map(|index| format!("viz.sid.{}=", index))
+        .collect::<Vec<_>>();
+    assert_eq!(removed, expected);
+    assert_eq!(
+        shorter.session_id(&request_cookies(&set_cookies)),
+        Some(sid.to_string())
+    );
+    let set_cookies = options.to_set_cookies("abc", &header)?;
+    assert_eq!(
+        options.session_id(&request_cookies(&set_cookies)),
+        Some("abc".to_string())
+    );
+
+    // A missing or tampered chunk is no session
+    let chunks = header.split("; ").collect::<Vec<_>>();
+    let mut missing = chunks.clone();
+    missing.remove(1);
+    assert_eq!(options.session_id(&missing.join("; ")), None);
+    assert_eq!(options.session_id(&chunks[1..].join("; ")), None);
+    let mut reordered = chunks.clone();
+    reordered.swap(0, 1);
+    assert_eq!(
+        options.session_id(&reordered.join("; ")),
+        Some(sid.to_string())
+    );
+    let tampered = chunks[0].replace("viz.sid.0=", "viz.sid.0=a");
+    let mut tampered_chunks = chunks.clone();
+    tampered_chunks[0] = &tampered;
+    assert_eq!(options.session_id(&tampered_chunks.join("; ")), None);
+
+    // Chunks are only read when chunking is enabled
+    let disabled = CookieOptions::new()
+        .with_encryption_key([1; 32])
+        .with_signing_key([2; 32]);
+    assert_eq!(disabled.session_id(&header), None);
+
+    Ok(())
+}
+
+#[cfg(feature = "cookie")]
+#[test]
+fn cookie_crate() -> Result<(), CookieError> {
+    let options = CookieOptions::new()
+        .with_name("sid".into())
+        .with_max_age(Duration::from_secs(3600))
+        .with_expires(UNIX_EPOCH + Duration::from_secs(784_111_777))
+        .with_domain("example.com".into())
+        .with_path("/app".into())
+        .with_secure(true)
+        .with_http_only(true)
+        .with_same_site(SameSite::None)
+        .with_partitioned(true);
+
+    let cookie = options.to_cookie("abc");
+    assert_eq!(cookie.name(), "sid");
+    assert_eq!(cookie.value(), "abc");
+    assert_eq!(cookie.same_site(), Some(cookie::SameSite::None));
+    assert_eq!(cookie.partitioned(), Some(true));
+    assert_eq!(CookieOptions::from_cookie(&cookie), options);
+
+    // Both render the same attributes
+    let mut rendered = cookie
+        .to_string()
+        .split("; ")
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let mut expected = options
+        .to_set_cookie("abc")?
+        .split("; ")
+        .map(String::from)
+        .collect::<Vec<_>>();
+    rendered.sort();
+    expected.sort();
+    assert_eq!(rendered, expected);
+
+    let options = CookieOptions::development().without_max_age();
+    assert_eq!(
+        CookieOptions::from_cookie(&options.to_cookie("abc")),
+        options
+    );
+
+    let cookie = cookie::Cookie::parse("sid=abc; Path=/app; Max-Age=60; SameSite=Strict").unwrap();
+    let options = CookieOptions::from_cookie(&cookie);
+    assert_eq!(options.max_age, Some(Duration::from_secs(60)));
+    assert_eq!(options.same_site, Some(SameSite::Strict));
+    assert_eq!(options.http_only, None);
+    assert_eq!(options.to_cookie("abc").to_string(), cookie.to_string());
+
+    Ok(())
+}
+
+#[cfg(feature = "cookie")]
+#[test]
+fn sealed_cookie_crate() -> Result<(), CookieError> {
+    let options = CookieOptions::new()
+        .with_signing_key([1; 32])
+        .with_encryption_key([2; 32]);
+
+    let cookie = options.to_sealed_cookie("abc")?;
+    assert_eq!(cookie.name(), "viz.sid");
+    assert_ne!(cookie.value(), "abc");
+    assert_eq!(
+        options.verify_value(cookie.value()),
+        Some(VerifiedCookie {
+            session_id: "abc".to_string(),
+            reissue: false,
+        })
+    );
+    let other = CookieOptions::new().with_signing_key([3; 32]);
+    assert_eq!(other.verify_value(cookie.value()), None);
+    assert_eq!(
+        CookieOptions::new()
+            .verify_value("abc")
+            .map(|c| c.session_id),
+        Some("abc".to_string())
+    );
+
+    // Encoded values are decoded like in a `Cookie` header
+    let options = CookieOptions::new().with_encode_value(true);
+    let cookie = options.to_sealed_cookie("a b")?;
+    assert_eq!(cookie.value(), "a%20b");
+    assert_eq!(
+        options.verify_value(cookie.value()).map(|c| c.session_id),
+        Some("a b".to_string())
+    );
+
+    Ok(())
+}
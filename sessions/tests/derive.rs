@@ -0,0 +1,91 @@
+#![cfg(all(feature = "derive", feature = "memory"))]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures_executor::block_on;
+
+use sessions::*;
+
+#[derive(Debug, Default, PartialEq, SessionData)]
+struct Login {
+    user_id: u64,
+    #[session(key = "role_name")]
+    role: String,
+    #[session(skip)]
+    cached: Vec<u64>,
+    #[session(flash)]
+    message: Option<String>,
+}
+
+fn config() -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn load(id: &str, config: &Arc<Config>) -> Result<Session> {
+    let session = Session::new(id, SessionStatus::Unchanged, config.clone());
+    if let Some(data) = config.get(id).await? {
+        session.set_data(data)?;
+    }
+    Ok(session)
+}
+
+#[test]
+fn derive() -> Result<()> {
+    block_on(async {
+        let config = config();
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        assert_eq!(Login::load(&session)?, Login::default());
+
+        let mut login = Login::load(&session)?;
+        login.user_id = 1;
+        login.role = "admin".to_string();
+        login.cached = vec![1];
+        login.message = Some("Welcome".to_string());
+        login.store(&session)?;
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        assert_eq!(session.get("role_name")?, Some("admin".to_string()));
+        assert!(!session.contains_key("role"));
+        assert!(!session.contains_key("cached"));
+        assert_eq!(Login::user_id(&session)?, Some(1));
+
+        let mut login = Login::load(&session)?;
+        assert_eq!(login.role, "admin");
+        assert!(login.cached.is_empty());
+        assert_eq!(login.message.take(), Some("Welcome".to_string()));
+        assert!(!session.contains_key("message"));
+
+        login.user_id = 2;
+        login.store(&session)?;
+        Login::set_role(&session, "owner".to_string())?;
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        let login = Login::load(&session)?;
+        assert_eq!(login.user_id, 2);
+        assert_eq!(login.role, "owner");
+        assert_eq!(login.message, None);
+
+        // A stored value of another type is an error
+        session.set("user_id", "two")?;
+        assert!(Login::load(&session).is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn derive_errors() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
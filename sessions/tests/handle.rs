@@ -0,0 +1,102 @@
+#![cfg(all(feature = "http", feature = "memory"))]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures_executor::block_on;
+use http::{header::COOKIE, HeaderMap, HeaderValue};
+
+use sessions::*;
+
+fn config() -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn headers(config: &Config, sid: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        COOKIE,
+        HeaderValue::from_str(&format!("{}={}", config.cookie().name, sid)).unwrap(),
+    );
+    headers
+}
+
+#[test]
+fn load() -> Result<()> {
+    block_on(async {
+        let config = config();
+
+        // No cookie, no stored session, not authenticated
+        assert!(SessionHandle::load(&HeaderMap::new(), &config)
+            .await?
+            .is_none());
+        let sid = nanoid::nanoid!(32);
+        assert!(SessionHandle::load(&headers(&config, &sid), &config)
+            .await?
+            .is_none());
+
+        let session = config.load_session("").await?;
+        session.set("user", "fundon")?;
+        session.save().await?;
+
+        let handle = SessionHandle::load(&headers(&config, &session.id()?), &config)
+            .await?
+            .unwrap();
+        assert_eq!(handle.session().id()?, session.id()?);
+        assert_eq!(
+            handle.session().get::<String>("user")?,
+            Some("fundon".to_string())
+        );
+
+        // Clones share the session
+        let clone = handle.clone();
+        clone.session().set("theme", "dark")?;
+        assert_eq!(
+            handle.session().get::<String>("theme")?,
+            Some("dark".to_string())
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn revalidate() -> Result<()> {
+    block_on(async {
+        let config = config();
+        let session = config.load_session("").await?;
+        session.set("user", "fundon")?;
+        session.save().await?;
+        let handle = SessionHandle::load(&headers(&config, &session.id()?), &config)
+            .await?
+            .unwrap();
+        assert!(handle.revalidate().await?);
+
+        // Changes saved elsewhere are noticed
+        let elsewhere = config.load_session(&session.id()?).await?;
+        elsewhere.set("user", "trek")?;
+        elsewhere.save().await?;
+        assert!(handle.revalidate().await?);
+        assert_eq!(
+            handle.session().get::<String>("user")?,
+            Some("trek".to_string())
+        );
+
+        // A logout elsewhere kills the socket's session
+        elsewhere.destroy().await?;
+        let clone = handle.clone();
+        assert!(!handle.revalidate().await?);
+        assert!(clone.session().is_destroyed());
+        assert!(clone.session().set("user", "fundon").is_err());
+        assert!(!clone.revalidate().await?);
+
+        Ok(())
+    })
+}
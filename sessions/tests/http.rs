@@ -0,0 +1,235 @@
+#![cfg(all(feature = "http", feature = "memory"))]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures_executor::block_on;
+use http::{
+    header::{COOKIE, SET_COOKIE},
+    Request, Response,
+};
+
+use sessions::*;
+
+fn config(save_uninitialized: bool) -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .save_uninitialized(save_uninitialized)
+            .build()
+            .unwrap(),
+    )
+}
+
+fn request(cookie: Option<&str>) -> Request<()> {
+    let mut req = Request::get("/");
+    if let Some(cookie) = cookie {
+        req = req.header(COOKIE, cookie);
+    }
+    req.body(()).unwrap()
+}
+
+fn set_cookies(res: &Response<()>) -> Vec<String> {
+    res.headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|val| val.to_str().unwrap().to_string())
+        .collect()
+}
+
+/// The `Cookie` header a browser sends back after the `Set-Cookie` values
+fn request_cookies(set_cookies: &[String]) -> String {
+    set_cookies
+        .iter()
+        .filter(|set_cookie| !set_cookie.contains("Max-Age=0"))
+        .filter_map(|set_cookie| set_cookie.split(';').next())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[test]
+fn extract_and_apply() -> Result<()> {
+    block_on(async {
+        let config = config(false);
+
+        // A fresh session gets no cookie
+        let session = extract_session(&request(None), &config).await?;
+        assert!(session.is_new());
+        let mut res = Response::new(());
+        apply_session(&mut res, &session).await?;
+        assert!(set_cookies(&res).is_empty());
+
+        session.set("user", "fundon")?;
+        let mut res = Response::new(());
+        apply_session(&mut res, &session).await?;
+        let set_cookie = set_cookies(&res);
+        assert_eq!(set_cookie, vec![session.cookie_header()?.unwrap()]);
+        let cookie = set_cookie[0].split(';').next().unwrap().to_string();
+
+        // The cookie is found among others, the session is loaded
+        let req = Request::get("/")
+            .header(COOKIE, "theme=dark")
+            .header(COOKIE, cookie.as_str())
+            .body(())?;
+        assert_eq!(
+            request_session_id(req.headers(), &config),
+            Some(session.id()?)
+        );
+        let loaded = extract_session(&req, &config).await?;
+        assert_eq!(loaded.id()?, session.id()?);
+        assert_eq!(loaded.get::<String>("user")?, Some("fundon".to_string()));
+
+        // Unchanged, nothing to save or send
+        let mut res = Response::new(());
+        apply_session(&mut res, &loaded).await?;
+        assert!(set_cookies(&res).is_empty());
+
+        loaded.destroy().await?;
+        let mut res = Response::new(());
+        apply_session(&mut res, &loaded).await?;
+        assert_eq!(set_cookies(&res), vec![config.cookie().to_removal_cookie()]);
+
+        let session = extract_session(&request(Some(&cookie)), &config).await?;
+        assert!(session.is_new());
+        assert_ne!(session.id()?, loaded.id()?);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn save_uninitialized() -> Result<()> {
+    block_on(async {
+        let config = config(true);
+
+        let session = extract_session(&request(None), &config).await?;
+        let mut res = Response::new(());
+        apply_session(&mut res, &session).await?;
+        assert_eq!(set_cookies(&res).len(), 1);
+        assert!(config.get(&session.id()?).await?.is_some());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn reissue_and_chunks() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = |cookie: CookieOptions| {
+            Arc::new(
+                Config::builder()
+                    .storage(storage.clone())
+                    .generate(|| nanoid::nanoid!(32))
+                    .verify(|sid: &str| sid.len() == 32)
+                    .cookie(cookie)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let a = Key::from([1; 32]);
+        let b = Key::from([2; 32]);
+
+        // Issued under the key A
+        let old = config(CookieOptions::new().with_keys(a.clone(), vec![]));
+        let session = extract_session(&request(None), &old).await?;
+        session.set("user", "fundon")?;
+        let mut res = Response::new(());
+        apply_session(&mut res, &session).await?;
+        let cookie = request_cookies(&set_cookies(&res));
+
+        // Rotated to the key B, the unchanged session gets its cookie under B
+        let rotated = config(CookieOptions::new().with_keys(b.clone(), vec![a]));
+        let loaded = extract_session(&request(Some(&cookie)), &rotated).await?;
+        assert_eq!(loaded.id()?, session.id()?);
+        assert!(loaded.is_reissued());
+        let mut res = Response::new(());
+        apply_session(&mut res, &loaded).await?;
+        let reissued = set_cookies(&res);
+        assert_eq!(
+            reissued,
+            vec![rotated.cookie().to_set_cookie(&session.id()?)?]
+        );
+        let only_b = config(CookieOptions::new().with_keys(b, vec![]));
+        let req = request(Some(&request_cookies(&reissued)));
+        assert_eq!(
+            request_session_id(req.headers(), &only_b),
+            Some(session.id()?)
+        );
+
+        // Cookies under the active key aren't reissued
+        let loaded = extract_session(&req, &rotated).await?;
+        assert!(!loaded.is_reissued());
+        let mut res = Response::new(());
+        apply_session(&mut res, &loaded).await?;
+        assert!(set_cookies(&res).is_empty());
+
+        // Long values are split, the chunks replace the single cookie
+        let chunked = config(CookieOptions::new().with_chunk_size(16));
+        let session = extract_session(&request(Some("viz.sid=stale")), &chunked).await?;
+        assert!(session.is_new());
+        session.set("user", "fundon")?;
+        let mut res = Response::new(());
+        apply_session(&mut res, &session).await?;
+        let cookies = set_cookies(&res);
+        assert_eq!(cookies.len(), 3);
+        assert!(cookies[0].starts_with("viz.sid.0="));
+        assert!(cookies[1].starts_with("viz.sid.1="));
+        assert_eq!(cookies[2], chunked.cookie().to_removal_cookie());
+        let loaded = extract_session(&request(Some(&request_cookies(&cookies))), &chunked).await?;
+        assert_eq!(loaded.id()?, session.id()?);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn reissue_opaque_ids() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = |ids: OpaqueIds| {
+            Arc::new(
+                Config::builder()
+                    .storage(storage.clone())
+                    .generate(|| nanoid::nanoid!(32))
+                    .verify(|sid: &str| sid.len() == 32)
+                    .opaque_ids(ids)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let a = Key::from([1; 32]);
+        let b = Key::from([2; 32]);
+
+        let old = config(OpaqueIds::new(a.clone()));
+        let session = extract_session(&request(None), &old).await?;
+        session.set("user", "fundon")?;
+        let mut res = Response::new(());
+        apply_session(&mut res, &session).await?;
+        let cookie = request_cookies(&set_cookies(&res));
+
+        // Rotated to the key B, the unchanged session gets a token under B
+        let rotated = config(OpaqueIds::with_keys(b.clone(), vec![a]));
+        let loaded = extract_session(&request(Some(&cookie)), &rotated).await?;
+        assert_ne!(loaded.id()?, session.id()?);
+        assert_eq!(loaded.get::<String>("user")?, Some("fundon".to_string()));
+        let mut res = Response::new(());
+        apply_session(&mut res, &loaded).await?;
+        let reissued = set_cookies(&res);
+        assert_eq!(
+            reissued,
+            vec![rotated.cookie().to_set_cookie(&loaded.id()?)?]
+        );
+
+        let only_b = config(OpaqueIds::new(b));
+        let loaded = extract_session(&request(Some(&request_cookies(&reissued))), &only_b).await?;
+        assert!(!loaded.is_new() && !loaded.is_reissued());
+        let mut res = Response::new(());
+        apply_session(&mut res, &loaded).await?;
+        assert!(set_cookies(&res).is_empty());
+
+        Ok(())
+    })
+}
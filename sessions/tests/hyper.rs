@@ -0,0 +1,89 @@
+#![cfg(all(feature = "hyper", feature = "memory"))]
+
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use anyhow::Result;
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::{
+    body::{Bytes, Incoming},
+    client,
+    header::{COOKIE, SET_COOKIE},
+    server,
+    service::service_fn,
+    Request, Response,
+};
+use hyper_util::rt::TokioIo;
+use tokio::net::{TcpListener, TcpStream};
+
+use sessions::*;
+
+async fn handler(mut req: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    // Takes the session out of the request and drops the request first,
+    // the changes are saved anyway
+    let session = req.extensions_mut().remove::<Session>().unwrap();
+    drop(req);
+    let count = session.get::<u64>("count").unwrap().unwrap_or_default() + 1;
+    session.set("count", count).unwrap();
+    Ok(Response::new(Full::new(count.to_string().into())))
+}
+
+async fn serve(config: Arc<Config>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let service = HyperSessionService::new(service_fn(handler), config.clone());
+            tokio::spawn(
+                server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service),
+            );
+        }
+    });
+    Ok(addr)
+}
+
+async fn send(addr: SocketAddr, cookie: Option<&str>) -> Result<(String, Option<String>)> {
+    let stream = TcpStream::connect(addr).await?;
+    let (mut sender, conn) = client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(conn);
+
+    let mut req = Request::get("/").header("host", addr.to_string());
+    if let Some(cookie) = cookie {
+        req = req.header(COOKIE, cookie);
+    }
+    let res = sender
+        .send_request(req.body(Empty::<Bytes>::new())?)
+        .await?;
+    let set_cookie = res
+        .headers()
+        .get(SET_COOKIE)
+        .map(|val| val.to_str().unwrap().to_string());
+    let body = res.into_body().collect().await?.to_bytes();
+    Ok((String::from_utf8(body.to_vec())?, set_cookie))
+}
+
+#[tokio::test]
+async fn counter() -> Result<()> {
+    let config = Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()?,
+    );
+    let addr = serve(config.clone()).await?;
+
+    let (body, set_cookie) = send(addr, None).await?;
+    assert_eq!(body, "1");
+    let set_cookie = set_cookie.unwrap();
+    let cookie = set_cookie.split(';').next().unwrap();
+
+    let (body, _) = send(addr, Some(cookie)).await?;
+    assert_eq!(body, "2");
+
+    // A forged id gets a new session
+    let forged = format!("{}=forged", config.cookie().name);
+    let (body, _) = send(addr, Some(&forged)).await?;
+    assert_eq!(body, "1");
+
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use sessions::*;
+
+#[test]
+fn verify_id_structure() {
+    assert!(verify_id("0123456789abcdef"));
+    assert!(verify_id(&"a-_Z".repeat(MAX_ID_LENGTH / 4)));
+    assert!(verify_id(&nanoid::nanoid!(32)));
+
+    assert!(!verify_id(""));
+    assert!(!verify_id("0123456789abcde"));
+    assert!(!verify_id(&"a".repeat(MAX_ID_LENGTH + 1)));
+    assert!(!verify_id(&"a".repeat(10 * 1024)));
+    assert!(!verify_id("../../../etc/passwd"));
+    assert!(!verify_id("0123456789abcdef\0"));
+    assert!(!verify_id("01234567\089abcdef"));
+    assert!(!verify_id("0123456789abcdef;"));
+    assert!(!verify_id("0123456789abcdé"));
+}
+
+#[cfg(feature = "nanoid")]
+#[test]
+fn generate_id_sanity() {
+    use std::collections::HashSet;
+
+    let ids = (0..10_000).map(|_| generate_id()).collect::<HashSet<_>>();
+    assert_eq!(ids.len(), 10_000);
+
+    let mut chars = HashSet::new();
+    for id in &ids {
+        assert_eq!(id.len(), ID_LENGTH);
+        assert!(verify_id(id));
+        assert!(id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-'));
+        chars.extend(id.chars());
+    }
+    // The whole alphabet shows up
+    assert_eq!(chars.len(), 64);
+}
+
+#[cfg(all(feature = "nanoid", feature = "memory"))]
+#[test]
+fn default_generate() -> Result<()> {
+    use std::sync::Arc;
+
+    let config = Config::builder()
+        .storage(Arc::new(MemoryStorage::new()))
+        .build()?;
+    let id = config.generate();
+    assert!(config.verify(&id));
+    assert_ne!(id, config.generate());
+    assert!(config.validate().is_ok());
+
+    Ok(())
+}
+
+#[cfg(all(feature = "nanoid", feature = "memory"))]
+#[test]
+fn signed_ids() -> Result<()> {
+    use std::{sync::Arc, time::Duration};
+
+    use futures_executor::block_on;
+
+    let storage = Arc::new(MemoryStorage::new());
+    let key = Key::generate()?;
+    let signed = |accept_unsigned_ids| {
+        Config::builder()
+            .storage(storage.clone())
+            .sign_ids(key.clone())
+            .accept_unsigned_ids(accept_unsigned_ids)
+            .build()
+            .map(Arc::new)
+    };
+    let config = signed(false)?;
+
+    // `<id>.<mac>`, with a 128 bits MAC in unpadded base64url
+    let id = config.generate();
+    let (raw, tag) = id.split_once('.').unwrap();
+    assert_eq!(raw.len(), ID_LENGTH);
+    assert_eq!(tag.len(), 22);
+    assert!(verify_id(raw));
+    assert!(config.verify(&id));
+    assert!(config.validate().is_ok());
+
+    // Forged ids are rejected
+    let other = Config::builder()
+        .storage(storage.clone())
+        .sign_ids(Key::generate()?)
+        .build()?;
+    assert!(!config.verify(&other.generate()));
+    assert!(!config.verify(&format!("{}.{}", raw, "A".repeat(22))));
+    assert!(!config.verify(&format!("{}.{}", generate_id(), tag)));
+    assert!(!config.verify(&id[..id.len() - 1]));
+    assert!(!config.verify(&format!("{}.", raw)));
+    assert!(!config.verify(raw));
+    assert!(!config.verify(""));
+
+    block_on(async {
+        // The storage isn't read for a forged id
+        let forged = format!("{}.{}", raw, "A".repeat(22));
+        let mut data = Data::new();
+        data.insert("user".into(), "fundon".into());
+        storage.set(&forged, data, Duration::from_secs(60)).await?;
+        let session = config.load_session(&forged).await?;
+        assert!(session.is_new());
+        assert_ne!(session.id()?, forged);
+        assert!(config.verify(&session.id()?));
+
+        // Renewed sessions get signed ids
+        let mut session = config.load_session("").await?;
+        session.set("user", "fundon")?;
+        session.save().await?;
+        session.renew().await?;
+        let renewed = session.id()?;
+        assert!(renewed.contains('.') && config.verify(&renewed));
+        session.set("user", "fundon")?;
+        session.save().await?;
+        session.renew_keeping_data().await?;
+        let renewed_again = session.id()?;
+        assert_ne!(renewed_again, renewed);
+        assert!(renewed_again.contains('.') && config.verify(&renewed_again));
+        assert!(config
+            .load_session(&renewed_again)
+            .await?
+            .contains_key("user"));
+
+        Ok::<_, Error>(())
+    })?;
+
+    // Unsigned ids issued before are rejected, unless they're grandfathered
+    let legacy = generate_id();
+    assert!(!config.verify(&legacy));
+    let lenient = signed(true)?;
+    assert!(lenient.verify(&legacy));
+    assert!(lenient.verify(&id));
+    assert!(!lenient.verify(&other.generate()));
+    assert!(!lenient.verify("../../../etc/passwd"));
+    assert!(lenient.generate().contains('.'));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "nanoid", feature = "memory"))]
+#[test]
+fn opaque_ids() -> Result<()> {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use futures_executor::block_on;
+
+    let storage = Arc::new(MemoryStorage::new());
+    let opaque = |ids: OpaqueIds| {
+        Config::builder()
+            .storage(storage.clone())
+            .generate(|| format!("eu.{}", generate_id()))
+            .verify(|id: &str| id.strip_prefix("eu.").is_some_and(verify_id))
+            .opaque_ids(ids)
+            .build()
+            .map(Arc::new)
+    };
+    let old = Key::generate()?;
+    let config = opaque(OpaqueIds::new(old.clone()))?;
+
+    block_on(async {
+        let mut session = config.load_session("").await?;
+        session.set("user", "fundon")?;
+        session.save().await?;
+        let token = session.id()?;
+        assert!(config.verify(&token));
+        assert!(config.validate().is_ok());
+
+        // The token hides the id and its tenant hint, the storage only knows the id
+        let opened = OpaqueIds::new(old.clone()).open(&token).unwrap();
+        assert!(opened.id.starts_with("eu.") && !token.contains("eu."));
+        assert!(!opened.reissue);
+        let age = SystemTime::now().duration_since(opened.issued_at)?;
+        assert!(age < Duration::from_secs(5));
+        assert!(storage.get(&opened.id).await?.is_some());
+        assert!(storage.get(&token).await?.is_none());
+        assert_eq!(config.store_key(&token), opened.id);
+        let again = OpaqueIds::new(old.clone()).issue(&opened.id, SystemTime::now());
+        assert_ne!(again, token);
+        assert!(config.load_session(&again).await?.contains_key("user"));
+
+        // Tokens which can't be decrypted are missing sessions
+        let mut tampered = token.clone().into_bytes();
+        tampered[10] = if tampered[10] == b'A' { b'B' } else { b'A' };
+        let tampered = String::from_utf8(tampered)?;
+        for bad in [tampered.as_str(), &opened.id, "", "not a token"] {
+            assert!(!config.verify(bad));
+            assert!(config.get(bad).await?.is_none());
+            assert!(config.load_session(bad).await?.is_new());
+        }
+        // Nor is a token of an id the verifier rejects
+        let foreign = OpaqueIds::new(old.clone()).issue(&generate_id(), SystemTime::now());
+        assert!(!config.verify(&foreign));
+
+        let loaded = config.load_session(&token).await?;
+        assert!(!loaded.is_new());
+        assert_eq!(loaded.get::<String>("user")?, Some("fundon".to_string()));
+
+        // A renewed session gets a fresh token, the previous one can't be reused
+        session.renew().await?;
+        session.set("user", "fundon")?;
+        session.save().await?;
+        let renewed = session.id()?;
+        assert_ne!(renewed, token);
+        assert!(config.load_session(&token).await?.is_new());
+        assert!(config.load_session(&renewed).await?.contains_key("user"));
+        session.renew_keeping_data().await?;
+        assert!(config.load_session(&renewed).await?.is_new());
+        assert!(config
+            .load_session(&session.id()?)
+            .await?
+            .contains_key("user"));
+        let token = session.id()?;
+
+        // During a rotation, tokens of the previous key are still decrypted
+        let new = Key::generate()?;
+        let rotated = opaque(OpaqueIds::with_keys(new.clone(), vec![old.clone()]))?;
+        assert!(
+            OpaqueIds::with_keys(new.clone(), vec![old.clone()])
+                .open(&token)
+                .unwrap()
+                .reissue
+        );
+        let session = rotated.load_session(&token).await?;
+        assert_eq!(session.get::<String>("user")?, Some("fundon".to_string()));
+        // and issued again under the new key, the cookie is sent though unchanged
+        let reissued = session.id()?;
+        assert_ne!(reissued, token);
+        assert!(!OpaqueIds::new(new.clone()).open(&reissued).unwrap().reissue);
+        assert!(session.is_reissued());
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        let cookies = session.set_cookies()?;
+        assert_eq!(cookies.len(), 1);
+        assert!(cookies[0].contains(&reissued));
+        let current = rotated.load_session(&reissued).await?;
+        assert!(!current.is_reissued());
+        assert_eq!(current.id()?, reissued);
+
+        // Once the previous key is dropped, only the new tokens are decrypted
+        let current = opaque(OpaqueIds::new(new))?;
+        assert!(current.load_session(&token).await?.is_new());
+        assert!(current.load_session(&reissued).await?.contains_key("user"));
+        assert!(config.load_session(&reissued).await?.is_new());
+
+        // Sessions of a user are listed as tokens
+        session.bind_user("fundon")?;
+        session.save().await?;
+        let sids = current.sessions_for("fundon").await?;
+        assert_eq!(sids.len(), 1);
+        assert!(current.load_session(&sids[0]).await?.contains_key("user"));
+        assert_eq!(current.destroy_for("fundon").await?, 1);
+        assert!(current.load_session(&reissued).await?.is_new());
+
+        Ok::<_, Error>(())
+    })
+}
@@ -0,0 +1,170 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures_executor::block_on;
+
+use sessions::{
+    async_trait,
+    local::{LocalSession, LocalStorage},
+    Data, DataError, SessionStatus,
+};
+
+/// A storage which isn't `Send`
+#[derive(Debug, Default)]
+struct RcStorage {
+    inner: Rc<RefCell<HashMap<String, Data>>>,
+    sets: Cell<usize>,
+}
+
+#[async_trait(?Send)]
+impl LocalStorage for RcStorage {
+    async fn get(&self, key: &str) -> Result<Option<Data>> {
+        Ok(self.inner.borrow().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, val: Data, _exp: Duration) -> Result<()> {
+        self.sets.set(self.sets.get() + 1);
+        self.inner.borrow_mut().insert(key.to_string(), val);
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.inner.borrow_mut().remove(key);
+        Ok(())
+    }
+}
+
+#[test]
+fn local() -> Result<()> {
+    // `Rc` makes the storage and the session futures `!Send`
+    block_on(async {
+        let storage = Rc::new(RcStorage::default());
+
+        let session = LocalSession::new(
+            "sid",
+            SessionStatus::Unchanged,
+            storage.clone(),
+            Duration::from_secs(60),
+        );
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+
+        let loaded = LocalSession::new(
+            "sid",
+            SessionStatus::Unchanged,
+            storage.clone(),
+            Duration::from_secs(60),
+        );
+        if let Some(data) = storage.get("sid").await? {
+            loaded.set_data(data)?;
+        }
+        assert_eq!(loaded.get("crate")?, Some("sessions".to_string()));
+        assert_eq!(loaded.metadata().version, 1);
+
+        loaded.renew("new").await?;
+        assert_eq!(loaded.id(), "new");
+        assert!(storage.inner.borrow().contains_key("new"));
+
+        loaded.destroy().await?;
+        assert!(storage.inner.borrow().is_empty());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn local_save_twice() -> Result<()> {
+    block_on(async {
+        let storage = Rc::new(RcStorage::default());
+        let session = LocalSession::new(
+            "sid",
+            SessionStatus::Unchanged,
+            storage.clone(),
+            Duration::from_secs(60),
+        );
+
+        // Never written to, nothing is saved
+        session.save().await?;
+        assert_eq!(storage.sets.get(), 0);
+
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+        assert_eq!(session.status(), SessionStatus::Saved);
+        assert!(!session.data_status());
+
+        // Changes after the first save are saved too
+        session.set("user", "fundon".to_string())?;
+        assert_eq!(session.changed_keys(), vec!["user".to_string()]);
+        session.save().await?;
+        assert_eq!(storage.sets.get(), 2);
+        let stored = storage.get("sid").await?.unwrap();
+        assert_eq!(stored.get("crate"), Some(&"sessions".into()));
+        assert_eq!(stored.get("user"), Some(&"fundon".into()));
+        assert_eq!(session.metadata().version, 2);
+
+        // Identical writes and reads don't touch the storage
+        session.set("user", "fundon".to_string())?;
+        assert_eq!(
+            session.get::<String>("crate")?,
+            Some("sessions".to_string())
+        );
+        session.save().await?;
+        assert_eq!(storage.sets.get(), 2);
+
+        // Writing the stored values back isn't saved either
+        session.set("user", "viz".to_string())?;
+        session.set("user", "fundon".to_string())?;
+        session.save().await?;
+        assert_eq!(storage.sets.get(), 2);
+
+        // Clearing keeps the version and times
+        let metadata = session.metadata();
+        session.clear()?;
+        assert_eq!(session.metadata(), metadata);
+        assert_eq!(session.get::<String>("user")?, None);
+        session.save().await?;
+        assert_eq!(storage.sets.get(), 3);
+        assert_eq!(session.metadata().version, 3);
+        assert_eq!(session.metadata().created_at, metadata.created_at);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn local_destroy() -> Result<()> {
+    block_on(async {
+        let storage = Rc::new(RcStorage::default());
+        let session = LocalSession::new(
+            "sid",
+            SessionStatus::Unchanged,
+            storage.clone(),
+            Duration::from_secs(60),
+        );
+        session.set("user", "fundon".to_string())?;
+        session.save().await?;
+
+        session.destroy().await?;
+        assert_eq!(session.status(), SessionStatus::Destroyed);
+        assert!(storage.inner.borrow().is_empty());
+
+        // The data can't be read nor written afterwards
+        assert_eq!(session.get::<String>("user")?, None);
+        assert!(session.data().is_empty());
+        let err = session.set("user", "viz".to_string()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DataError>(),
+            Some(DataError::Destroyed)
+        ));
+        assert!(session.clear().is_err());
+        session.save().await?;
+        assert!(storage.inner.borrow().is_empty());
+
+        Ok(())
+    })
+}
@@ -1,52 +1,84 @@
 #![cfg(feature = "memory")]
 
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    iter::FromIterator,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
 
 use anyhow::Result;
 use futures_executor::block_on;
 
-use sessions::*;
+use sessions::{local::LocalSession, *};
+
+use serde::{Deserialize, Serialize};
+
+fn builder(storage: Arc<MemoryStorage>) -> ConfigBuilder {
+    Config::builder()
+        .storage(storage)
+        .generate(|| nanoid::nanoid!(32))
+        .verify(|sid: &str| sid.len() == 32)
+}
+
+fn config(storage: Arc<MemoryStorage>) -> Arc<Config> {
+    Arc::new(builder(storage).build().unwrap())
+}
+
+async fn load(id: &str, config: &Arc<Config>) -> Result<Session> {
+    let session = Session::new(id, SessionStatus::Unchanged, config.clone());
+    if let Some(data) = config.get(id).await? {
+        session.set_data(data)?;
+    }
+    Ok(session)
+}
 
 #[test]
 fn memory() -> Result<()> {
     block_on(async {
         let storage = Arc::new(MemoryStorage::new());
 
-        let config = Arc::new(Config {
-            cookie: CookieOptions::new(),
-            storage: storage.clone(),
-            generate: Box::new(|| nanoid::nanoid!(32)),
-            verify: Box::new(|sid: &str| sid.len() == 32),
-        });
+        let config = config(storage.clone());
+
+        assert!(config.ping().await.is_ok());
 
         let id = config.generate();
 
-        let session = Session::new(&id, 0, config.clone());
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
 
-        assert_eq!(session.set::<String>("crate", "sessions".to_string()), None);
+        assert_eq!(
+            session.set::<String>("crate", "sessions".to_string())?,
+            None
+        );
 
         assert!(session.save().await.is_ok());
 
-        assert_eq!(session.get("crate"), Some("sessions".to_string()));
+        assert_eq!(session.get("crate")?, Some("sessions".to_string()));
 
         assert_eq!(
-            session.remove::<String>("crate"),
+            session.remove::<String>("crate")?,
             Some("sessions".to_string())
         );
 
-        assert_eq!(session.remove::<String>("crate"), None);
+        assert_eq!(session.remove::<String>("crate")?, None);
 
-        assert_eq!(session.get::<String>("crate"), None);
+        assert_eq!(session.get::<String>("crate")?, None);
 
         assert!(session.clear().is_ok());
 
-        let mut session = Session::new(&id, 0, config.clone());
+        let mut session = Session::new(&id, SessionStatus::Unchanged, config.clone());
 
         if let Some(data) = storage.get(&id).await? {
             session.set_data(data)?;
         }
 
-        assert_eq!(session.get("crate"), Some("sessions".to_string()));
+        assert_eq!(session.get("crate")?, Some("sessions".to_string()));
 
         assert!(session.renew().await.is_ok());
 
@@ -57,3 +89,2041 @@ fn memory() -> Result<()> {
         Ok(())
     })
 }
+
+#[test]
+fn cleanup() -> Result<()> {
+    block_on(async {
+        let storage = MemoryStorage::new();
+
+        storage
+            .set("a", Data::new(), Duration::from_millis(10))
+            .await?;
+        storage
+            .set("b", Data::new(), Duration::from_millis(10))
+            .await?;
+        storage
+            .set("c", Data::new(), Duration::from_secs(60))
+            .await?;
+
+        assert_eq!(storage.cleanup().await?, 0);
+
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(storage.cleanup().await?, 2);
+        assert_eq!(storage.cleanup().await?, 0);
+
+        assert!(storage.get("a").await?.is_none());
+        assert!(storage.get("c").await?.is_some());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn users() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = config(storage.clone());
+
+        let laptop = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        laptop.bind_user("fundon")?;
+        laptop.save().await?;
+
+        let phone = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        phone.bind_user("fundon")?;
+        phone.save().await?;
+
+        let mut tablet = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        tablet.bind_user("fundon")?;
+        tablet.save().await?;
+
+        assert_eq!(tablet.user(), Some("fundon".to_string()));
+
+        let mut ids = config.sessions_for("fundon").await?;
+        ids.sort();
+        let mut expected = vec![laptop.id()?, phone.id()?, tablet.id()?];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        tablet.renew().await?;
+
+        assert_eq!(tablet.user(), None);
+        assert_eq!(config.sessions_for("fundon").await?.len(), 2);
+
+        storage
+            .set("expired", laptop.data()?, Duration::from_millis(10))
+            .await?;
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(config.sessions_for("fundon").await?.len(), 2);
+
+        assert_eq!(config.destroy_for("fundon").await?, 2);
+
+        assert!(config.sessions_for("fundon").await?.is_empty());
+        assert!(storage.get(&laptop.id()?).await?.is_none());
+        assert!(storage.get(&phone.id()?).await?.is_none());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn key_prefix() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = Arc::new(
+            Config::builder()
+                .storage(storage.clone())
+                .generate(|| nanoid::nanoid!(32))
+                .verify(|sid: &str| sid.len() == 32)
+                .key_prefix("myapp:")
+                .build()?,
+        );
+        let other = self::config(storage.clone());
+
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.bind_user("fundon")?;
+        session.save().await?;
+        let id = session.id()?;
+        assert!(storage.get(&id).await?.is_none());
+        assert!(storage.get(&format!("myapp:{}", id)).await?.is_some());
+        assert_eq!(config.get(&id).await?, session.data().ok());
+
+        // The cookie keeps the bare id
+        let cookie = session.cookie_header()?.unwrap();
+        assert!(cookie.starts_with(&format!("{}={};", config.cookie().name, id)));
+
+        session.touch().await?;
+        assert_eq!(session.status(), SessionStatus::Saved);
+
+        session.renew_keeping_data().await?;
+        let renewed = session.id()?;
+        assert!(storage.get(&format!("myapp:{}", id)).await?.is_none());
+        assert!(storage.get(&format!("myapp:{}", renewed)).await?.is_some());
+
+        // Sessions of other apps are left alone
+        let unprefixed = Session::new(&other.generate(), SessionStatus::Unchanged, other.clone());
+        unprefixed.bind_user("fundon")?;
+        unprefixed.save().await?;
+        assert_eq!(config.sessions_for("fundon").await?, vec![renewed.clone()]);
+        assert_eq!(config.destroy_for("fundon").await?, 1);
+        assert!(storage.get(&format!("myapp:{}", renewed)).await?.is_none());
+        assert_eq!(other.sessions_for("fundon").await?, vec![unprefixed.id()?]);
+
+        let mut session =
+            Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        let id = session.id()?;
+        session.renew().await?;
+        let fresh = session.id()?;
+        assert!(storage.get(&format!("myapp:{}", id)).await?.is_none());
+        assert!(storage.get(&format!("myapp:{}", fresh)).await?.is_some());
+        session.destroy().await?;
+        assert!(storage.get(&format!("myapp:{}", fresh)).await?.is_none());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn load_session() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+
+        // The first request has no cookie
+        let session = config.load_session("").await?;
+        assert!(session.is_new());
+        session.set("count", 1)?;
+        session.save().await?;
+        let set_cookie = session.cookie_header()?.unwrap();
+        let cookie = set_cookie.split(';').next().unwrap();
+        let id = config.cookie().session_id(cookie).unwrap();
+        assert_eq!(id, session.id()?);
+
+        // The next one sends it back
+        let session = config.load_session(&id).await?;
+        assert!(!session.is_new());
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        assert_eq!(session.get::<i32>("count")?, Some(1));
+        session.set("count", 2)?;
+        session.save().await?;
+        assert_eq!(
+            config.get(&id).await?.unwrap().get("count"),
+            Some(&serde_json::json!(2))
+        );
+
+        // A background job invalidates it by id
+        config.remove(&id).await?;
+        let session = config.load_session(&id).await?;
+        assert!(session.is_new());
+        assert_ne!(session.id()?, id);
+        assert_eq!(session.get::<i32>("count")?, None);
+
+        // Unverified ids never reach the storage
+        config.set("short", Data::new(), config.store_ttl()).await?;
+        assert!(config.load_session("short").await?.is_new());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn conflict() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("count", 0)?;
+        session.save().await?;
+        assert_eq!(session.version()?, 1);
+
+        let first = load(&id, &config).await?;
+        let second = load(&id, &config).await?;
+
+        first.set("theme", "dark".to_string())?;
+        second.set("locale", "en".to_string())?;
+
+        first.save_with_conflict_check().await?;
+        assert_eq!(first.version()?, 2);
+
+        let err = second.save_with_conflict_check().await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Conflict>(),
+            Some(&Conflict {
+                expected: 1,
+                found: 2
+            })
+        );
+
+        let second = load(&id, &config).await?;
+        second.set("locale", "en".to_string())?;
+        second.save_with_conflict_check().await?;
+
+        let session = load(&id, &config).await?;
+        assert_eq!(session.version()?, 3);
+        assert_eq!(session.get("theme")?, Some("dark".to_string()));
+        assert_eq!(session.get("locale")?, Some("en".to_string()));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn conflict_race() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let id = config.generate();
+
+    let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+    session.set("count", 0)?;
+    block_on(session.save())?;
+
+    let handles = (0..8)
+        .map(|_| {
+            let id = id.clone();
+            let config = config.clone();
+            thread::spawn(move || {
+                block_on(async {
+                    loop {
+                        let session = load(&id, &config).await?;
+                        let count = session.get::<u64>("count")?.unwrap_or(0);
+                        session.set("count", count + 1)?;
+                        match session.save_with_conflict_check().await {
+                            Err(e) if e.is::<Conflict>() => continue,
+                            res => return res,
+                        }
+                    }
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    let session = block_on(load(&id, &config))?;
+    assert_eq!(session.get("count")?, Some(8));
+    assert_eq!(session.version()?, 9);
+
+    Ok(())
+}
+
+#[test]
+fn lock() -> Result<()> {
+    block_on(async {
+        let storage = MemoryStorage::new();
+
+        let first = storage.lock("sid", Duration::from_millis(20)).await?;
+        assert!(
+            !storage
+                .try_lock("sid", "other", Duration::from_secs(1))
+                .await?
+        );
+
+        // The first holder never releases, the lock expires on its own
+        let second = storage.lock("sid", Duration::from_secs(60)).await?;
+
+        // An expired holder must not release the lock taken over by another
+        assert!(!first.release().await?);
+        assert!(
+            !storage
+                .try_lock("sid", "other", Duration::from_secs(1))
+                .await?
+        );
+
+        assert!(second.release().await?);
+        assert!(!second.release().await?);
+
+        let third = storage.lock("sid", Duration::from_secs(60)).await?;
+        assert!(!second.release().await?);
+        assert!(third.release().await?);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn with_lock() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let id = config.generate();
+
+    let handles = (0..2)
+        .map(|_| {
+            let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+            thread::spawn(move || {
+                block_on(
+                    session.with_lock(Duration::from_secs(5), |session| async move {
+                        let count = session.get::<u64>("count")?.unwrap_or(0);
+                        thread::sleep(Duration::from_millis(20));
+                        session.set("count", count + 1)?;
+                        Ok(())
+                    }),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    let session = block_on(load(&id, &config))?;
+    assert_eq!(session.get("count")?, Some(2));
+
+    Ok(())
+}
+
+#[test]
+fn metadata() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = config(storage.clone());
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        assert_eq!(session.created_at(), None);
+        assert_eq!(session.last_accessed(), None);
+
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+
+        let created_at = session.created_at().unwrap();
+        assert_eq!(session.last_accessed(), Some(created_at));
+        assert_eq!(config.metadata(&id).await?, Some(session.metadata()?));
+
+        thread::sleep(Duration::from_millis(10));
+
+        let session = load(&id, &config).await?;
+        assert_eq!(session.created_at(), Some(created_at));
+        session.set("crate", "sessions-core".to_string())?;
+        session.save().await?;
+
+        let meta = config.metadata(&id).await?.unwrap();
+        assert_eq!(meta.version, 2);
+        assert_eq!(meta.created_at, Some(created_at));
+        assert!(meta.last_accessed.unwrap() > created_at);
+
+        assert_eq!(config.metadata(&format!("{:_<32}", "missing")).await?, None);
+
+        // Entries saved without metadata are still readable
+        let mut data = Data::new();
+        data.insert("crate".into(), "sessions".into());
+        let legacy = format!("{:_<32}", "legacy");
+        storage.set(&legacy, data, Duration::from_secs(60)).await?;
+
+        assert_eq!(config.metadata(&legacy).await?, Some(Metadata::default()));
+
+        let session = load(&legacy, &config).await?;
+        assert_eq!(session.created_at(), None);
+        assert_eq!(session.get("crate")?, Some("sessions".to_string()));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn renew_keeping_data() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = config(storage.clone());
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        session.bind_user("fundon")?;
+        session.save().await?;
+
+        let state = storage.get(&id).await?;
+
+        let session = load(&id, &config).await?;
+        session.renew_keeping_data().await?;
+
+        let new = session.id()?;
+        assert_ne!(id, new);
+        assert_eq!(session.status(), SessionStatus::Renewed);
+        assert!(storage.get(&id).await?.is_none());
+        assert_eq!(storage.get(&new).await?, state);
+        assert_eq!(config.sessions_for("fundon").await?, vec![new.clone()]);
+
+        let other = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        other.set("user", "other")?;
+        other.save().await?;
+        assert!(config.rename(&other.id()?, &new).await.is_err());
+        assert!(storage.get(&other.id()?).await?.is_some());
+        assert_eq!(storage.get(&new).await?, state);
+
+        // A session which was never saved only changes its id
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.renew_keeping_data().await?;
+        assert!(storage.get(&session.id()?).await?.is_none());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn local() -> Result<()> {
+    block_on(async {
+        let storage = Rc::new(MemoryStorage::new());
+
+        let session = LocalSession::new(
+            "sid",
+            SessionStatus::Unchanged,
+            storage.clone(),
+            Duration::from_secs(60),
+        );
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+
+        assert_eq!(session.metadata().version, 1);
+
+        // The same storage works on both sides
+        let data = Storage::get(&*storage, "sid").await?;
+        assert_eq!(
+            data.as_ref().and_then(|d| d.get("crate")),
+            Some(&"sessions".into())
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn get_or_insert() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        let cart: Vec<u64> = session.get_or_insert_with("cart", Vec::new)?;
+        assert!(cart.is_empty());
+        assert!(session.data_status());
+        session.set("cart", vec![1u64, 2])?;
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        let cart: Vec<u64> = session.get_or_insert_with("cart", || unreachable!())?;
+        assert_eq!(cart, vec![1, 2]);
+        assert!(!session.data_status());
+        assert_eq!(session.get_or_insert("count", 0u64)?, 0);
+        assert!(session.data_status());
+
+        // An existing value of another type is an error and stays as is
+        let session = load(&id, &config).await?;
+        let err = session.get_or_insert::<u64>("crate", 0).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(DataError::Deserialize { key, .. }) if key == "crate")
+        );
+        assert_eq!(session.get("crate")?, Some("sessions".to_string()));
+        assert!(!session.data_status());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn entry() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session
+            .entry("cart")?
+            .or_default::<Vec<u64>>()?
+            .and_modify(|cart| cart.as_array_mut().unwrap().push(1.into()));
+        assert_eq!(session.get("cart")?, Some(vec![1u64]));
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        let entry = session.entry("cart")?.or_default::<Vec<u64>>()?;
+        assert_eq!(entry.key(), "cart");
+        entry.and_modify(|_| {});
+        assert!(!session.data_status());
+
+        session
+            .entry("cart")?
+            .and_modify_as(|cart: &mut Vec<u64>| cart.push(2))?;
+        assert_eq!(session.get("cart")?, Some(vec![1u64, 2]));
+        assert!(session.data_status());
+
+        let session = load(&id, &config).await?;
+        session
+            .entry("cart")?
+            .insert(vec![1u64])?
+            .and_modify_as(|cart: &mut Vec<u64>| cart.retain(|n| *n > 1))?;
+        assert_eq!(session.get("cart")?, Some(Vec::<u64>::new()));
+
+        assert!(session
+            .entry("cart")?
+            .and_modify_as(|_: &mut String| {})
+            .is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn contains_key() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        assert!(session.is_empty());
+        assert!(!session.contains_key("crate"));
+
+        session.set("crate", "sessions".to_string())?;
+        session.bind_user("fundon")?;
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        assert!(!session.is_empty());
+        assert!(session.contains_key("crate"));
+        assert!(session.has_any(&["token", keys::USER]));
+        assert!(!session.has_any(&["token", "csrf"]));
+        assert!(!session.has_any(&[]));
+        assert!(!session.data_status());
+
+        session.clear()?;
+        assert!(session.is_empty());
+        assert!(!session.contains_key("crate"));
+        assert!(!session.has_any(&["crate", keys::USER]));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn iter() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        session.set("count", 1u64)?;
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        let mut keys = session.keys();
+        keys.sort();
+        assert_eq!(keys, vec!["count", "crate"]);
+        assert_eq!(session.len(), 2);
+
+        let mut pairs = session.iter().collect::<Vec<_>>();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            pairs,
+            vec![
+                ("count".to_string(), 1.into()),
+                ("crate".to_string(), "sessions".into())
+            ]
+        );
+
+        session.remove::<u64>("count")?;
+        assert_eq!(session.keys(), vec!["crate"]);
+        let mut seen = Vec::new();
+        session.for_each(|key, val| seen.push((key.to_string(), val.clone())));
+        assert_eq!(seen, vec![("crate".to_string(), "sessions".into())]);
+
+        session.clear()?;
+        assert!(session.keys().is_empty());
+        assert_eq!(session.iter().count(), 0);
+        assert_eq!(session.len(), 0);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn take() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+
+    assert_eq!(session.take::<String>("token")?, None);
+    assert!(!session.data_status());
+
+    session.set("flash", "saved".to_string())?;
+    assert_eq!(session.take_raw("flash")?, Some("saved".into()));
+    assert_eq!(session.take_raw("flash")?, None);
+
+    // A value of the wrong type is an error, and is taken anyway
+    session.set("count", "one")?;
+    assert!(session
+        .take::<u32>("count")
+        .unwrap_err()
+        .downcast_ref::<DataError>()
+        .is_some_and(|e| matches!(e, DataError::Deserialize { key, .. } if key == "count")));
+    assert!(!session.contains_key("count"));
+
+    session.set("token", "secret".to_string())?;
+    let handles = (0..2)
+        .map(|_| {
+            let session = session.clone();
+            thread::spawn(move || session.take::<String>("token").unwrap())
+        })
+        .collect::<Vec<_>>();
+    let taken = handles
+        .into_iter()
+        .filter_map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(taken, vec!["secret".to_string()]);
+    assert!(!session.contains_key("token"));
+
+    Ok(())
+}
+
+#[test]
+fn modify() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+
+    assert!(session
+        .modify("cart", |cart: &mut Vec<u64>| cart.push(1))
+        .is_err());
+    assert!(!session.data_status());
+
+    let len = session.modify_or_default("cart", |cart: &mut Vec<u64>| {
+        cart.push(1);
+        cart.len()
+    })?;
+    assert_eq!(len, 1);
+    assert!(session.data_status());
+    assert_eq!(session.get("cart")?, Some(vec![1u64]));
+
+    let session = Session::new(&session.id()?, SessionStatus::Unchanged, config);
+    session.set_data(Data::from_iter(vec![(
+        "cart".to_string(),
+        vec![1u64].into(),
+    )]))?;
+    session.modify("cart", |cart: &mut Vec<u64>| cart.sort())?;
+    assert!(!session.data_status());
+    let err = session.modify("cart", |_: &mut String| ()).unwrap_err();
+    assert!(
+        matches!(err.downcast_ref(), Some(DataError::Deserialize { key, .. }) if key == "cart")
+    );
+
+    // A panicking closure leaves the value and the lock intact
+    let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+        session.modify("cart", |cart: &mut Vec<u64>| {
+            cart.push(2);
+            panic!("boom")
+        })
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(session.get("cart")?, Some(vec![1u64]));
+    session.modify("cart", |cart: &mut Vec<u64>| cart.push(2))?;
+    assert_eq!(session.get("cart")?, Some(vec![1u64, 2]));
+    assert!(session.data_status());
+
+    Ok(())
+}
+
+#[test]
+fn poisoned() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+    session.set("cart", vec![1u64])?;
+
+    // Panics while holding the write lock, poisoning it
+    let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+        session.entry("cart").map(|entry| {
+            entry.and_modify(|_| panic!("boom"));
+        })
+    }));
+    assert!(panicked.is_err());
+
+    assert_eq!(session.get("cart")?, Some(vec![1u64]));
+    session.set("cart", vec![1u64, 2])?;
+    assert_eq!(session.get("cart")?, Some(vec![1u64, 2]));
+    session.modify("cart", |cart: &mut Vec<u64>| cart.push(3))?;
+    assert_eq!(session.len(), 1);
+
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    struct Basket {
+        cart: Vec<u64>,
+    }
+
+    let typed = TypedSession::<Basket>::new(session);
+    let panicked = panic::catch_unwind(AssertUnwindSafe(|| typed.with_mut(|_| panic!("boom"))));
+    assert!(panicked.is_err());
+    assert_eq!(typed.get()?.cart, vec![1, 2, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn data_errors() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+    session.set("user_id", 1u64)?;
+
+    assert_eq!(session.get::<u64>("missing")?, None);
+    let err = session.get::<String>("user_id").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<DataError>(),
+        Some(DataError::Deserialize { key, .. }) if key == "user_id"
+    ));
+
+    let err = session.replace("user_id", "one".to_string()).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<DataError>(),
+        Some(DataError::Deserialize { .. })
+    ));
+    // The value was replaced anyway
+    assert_eq!(
+        session.replace("user_id", "two".to_string())?,
+        Some("one".to_string())
+    );
+    session.set("user_id", 1u64)?;
+    let err = session.remove::<String>("user_id").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<DataError>(),
+        Some(DataError::Deserialize { .. })
+    ));
+    assert!(!session.contains_key("user_id"));
+
+    let mut map = BTreeMap::new();
+    map.insert((1u8, 2u8), "tuple keys aren't strings".to_string());
+    let err = session.set("map", map).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<DataError>(),
+        Some(DataError::Serialize { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn set_serialize_only() -> Result<()> {
+    // Borrows its fields, so it can't be deserialized
+    #[derive(Serialize)]
+    struct Token<'a> {
+        issuer: &'a str,
+        subject: &'a str,
+    }
+
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+    let issuer = String::from("upstream");
+
+    let prev = session.set(
+        "token",
+        Token {
+            issuer: &issuer,
+            subject: "fundon",
+        },
+    )?;
+    assert_eq!(prev, None);
+
+    let prev = session.set(
+        "token",
+        Token {
+            issuer: &issuer,
+            subject: "admin",
+        },
+    )?;
+    assert_eq!(prev.unwrap()["subject"], "fundon");
+    assert_eq!(
+        session.replace("token", BTreeMap::<String, String>::new())?,
+        Some(BTreeMap::from_iter(vec![
+            ("issuer".to_string(), "upstream".to_string()),
+            ("subject".to_string(), "admin".to_string()),
+        ]))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn raw_values() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+
+    session.set_value("identity", serde_json::json!({ "sub": "fundon" }))?;
+    assert!(session.data_status());
+    assert_eq!(
+        session.get::<BTreeMap<String, String>>("identity")?,
+        Some(BTreeMap::from_iter(vec![(
+            "sub".to_string(),
+            "fundon".to_string()
+        )]))
+    );
+
+    session.set("role", "admin")?;
+    assert_eq!(session.get_value("role"), Some("admin".into()));
+    assert_eq!(session.get_value("missing"), None);
+
+    let session = Session::new(&session.id()?, SessionStatus::Unchanged, config);
+    session.set_data(Data::from_iter(vec![("role".to_string(), "admin".into())]))?;
+
+    // Setting the same value isn't a change
+    assert_eq!(
+        session.set_value("role", "admin".into())?,
+        Some("admin".into())
+    );
+    assert!(!session.data_status());
+
+    // No type annotation needed to discard the removed value
+    session.remove_value("missing")?;
+    assert!(!session.data_status());
+    assert_eq!(session.remove_value("role")?, Some("admin".into()));
+    assert_eq!(session.changed_keys(), vec!["role".to_string()]);
+    assert_eq!(session.get::<String>("role")?, None);
+
+    Ok(())
+}
+
+#[test]
+fn snapshot() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "admin")?;
+        session.set("roles", vec!["admin", "staff"])?;
+        session.save().await?;
+
+        let admin = session.snapshot()?;
+        let before = serde_json::to_vec(&admin.data)?;
+        assert_eq!(admin.id, id);
+        assert_eq!(admin.version, 1);
+        assert!(!admin.data.contains_key(keys::VERSION));
+
+        // Impersonates a user, keeping the admin state in the session itself
+        session.clear()?;
+        session.set("user", "fundon")?;
+        session.set("impersonator", &admin)?;
+        session.save().await?;
+
+        let admin = session.get::<StateSnapshot>("impersonator")?.unwrap();
+        let meta = session.metadata()?;
+        session.restore(admin)?;
+        let mut changed = session.changed_keys();
+        changed.sort();
+        assert_eq!(changed, vec!["impersonator", "roles", "user"]);
+        assert_eq!(serde_json::to_vec(&session.snapshot()?.data)?, before);
+        assert_eq!(session.metadata()?, meta);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn export() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.set("cart", vec![1u64, 2])?;
+        session.save().await?;
+
+        let export = session.export()?;
+        assert_eq!(export.id, id);
+        assert_eq!(export.status, SessionStatus::Saved);
+        assert_eq!(export.version, 1);
+        assert_eq!(export.created_at, session.created_at());
+        assert!(!export.data.contains_key(keys::VERSION));
+
+        let json = serde_json::to_string(&export)?;
+        assert!(json.contains(r#""status":"saved""#));
+        let export: SessionExport = serde_json::from_str(&json)?;
+        assert_eq!(export, session.export()?);
+
+        // Moves the session to another store
+        let other = self::config(Arc::new(MemoryStorage::new()));
+        let imported = Session::import(export, other.clone());
+        assert_eq!(imported.id()?, id);
+        assert_eq!(imported.status(), SessionStatus::Unchanged);
+        assert!(imported.data_status());
+        assert_eq!(imported.created_at(), session.created_at());
+        imported.save().await?;
+
+        let loaded = load(&id, &other).await?;
+        assert_eq!(loaded.get::<String>("user")?, Some("fundon".to_string()));
+        assert_eq!(loaded.get::<Vec<u64>>("cart")?, Some(vec![1, 2]));
+        assert_eq!(loaded.version()?, 2);
+        assert_eq!(loaded.created_at(), session.created_at());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn namespace() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        let auth = session.namespace("auth");
+        let cart = session.namespace("cart");
+        auth.set("id", 1u64)?;
+        cart.set("id", "c-42")?;
+        cart.set("items", vec![1u64, 2])?;
+        session.set("id", true)?;
+
+        assert_eq!(auth.get::<u64>("id")?, Some(1));
+        assert_eq!(cart.get::<String>("id")?, Some("c-42".to_string()));
+        assert_eq!(session.get::<bool>("id")?, Some(true));
+
+        let promo = cart.namespace("promo");
+        assert_eq!(promo.name(), "cart.promo");
+        promo.set("code", "SPRING")?;
+        assert_eq!(cart.get_value("promo.code"), Some("SPRING".into()));
+
+        let mut keys = cart.keys();
+        keys.sort();
+        assert_eq!(keys, vec!["id", "items", "promo.code"]);
+        assert_eq!(
+            promo.iter().collect::<Vec<_>>(),
+            vec![("code".to_string(), "SPRING".into())]
+        );
+        assert_eq!(auth.len(), 1);
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        let cart = session.namespace("cart");
+        assert_eq!(cart.get::<Vec<u64>>("items")?, Some(vec![1, 2]));
+
+        // Clears the namespace and the nested ones only
+        cart.clear()?;
+        assert!(cart.is_empty());
+        assert!(session.namespace("cart").namespace("promo").is_empty());
+        assert_eq!(session.namespace("auth").get::<u64>("id")?, Some(1));
+        assert_eq!(session.get::<bool>("id")?, Some(true));
+        let mut changed = session.changed_keys();
+        changed.sort();
+        assert_eq!(changed, vec!["cart.id", "cart.items", "cart.promo.code"]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn timeouts() -> Result<()> {
+    block_on(async {
+        const MINUTE: u64 = 60_000;
+        let now = Arc::new(AtomicU64::new(to_millis(SystemTime::now())));
+        let timeouts = |idle: u64, absolute: Option<u64>| {
+            let clock = now.clone();
+            let builder = Config::builder()
+                .storage(Arc::new(MemoryStorage::new()))
+                .generate(|| nanoid::nanoid!(32))
+                .idle_timeout(Duration::from_millis(idle * MINUTE))
+                .clock(move || from_millis(clock.load(Ordering::SeqCst)).unwrap());
+            let builder = match absolute {
+                Some(absolute) => {
+                    builder.absolute_timeout(Duration::from_millis(absolute * MINUTE))
+                }
+                None => builder,
+            };
+            Arc::new(builder.build().unwrap())
+        };
+
+        // The idle timeout fires first
+        let config = timeouts(30, Some(12 * 60));
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        let saved_at = now.load(Ordering::SeqCst);
+        assert_eq!(session.expires_at(), from_millis(saved_at + 30 * MINUTE));
+        now.fetch_add(30 * MINUTE - 1, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_some());
+        now.fetch_add(1, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_none());
+        assert!(config.metadata(&id).await?.is_none());
+        assert!(load(&id, &config).await?.is_empty());
+        session.touch().await?;
+        assert_eq!(session.status(), SessionStatus::Purged);
+
+        // Saves and touches restart the idle timeout, never the absolute one
+        let config = timeouts(30, Some(60));
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        let created_at = now.load(Ordering::SeqCst);
+        now.fetch_add(20 * MINUTE, Ordering::SeqCst);
+        let session = load(&id, &config).await?;
+        session.touch().await?;
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        now.fetch_add(20 * MINUTE, Ordering::SeqCst);
+        let session = load(&id, &config).await?;
+        session.set("cart", 1)?;
+        session.save().await?;
+        assert_eq!(session.expires_at(), from_millis(created_at + 60 * MINUTE));
+        now.fetch_add(20 * MINUTE - 1, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_some());
+        now.fetch_add(1, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_none());
+        assert_eq!(
+            config.metadata(&id).await?,
+            None,
+            "the absolute timeout fires though the session was used 20 minutes ago"
+        );
+
+        // The absolute timeout fires first
+        let config = timeouts(30, Some(10));
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        now.fetch_add(10 * MINUTE, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_none());
+
+        // Without an absolute timeout, active sessions live on
+        let config = timeouts(30, None);
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        for _ in 0..48 {
+            now.fetch_add(29 * MINUTE, Ordering::SeqCst);
+            session.touch().await?;
+        }
+        assert!(config.get(&id).await?.is_some());
+        assert_ne!(session.status(), SessionStatus::Purged);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn ttl() -> Result<()> {
+    block_on(async {
+        let now = Arc::new(AtomicU64::new(to_millis(SystemTime::now())));
+        let clock = now.clone();
+        let config = Arc::new(
+            builder(Arc::new(MemoryStorage::new()))
+                .clock(move || from_millis(clock.load(Ordering::SeqCst)).unwrap())
+                .build()?,
+        );
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set_with_ttl("otp", "123456", Duration::from_secs(300))?;
+        session.set_with_ttl("token", "abc", Duration::from_secs(300))?;
+        session.set("user", "fundon")?;
+        assert_eq!(session.get::<String>("otp")?, Some("123456".to_string()));
+        session.save().await?;
+
+        // The expiry survives a reload
+        let session = load(&id, &config).await?;
+        now.fetch_add(300_000 - 1, Ordering::SeqCst);
+        assert!(session.contains_key("otp"));
+        assert!(session.has_any(&["otp", "token"]));
+        assert_eq!(session.get::<String>("otp")?, Some("123456".to_string()));
+        assert_eq!(session.len(), 3);
+        assert!(!session.data_status());
+
+        // Expires exactly at the boundary
+        now.fetch_add(1, Ordering::SeqCst);
+        assert!(!session.contains_key("otp"));
+        assert!(!session.has_any(&["otp", "token"]));
+        assert!(session.has_any(&["otp", "user"]));
+        assert_eq!(session.len(), 1);
+        assert_eq!(
+            session.get_many(&["otp", "user"]),
+            vec![None, Some("fundon".into())]
+        );
+        assert_eq!(session.get::<String>("otp")?, None);
+        assert_eq!(session.take::<String>("token")?, None);
+        assert_eq!(session.changed_keys().len(), 2);
+        assert_eq!(session.get::<String>("user")?, Some("fundon".to_string()));
+
+        // Pruned from the store too
+        session.save().await?;
+        let data = config.get(&id).await?.unwrap();
+        assert!(!data.contains_key("otp"));
+        assert!(!data.contains_key("token"));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn rename_key() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+    session.set("uid", 1)?;
+    session.set("name", "fundon")?;
+
+    assert!(!session.rename_key("missing", "user_id", false)?);
+    assert!(!session.rename_key("uid", "uid", true)?);
+    assert!(session.rename_key("uid", "user_id", false)?);
+    assert_eq!(session.get::<u32>("uid")?, None);
+    assert_eq!(session.get::<u32>("user_id")?, Some(1));
+
+    // Refuses to clobber unless asked to
+    assert!(!session.rename_key("user_id", "name", false)?);
+    assert_eq!(session.get::<String>("name")?, Some("fundon".to_string()));
+    assert_eq!(session.get::<u32>("user_id")?, Some(1));
+    assert!(session.rename_key("user_id", "name", true)?);
+    assert_eq!(session.get::<u32>("name")?, Some(1));
+    assert_eq!(session.len(), 1);
+
+    let mut changed = session.changed_keys();
+    changed.sort();
+    assert_eq!(changed, vec!["name", "uid", "user_id"]);
+
+    Ok(())
+}
+
+#[test]
+fn migrate() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("uid", 1)?;
+        session.save().await?;
+
+        let runs = AtomicU64::new(0);
+        let migration = |data: &mut Data| {
+            runs.fetch_add(1, Ordering::SeqCst);
+            if let Some(uid) = data.remove("uid") {
+                data.insert("user_id".into(), uid);
+            }
+            Ok(())
+        };
+
+        let session = load(&id, &config).await?;
+        assert!(session.migrate(1, migration)?);
+        assert!(!session.migrate(1, migration)?);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(session.get::<u32>("user_id")?, Some(1));
+        let mut changed = session.changed_keys();
+        changed.sort();
+        assert_eq!(changed, vec![keys::SCHEMA, "uid", "user_id"]);
+        session.save().await?;
+
+        // Already migrated sessions are left alone
+        let session = load(&id, &config).await?;
+        assert!(!session.migrate(1, migration)?);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(!session.data_status());
+
+        // A failed migration leaves the data as is
+        assert!(session
+            .migrate(2, |data: &mut Data| {
+                data.clear();
+                Err(anyhow!("broken"))
+            })
+            .is_err());
+        assert_eq!(session.get::<u32>("user_id")?, Some(1));
+        assert!(!session.data_status());
+
+        Ok(())
+    })
+}
+
+fn versioned(storage: Arc<MemoryStorage>, version: u64, runs: Arc<AtomicU64>) -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(storage)
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .schema_version(version)
+            .migration(move |from, mut data: Data| {
+                runs.fetch_add(1, Ordering::SeqCst);
+                if from < 2 {
+                    if let Some(uid) = data.remove("uid") {
+                        data.insert("user_id".into(), uid);
+                    }
+                }
+                Ok(data)
+            })
+            .build()
+            .unwrap(),
+    )
+}
+
+#[test]
+fn schema_version() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let runs = Arc::new(AtomicU64::new(0));
+        let v1 = versioned(storage.clone(), 1, runs.clone());
+        let v2 = versioned(storage.clone(), 2, runs.clone());
+
+        // A request to the v1 app stores the old key name
+        let session = v1.load_session("").await?;
+        session.set("uid", 1)?;
+        session.save().await?;
+        let id = session.id()?;
+        let stored = storage.get(&id).await?.unwrap();
+        assert_eq!(stored[keys::SCHEMA_VERSION], 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        // The next request reaches the v2 app, the session is migrated on load
+        let session = v2.load_session(&id).await?;
+        assert!(!session.is_new());
+        assert_eq!(session.id()?, id);
+        assert_eq!(session.get::<u32>("user_id")?, Some(1));
+        assert!(!session.contains_key("uid"));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        let mut changed = session.changed_keys();
+        changed.sort();
+        assert_eq!(changed, vec![keys::SCHEMA_VERSION, "uid", "user_id"]);
+        session.save().await?;
+
+        let stored = storage.get(&id).await?.unwrap();
+        assert_eq!(stored[keys::SCHEMA_VERSION], 2);
+        assert_eq!(stored["user_id"], 1);
+        assert!(!stored.contains_key("uid"));
+
+        // Migrated sessions are left alone
+        let session = v2.load_session(&id).await?;
+        assert_eq!(session.get::<u32>("user_id")?, Some(1));
+        assert!(!session.data_status());
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // The schema versions are kept out of the values, and kept by `clear`
+        assert_eq!(session.keys(), vec!["user_id"]);
+        assert!(!session.export()?.data.contains_key(keys::SCHEMA_VERSION));
+        session.clear()?;
+        assert!(session.is_empty());
+        session.save().await?;
+        assert_eq!(storage.get(&id).await?.unwrap()[keys::SCHEMA_VERSION], 2);
+
+        // A lazy migration has its own schema version, never from the future
+        let session = v2.load_session(&id).await?;
+        assert!(session.migrate(5, |_: &mut Data| Ok(()))?);
+        session.set("user_id", 1)?;
+        session.save().await?;
+        let session = v2.load_session(&id).await?;
+        assert_eq!(session.id()?, id);
+        assert_eq!(session.get::<u32>("user_id")?, Some(1));
+        assert!(!session.migrate(5, |_: &mut Data| Ok(()))?);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // The v1 app can't read the future version, it starts a new session
+        let session = v1.load_session(&id).await?;
+        assert!(session.is_new());
+        assert_ne!(session.id()?, id);
+        assert!(v1.get(&id).await?.is_none());
+        assert!(storage.get(&id).await?.is_some());
+
+        // Data written before it was versioned is version 0
+        let unversioned = config(storage.clone());
+        let session = unversioned.load_session("").await?;
+        session.set("uid", 7)?;
+        session.save().await?;
+        let id = session.id()?;
+        assert!(!storage
+            .get(&id)
+            .await?
+            .unwrap()
+            .contains_key(keys::SCHEMA_VERSION));
+        let session = v2.load_session(&id).await?;
+        assert_eq!(session.get::<u32>("user_id")?, Some(7));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // A failed migration fails the load
+        let broken = Arc::new(
+            Config::builder()
+                .storage(storage.clone())
+                .generate(|| nanoid::nanoid!(32))
+                .verify(|sid: &str| sid.len() == 32)
+                .schema_version(3)
+                .migration(|_, _| Err(anyhow!("broken")))
+                .build()?,
+        );
+        assert!(broken.load_session(&id).await.is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn set_state() -> Result<()> {
+    #[derive(Serialize)]
+    struct Login {
+        user_id: u32,
+        role: &'static str,
+    }
+
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("cart", vec![1, 2])?;
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        assert!(!session.data_status());
+        session.set_state(Login {
+            user_id: 1,
+            role: "admin",
+        })?;
+        assert!(session.data_status());
+        let mut changed = session.changed_keys();
+        changed.sort();
+        assert_eq!(changed, vec!["cart", "role", "user_id"]);
+        assert_eq!(session.version()?, 1);
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        assert_eq!(session.version()?, 2);
+        assert_eq!(session.get::<Vec<u32>>("cart")?, None);
+        assert_eq!(session.get::<u32>("user_id")?, Some(1));
+        assert_eq!(session.get::<String>("role")?, Some("admin".to_string()));
+
+        let err = session.set_state(vec![1, 2]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DataError>(),
+            Some(DataError::NotAnObject)
+        ));
+        assert_eq!(session.len(), 2);
+        assert!(!session.data_status());
+
+        let state = session.take_state()?;
+        assert_eq!(state.len(), 2);
+        assert_eq!(state.get("user_id"), Some(&1.into()));
+        assert!(session.is_empty());
+        assert_eq!(session.version()?, 2);
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        assert!(session.is_empty());
+        assert_eq!(session.version()?, 3);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn get_many() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+    session.set("user_id", 1u64)?;
+    session.set("role", "admin".to_string())?;
+
+    assert_eq!(
+        session.get_many(&["user_id", "csrf", "role"]),
+        vec![Some(1.into()), None, Some("admin".into())]
+    );
+    assert!(session.get_many(&[]).is_empty());
+
+    let (user_id, role, csrf) =
+        session.get_all::<(u64, String, String)>(("user_id", "role", "csrf"))?;
+    assert_eq!(user_id, Some(1));
+    assert_eq!(role, Some("admin".to_string()));
+    assert_eq!(csrf, None);
+
+    // A wrong-typed value is `None` without failing the others
+    let (user_id, role) = session.get_all::<(String, String)>(("user_id", "role"))?;
+    assert_eq!(user_id, None);
+    assert_eq!(role, Some("admin".to_string()));
+
+    let (role,) = session.get_all::<(String,)>(("role",))?;
+    assert_eq!(role, Some("admin".to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn extend() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+    session.set_data(Data::from_iter(vec![("role".to_string(), "guest".into())]))?;
+
+    session.extend(serde_json::json!({ "role": "guest" }))?;
+    assert!(!session.data_status());
+
+    session.extend(serde_json::json!({ "id": 1, "role": "admin", "locale": "en" }))?;
+    assert!(session.data_status());
+    assert_eq!(session.len(), 3);
+    assert_eq!(session.get("id")?, Some(1u64));
+    assert_eq!(session.get("role")?, Some("admin".to_string()));
+    assert_eq!(session.get("locale")?, Some("en".to_string()));
+
+    assert!(session.extend(vec![1, 2]).is_err());
+    assert!(session.extend("admin").is_err());
+    assert_eq!(session.len(), 3);
+
+    let mut roles = BTreeMap::new();
+    roles.insert("role", "owner");
+    session.extend(roles)?;
+    assert_eq!(session.get("role")?, Some("owner".to_string()));
+
+    session.extend_pairs(vec![("theme".to_string(), "dark".into())])?;
+    assert_eq!(session.get("theme")?, Some("dark".to_string()));
+    assert_eq!(session.len(), 4);
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Profile {
+    name: String,
+    cart: Cart,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Cart {
+    items: Vec<u64>,
+}
+
+/// `Profile` with a field added later on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct ProfileV2 {
+    name: String,
+    cart: Cart,
+    locale: Option<String>,
+}
+
+#[test]
+fn typed() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = TypedSession::<Profile>::new(Session::new(
+            &id,
+            SessionStatus::Unchanged,
+            config.clone(),
+        ));
+        assert_eq!(session.get()?, Profile::default());
+        assert!(!session.is_defaulted());
+        session.with_mut(|profile| {
+            profile.name = "fundon".to_string();
+            profile.cart.items.push(1);
+        })?;
+        session.session().set("theme", "dark".to_string())?;
+        session.save().await?;
+
+        let session = TypedSession::<Profile>::new(load(&id, &config).await?);
+        assert_eq!(session.with(|profile| profile.cart.items.clone())?, vec![1]);
+        assert_eq!(session.get()?.name, "fundon");
+        assert_eq!(session.session().get("theme")?, Some("dark".to_string()));
+
+        let session = TypedSession::<ProfileV2>::new(load(&id, &config).await?);
+        assert_eq!(session.get()?.locale, None);
+        session.with_mut(|profile| profile.locale = Some("en".to_string()))?;
+        session.save().await?;
+
+        let session = TypedSession::<ProfileV2>::new(load(&id, &config).await?);
+        let profile = session.get()?;
+        assert_eq!(profile.name, "fundon");
+        assert_eq!(profile.cart.items, vec![1]);
+        assert_eq!(profile.locale, Some("en".to_string()));
+
+        // Data which can't be read as the type falls back to its default
+        let session = load(&id, &config).await?;
+        session.set("cart", "legacy")?;
+        let session = TypedSession::<Profile>::new(session);
+        assert_eq!(session.get()?, Profile::default());
+        assert!(session.is_defaulted());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn flash() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        // POST: flash and redirect
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("flash", "user data".to_string())?;
+        session.flash(Level::Success, "Profile updated")?;
+        session.flash(Level::Warning, "Email not verified")?;
+        session.save().await?;
+
+        // GET: the messages are shown
+        let session = load(&id, &config).await?;
+        assert_eq!(
+            session.take_flashes::<String>()?,
+            vec![
+                Flash {
+                    level: Level::Success,
+                    message: "Profile updated".to_string()
+                },
+                Flash {
+                    level: Level::Warning,
+                    message: "Email not verified".to_string()
+                },
+            ]
+        );
+        assert!(session.take_flashes::<String>()?.is_empty());
+        session.save().await?;
+
+        // GET again: the messages are gone, user data is untouched
+        let session = load(&id, &config).await?;
+        assert!(session.take_flashes::<String>()?.is_empty());
+        assert_eq!(session.get("flash")?, Some("user data".to_string()));
+
+        session.flash(Level::Error, vec![1, 2])?;
+        let flashes = session.take_flashes::<Vec<u64>>()?;
+        assert_eq!(flashes[0].level, Level::Error);
+        assert_eq!(flashes[0].message, vec![1, 2]);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn csrf() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = config(storage.clone());
+        let id = config.generate();
+
+        let mut session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        assert!(!session.verify_csrf(""));
+        let token = session.csrf_token()?;
+        assert_eq!(token.len(), 64);
+        assert_eq!(session.csrf_token()?, token);
+        session.save().await?;
+
+        let session2 = load(&id, &config).await?;
+        assert_eq!(session2.csrf_token()?, token);
+        assert!(!session2.data_status());
+        assert!(session2.verify_csrf(&token));
+        assert!(session2.verify_csrf(&token));
+
+        // Differences anywhere, and prefixes, are rejected
+        let mut first = token.clone().into_bytes();
+        first[0] = if first[0] == b'0' { b'1' } else { b'0' };
+        let mut last = token.clone().into_bytes();
+        last[63] = if last[63] == b'0' { b'1' } else { b'0' };
+        assert!(!session2.verify_csrf(&String::from_utf8(first)?));
+        assert!(!session2.verify_csrf(&String::from_utf8(last)?));
+        assert!(!session2.verify_csrf(&token[..63]));
+        assert!(!session2.verify_csrf(&format!("{}0", token)));
+
+        session.renew().await?;
+        assert_ne!(session.csrf_token()?, token);
+
+        let config = Arc::new(builder(storage).rotate_csrf(true).build()?);
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+        let token = session.csrf_token()?;
+        assert!(session.verify_csrf(&token));
+        assert!(!session.verify_csrf(&token));
+        assert_ne!(session.csrf_token()?, token);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn increment() -> Result<()> {
+    let config = config(Arc::new(MemoryStorage::new()));
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+
+    assert_eq!(session.increment("attempts", 1)?, 1);
+    assert_eq!(session.increment("attempts", 2)?, 3);
+    assert_eq!(session.decrement("attempts", 5)?, -2);
+    assert_eq!(session.decrement("balance", 1)?, -1);
+
+    session.set("name", "fundon".to_string())?;
+    assert!(session.increment("name", 1).is_err());
+    session.set("ratio", 0.5)?;
+    assert!(session.increment("ratio", 1).is_err());
+
+    session.set("max", i64::MAX)?;
+    assert!(session.increment("max", 1).is_err());
+    assert_eq!(session.get("max")?, Some(i64::MAX));
+    assert_eq!(session.saturating_increment("max", 1)?, i64::MAX);
+    assert_eq!(session.saturating_decrement("min", i64::MAX)?, -i64::MAX);
+    assert_eq!(session.saturating_decrement("min", 10)?, i64::MIN);
+
+    let handles = (0..8)
+        .map(|_| {
+            let session = session.clone();
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    session.increment("hits", 1).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(session.get("hits")?, Some(800));
+
+    Ok(())
+}
+
+#[test]
+fn expires() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = Arc::new(
+            builder(storage)
+                .cookie(CookieOptions::new().with_max_age(Duration::from_millis(50)))
+                .build()?,
+        );
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        assert_eq!(session.expires_at(), None);
+        assert_eq!(session.expires_in(), None);
+
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+        let expires_at = session.expires_at().unwrap();
+        assert_eq!(
+            expires_at,
+            session.last_accessed().unwrap() + Duration::from_millis(50)
+        );
+        assert!(session.expires_in().unwrap() <= Duration::from_millis(50));
+
+        thread::sleep(Duration::from_millis(10));
+        let session = load(&id, &config).await?;
+        assert_eq!(session.expires_at(), Some(expires_at));
+        session.set("crate", "sessions-core".to_string())?;
+        session.save().await?;
+        assert!(session.expires_at().unwrap() > expires_at);
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(session.expires_in(), Some(Duration::from_secs(0)));
+
+        session.destroy().await?;
+        assert_eq!(session.expires_at(), None);
+        assert_eq!(session.expires_in(), None);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn touch() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        session.touch().await?;
+        assert_eq!(config.get(&id).await?, None);
+        session.save().await?;
+
+        thread::sleep(Duration::from_millis(10));
+
+        let session = load(&id, &config).await?;
+        let expires_at = session.expires_at().unwrap();
+        session.touch().await?;
+        assert!(!session.data_status());
+        assert!(session.expires_at().unwrap() > expires_at);
+
+        let meta = config.metadata(&id).await?.unwrap();
+        assert_eq!(meta.version, 1);
+        assert_eq!(meta.last_accessed, session.last_accessed());
+        assert_eq!(
+            config.get(&id).await?.unwrap().get("crate"),
+            Some(&"sessions".into())
+        );
+
+        // Destroyed by another request
+        let other = load(&id, &config).await?;
+        other.destroy().await?;
+        session.touch().await?;
+        assert_eq!(session.status(), SessionStatus::Purged);
+        session.set("crate", "core".to_string())?;
+        session.save().await?;
+        assert_eq!(config.get(&id).await?, None);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn destroy() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon".to_string())?;
+        session.save().await?;
+
+        let session = load(&id, &config).await?;
+        session.destroy().await?;
+        assert!(session.is_destroyed());
+        assert_eq!(config.get(&id).await?, None);
+
+        // The dead session can't render or store user data anymore
+        assert_eq!(session.get::<String>("user")?, None);
+        assert!(session.is_empty());
+        assert!(matches!(
+            session
+                .set("user", "other".to_string())
+                .unwrap_err()
+                .downcast_ref::<DataError>(),
+            Some(DataError::Destroyed)
+        ));
+        assert_eq!(session.get::<String>("user")?, None);
+        assert!(session.csrf_token().is_err());
+        assert!(session.increment("count", 1).is_err());
+        assert!(!session.data_status());
+        session.save().await?;
+        assert_eq!(config.get(&id).await?, None);
+
+        let cookie = session.removal_cookie();
+        assert!(cookie.starts_with("viz.sid=;"));
+        assert!(cookie.contains("Path=/"));
+        assert!(cookie.contains("Max-Age=0"));
+        assert!(cookie.contains("Expires="));
+
+        Ok(())
+    })
+}
+
+#[test]
+#[allow(deprecated)]
+fn same_site() -> Result<()> {
+    assert_eq!("lax".parse::<SameSite>()?, SameSite::Lax);
+    assert_eq!("STRICT".parse::<SameSite>()?, SameSite::Strict);
+    assert!("strictish".parse::<SameSite>().is_err());
+    assert_eq!(SameSite::None.to_string(), "None");
+    assert_eq!(serde_json::to_string(&SameSite::Lax)?, r#""lax""#);
+    assert_eq!(
+        serde_json::from_str::<SameSite>(r#""strict""#)?,
+        SameSite::Strict
+    );
+
+    let removal_cookie = |cookie: CookieOptions| {
+        let mut config = builder(Arc::new(MemoryStorage::new())).build().unwrap();
+        config.cookie = cookie;
+        Session::new("a", SessionStatus::Unchanged, Arc::new(config)).removal_cookie()
+    };
+
+    let cookie = removal_cookie(CookieOptions::new().with_same_site(SameSite::Strict));
+    assert!(cookie.contains("SameSite=Strict"));
+    assert!(!cookie.contains("Secure"));
+
+    // `SameSite=None` requires `Secure`
+    let options = CookieOptions::new()
+        .with_secure(false)
+        .with_same_site_str("none")?;
+    assert_eq!(options.same_site, Some(SameSite::None));
+    let cookie = removal_cookie(options);
+    assert!(cookie.contains("SameSite=None"));
+    assert!(cookie.contains("Secure"));
+
+    assert!(CookieOptions::new()
+        .with_same_site_str("strictish")
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn config_options() -> Result<()> {
+    block_on(async {
+        let options: ConfigOptions = toml::from_str(
+            r#"
+            rotate_csrf = true
+            ttl = 3600
+
+            [cookie]
+            name = "__Host-sid"
+            max_age = 600
+            secure = true
+            same_site = "strict"
+            "#,
+        )?;
+        assert_eq!(
+            options.cookie,
+            CookieOptions::host_prefixed("sid")
+                .with_max_age(Duration::from_secs(600))
+                .with_same_site(SameSite::Strict)
+        );
+        assert_eq!(options.ttl, Some(Duration::from_secs(3600)));
+        assert_eq!(options.ping_timeout, None);
+
+        let config = Arc::new(
+            Config::builder()
+                .options(options.clone())
+                .storage(Arc::new(MemoryStorage::new()))
+                .generate(|| nanoid::nanoid!(32))
+                .verify(|sid: &str| sid.len() == 32)
+                .build()?,
+        );
+        assert!(config.rotate_csrf);
+        assert_eq!(config.store_ttl(), Duration::from_secs(3600));
+
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        assert!(config.get(&session.id()?).await?.is_some());
+        assert_eq!(
+            session.cookie_header()?,
+            Some(format!(
+                "__Host-sid={}; Max-Age=600; Path=/; Secure; HttpOnly; SameSite=Strict",
+                session.id()?
+            ))
+        );
+
+        // Round trips, the defaults fill the missing fields
+        let toml = toml::to_string(&options)?;
+        assert_eq!(toml::from_str::<ConfigOptions>(&toml)?, options);
+        assert_eq!(
+            toml::from_str::<ConfigOptions>("")?,
+            ConfigOptions::default()
+        );
+
+        // Typos and misconfigurations are caught
+        let err = toml::from_str::<ConfigOptions>("[cookie]\nhttp_olny = true").unwrap_err();
+        assert!(err.to_string().contains("http_olny"));
+        assert!(toml::from_str::<ConfigOptions>("auto_sav = true").is_err());
+        let options: ConfigOptions = toml::from_str("[cookie]\nname = \"__Host-sid\"")?;
+        let err = Config::builder()
+            .options(options)
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ConfigError::Cookie(CookieError::InsecurePrefix));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn validate_config() {
+    assert!(config(Arc::new(MemoryStorage::new())).validate().is_ok());
+
+    let mut config = builder(Arc::new(MemoryStorage::new())).build().unwrap();
+    config.cookie = CookieOptions::new().with_name("__Host-sid".into());
+    let err = config.validate().unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&CookieError::InsecurePrefix));
+
+    // The generated ids must be valid cookie values
+    let config = Config {
+        cookie: CookieOptions::new(),
+        generate: Box::new(|| "a,b".to_string()),
+        ..config
+    };
+    let err = config.validate().unwrap_err();
+    assert_eq!(err.downcast_ref(), Some(&CookieError::InvalidValue));
+}
+
+#[test]
+fn config_builder() -> Result<()> {
+    let builder = || {
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+    };
+
+    // Defaults
+    let config = builder().build()?;
+    assert_eq!(config.cookie, CookieOptions::new());
+    assert_eq!(config.store_ttl(), Duration::from_secs(3600 * 24));
+    assert_eq!(config.ping_timeout, None);
+    assert!(!config.rotate_csrf);
+    assert!(config.verify(&config.generate()));
+
+    let config = builder()
+        .max_age(Duration::from_secs(60))
+        .cookie(CookieOptions::strict())
+        .ttl(Duration::from_secs(3600))
+        .ping_timeout(Duration::from_secs(1))
+        .rotate_csrf(true)
+        .max_data_size(1024)
+        .build()?;
+    assert_eq!(
+        config.cookie,
+        CookieOptions::strict().with_max_age(Duration::from_secs(60))
+    );
+    assert_eq!(config.cookie_max_age(), Some(Duration::from_secs(60)));
+    assert_eq!(config.store_ttl(), Duration::from_secs(3600));
+    assert_eq!(config.ping_timeout, Some(Duration::from_secs(1)));
+    assert!(config.rotate_csrf);
+    assert_eq!(config.max_data_size, Some(1024));
+
+    // Missing parts
+    let err = Config::builder()
+        .generate(|| nanoid::nanoid!(32))
+        .verify(|sid: &str| sid.len() == 32)
+        .build()
+        .unwrap_err();
+    assert_eq!(err, ConfigError::MissingStorage);
+    assert_eq!(err.to_string(), "config has no storage");
+    #[cfg(not(feature = "nanoid"))]
+    {
+        let err = Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ConfigError::MissingGenerate);
+    }
+
+    // The verifier defaults to `verify_id`
+    let config = Config::builder()
+        .storage(Arc::new(MemoryStorage::new()))
+        .generate(|| nanoid::nanoid!(32))
+        .build()?;
+    assert!(config.verify(&config.generate()));
+    assert!(!config.verify("../etc/passwd"));
+
+    // Invalid parts
+    let err = builder()
+        .cookie(
+            CookieOptions::new()
+                .with_same_site(SameSite::None)
+                .with_secure(false),
+        )
+        .build()
+        .unwrap_err();
+    assert_eq!(err, ConfigError::Cookie(CookieError::InsecureSameSiteNone));
+    let err = builder()
+        .generate(|| "a b".to_string())
+        .build()
+        .unwrap_err();
+    assert_eq!(err, ConfigError::Cookie(CookieError::InvalidValue));
+    assert!(std::error::Error::source(&err).is_some());
+    let err = builder()
+        .generate(|| "12345".to_string())
+        .verify(verify_id)
+        .build()
+        .unwrap_err();
+    assert_eq!(err, ConfigError::UnverifiedId);
+
+    Ok(())
+}
+
+#[test]
+fn cookie_header() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        assert_eq!(session.cookie_header()?, None);
+
+        session.set("user", "fundon")?;
+        session.save().await?;
+        assert_eq!(
+            session.cookie_header()?,
+            Some(format!("viz.sid={}; Max-Age=86400; Path=/; HttpOnly", id))
+        );
+
+        let mut session = load(&id, &config).await?;
+        session.renew().await?;
+        let renewed = session.id()?;
+        assert_ne!(renewed, id);
+        assert_eq!(
+            session.cookie_header()?,
+            Some(format!(
+                "viz.sid={}; Max-Age=86400; Path=/; HttpOnly",
+                renewed
+            ))
+        );
+
+        // Logging out drops the cookie
+        session.destroy().await?;
+        assert_eq!(session.cookie_header()?, Some(session.removal_cookie()));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn save_twice() -> Result<()> {
+    block_on(async {
+        let config = config(Arc::new(MemoryStorage::new()));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("first", 1u64)?;
+        session.save().await?;
+        assert!(!session.data_status());
+        session.set("second", 2u64)?;
+        assert!(session.data_status());
+        session.save().await?;
+        assert_eq!(session.status(), SessionStatus::Saved);
+
+        let session = load(&id, &config).await?;
+        assert_eq!(session.get("first")?, Some(1u64));
+        assert_eq!(session.get("second")?, Some(2u64));
+        assert_eq!(session.version()?, 2);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn session_guard() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = config(storage.clone());
+
+        // Changed sessions are saved without a call to `save`
+        let id = config.generate();
+        let guard = SessionGuard::new(Session::new(&id, SessionStatus::Unchanged, config.clone()));
+        guard.set("crate", "sessions".to_string())?;
+        guard.finish().await?;
+
+        let session = load(&id, &config).await?;
+        assert_eq!(
+            session.get::<String>("crate")?,
+            Some("sessions".to_string())
+        );
+
+        // Fresh ones aren't
+        let id = config.generate();
+        let guard = SessionGuard::new(Session::new(&id, SessionStatus::Unchanged, config.clone()));
+        assert_eq!(guard.status(), SessionStatus::Unchanged);
+        guard.finish().await?;
+        assert_eq!(config.get(&id).await?, None);
+
+        Ok(())
+    })
+}
@@ -0,0 +1,109 @@
+#![cfg(all(feature = "ntex", feature = "memory"))]
+
+use std::sync::Arc;
+
+use ntex::{
+    http::{
+        header::{COOKIE, SET_COOKIE},
+        StatusCode,
+    },
+    web::{self, test, App, HttpRequest, HttpResponse},
+};
+
+use sessions::*;
+
+fn config() -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn set(session: Session) -> HttpResponse {
+    session.set("user", "fundon").unwrap();
+    HttpResponse::NoContent().finish()
+}
+
+async fn read(req: HttpRequest) -> HttpResponse {
+    let session = req.session().unwrap();
+    match session.get::<String>("user").unwrap() {
+        Some(user) => HttpResponse::Ok().body(user),
+        None => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+async fn destroy(session: Session) -> HttpResponse {
+    session.destroy().await.unwrap();
+    HttpResponse::NoContent().finish()
+}
+
+#[ntex::test]
+async fn set_read_destroy() {
+    let config = config();
+    let app = test::init_service(
+        App::new()
+            .wrap(NtexSessionMiddleware::new(config.clone()))
+            .route("/set", web::post().to(set))
+            .route("/read", web::get().to(read))
+            .route("/destroy", web::post().to(destroy)),
+    )
+    .await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/read").to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(res.headers().get(SET_COOKIE).is_none());
+
+    let res = test::call_service(&app, test::TestRequest::post().uri("/set").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let set_cookie = res
+        .headers()
+        .get(SET_COOKIE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri("/read")
+        .header(COOKIE, cookie.as_str())
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(SET_COOKIE).is_none());
+    assert_eq!(test::read_body(res).await, "fundon");
+
+    let req = test::TestRequest::post()
+        .uri("/destroy")
+        .header(COOKIE, cookie.as_str())
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        res.headers().get(SET_COOKIE).unwrap().to_str().unwrap(),
+        config.cookie().to_removal_cookie()
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/read")
+        .header(COOKIE, cookie.as_str())
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[ntex::test]
+async fn missing_middleware() {
+    let app = test::init_service(App::new().route("/set", web::post().to(set))).await;
+
+    let res = test::call_service(&app, test::TestRequest::post().uri("/set").to_request()).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = test::read_body(res).await;
+    assert!(std::str::from_utf8(&body)
+        .unwrap()
+        .contains("`SessionMiddleware`"));
+}
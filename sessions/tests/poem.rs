@@ -0,0 +1,149 @@
+#![cfg(all(feature = "poem", feature = "memory"))]
+
+use std::sync::Arc;
+
+use poem::{
+    get, handler,
+    http::{header::SET_COOKIE, StatusCode},
+    post,
+    test::TestClient,
+    web::cookie::Cookie,
+    EndpointExt, Route,
+};
+
+use sessions::*;
+
+fn config() -> Arc<Config> {
+    config_with(CookieOptions::new())
+}
+
+fn config_with(cookie: CookieOptions) -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .cookie(cookie)
+            .build()
+            .unwrap(),
+    )
+}
+
+/// The cookies of the `Set-Cookie` headers, sorted by name
+fn set_cookies(res: &poem::test::TestResponse) -> Vec<Cookie> {
+    let mut cookies = res
+        .0
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|val| Cookie::parse(val.to_str().unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    cookies.sort_by(|a, b| a.name().cmp(b.name()));
+    cookies
+}
+
+#[handler]
+async fn login(session: Session) -> StatusCode {
+    session.set("user", "fundon").unwrap();
+    session.renew_keeping_data().await.unwrap();
+    StatusCode::NO_CONTENT
+}
+
+#[handler]
+fn me(session: Session) -> Result<String, StatusCode> {
+    session
+        .get::<String>("user")
+        .unwrap()
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+#[handler]
+async fn logout(session: Session) -> StatusCode {
+    session.destroy().await.unwrap();
+    StatusCode::NO_CONTENT
+}
+
+fn routes() -> Route {
+    Route::new()
+        .at("/login", post(login))
+        .at("/me", get(me))
+        .at("/logout", post(logout))
+}
+
+#[tokio::test]
+async fn login_logout() {
+    let config = config();
+    let cli = TestClient::new(routes().with(PoemSessionMiddleware::new(config.clone())));
+
+    let res = cli.get("/me").send().await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+    res.assert_header_is_not_exist(SET_COOKIE);
+
+    let res = cli.post("/login").send().await;
+    res.assert_status(StatusCode::NO_CONTENT);
+    let cookie = Cookie::parse(res.0.headers()[SET_COOKIE].to_str().unwrap()).unwrap();
+    assert_eq!(cookie.name(), config.cookie().name);
+    assert_eq!(cookie.path(), Some("/"));
+    assert!(cookie.http_only());
+    let cookie = format!("{}={}", cookie.name(), cookie.value_str());
+
+    let res = cli.get("/me").header("cookie", &cookie).send().await;
+    res.assert_status_is_ok();
+    res.assert_header_is_not_exist(SET_COOKIE);
+    res.assert_text("fundon").await;
+
+    let res = cli.post("/logout").header("cookie", &cookie).send().await;
+    res.assert_status(StatusCode::NO_CONTENT);
+    let removal = Cookie::parse(res.0.headers()[SET_COOKIE].to_str().unwrap()).unwrap();
+    assert_eq!(removal.name(), config.cookie().name);
+    assert_eq!(removal.value_str(), "");
+    assert_eq!(removal.max_age(), Some(std::time::Duration::ZERO));
+
+    let res = cli.get("/me").header("cookie", &cookie).send().await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn chunked() {
+    let config = config_with(CookieOptions::new().with_chunk_size(16));
+    let cli = TestClient::new(routes().with(PoemSessionMiddleware::new(config.clone())));
+    let name = &config.cookie().name;
+
+    // A cookie set before the chunking is replaced by the chunks
+    let res = cli
+        .post("/login")
+        .header("cookie", format!("{}={}", name, nanoid::nanoid!(32)))
+        .send()
+        .await;
+    res.assert_status(StatusCode::NO_CONTENT);
+    let cookies = set_cookies(&res);
+    let names = cookies.iter().map(Cookie::name).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [name.clone(), format!("{}.0", name), format!("{}.1", name)]
+    );
+    assert_eq!(cookies[0].max_age(), Some(std::time::Duration::ZERO));
+    let cookie = cookies[1..]
+        .iter()
+        .map(|cookie| format!("{}={}", cookie.name(), cookie.value_str()))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let res = cli.get("/me").header("cookie", &cookie).send().await;
+    res.assert_status_is_ok();
+    res.assert_header_is_not_exist(SET_COOKIE);
+    res.assert_text("fundon").await;
+}
+
+#[tokio::test]
+async fn missing_middleware() {
+    let res = TestClient::new(routes()).get("/me").send().await;
+    res.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(res
+        .0
+        .into_body()
+        .into_string()
+        .await
+        .unwrap()
+        .contains("`SessionMiddleware`"));
+}
@@ -1,50 +1,63 @@
 #![cfg(feature = "redis")]
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
 
 use sessions::*;
 
+fn config(storage: Arc<dyn Storage>) -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(storage)
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
 #[tokio::test]
 async fn redis() -> Result<()> {
-    let storage = Arc::new(RedisStorage::new(RedisClient::open("redis://127.0.0.1")?));
+    let storage = Arc::new(Serialized::new(RedisStorage::new(RedisClient::open(
+        "redis://127.0.0.1",
+    )?)));
 
-    let config = Arc::new(Config {
-        cookie: CookieOptions::new(),
-        storage: storage.clone(),
-        generate: Box::new(|| nanoid::nanoid!(32)),
-        verify: Box::new(|sid: &str| sid.len() == 32),
-    });
+    let config = config(storage.clone());
+
+    assert!(config.ping().await.is_ok());
 
     let id = config.generate();
 
-    let session = Session::new(&id, 0, config.clone());
+    let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
 
-    assert_eq!(session.set::<String>("crate", "sessions".to_string()), None);
+    assert_eq!(
+        session.set::<String>("crate", "sessions".to_string())?,
+        None
+    );
 
     assert!(session.save().await.is_ok());
 
-    assert_eq!(session.get("crate"), Some("sessions".to_string()));
+    assert_eq!(session.get("crate")?, Some("sessions".to_string()));
 
     assert_eq!(
-        session.remove::<String>("crate"),
+        session.remove::<String>("crate")?,
         Some("sessions".to_string())
     );
 
-    assert_eq!(session.remove::<String>("crate"), None);
+    assert_eq!(session.remove::<String>("crate")?, None);
 
-    assert_eq!(session.get::<String>("crate"), None);
+    assert_eq!(session.get::<String>("crate")?, None);
 
     assert!(session.clear().is_ok());
 
-    let mut session = Session::new(&id, 0, config.clone());
+    let mut session = Session::new(&id, SessionStatus::Unchanged, config.clone());
 
     if let Some(data) = storage.get(&id).await? {
         session.set_data(data)?;
     }
 
-    assert_eq!(session.get("crate"), Some("sessions".to_string()));
+    assert_eq!(session.get("crate")?, Some("sessions".to_string()));
 
     assert!(session.renew().await.is_ok());
 
@@ -54,3 +67,132 @@ async fn redis() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn redis_users() -> Result<()> {
+    let storage = Arc::new(Serialized::new(RedisStorage::new(RedisClient::open(
+        "redis://127.0.0.1",
+    )?)));
+
+    let config = config(storage.clone());
+
+    let user = nanoid::nanoid!(8);
+
+    let laptop = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+    laptop.bind_user(&user)?;
+    laptop.save().await?;
+
+    let phone = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+    phone.bind_user(&user)?;
+    phone.save().await?;
+
+    assert_eq!(config.sessions_for(&user).await?.len(), 2);
+
+    assert_eq!(config.destroy_for(&user).await?, 2);
+
+    assert!(config.sessions_for(&user).await?.is_empty());
+    assert!(storage.get(&laptop.id()?).await?.is_none());
+    assert!(storage.get(&phone.id()?).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn redis_conflict() -> Result<()> {
+    let storage = Arc::new(Serialized::new(RedisStorage::new(RedisClient::open(
+        "redis://127.0.0.1",
+    )?)));
+
+    let config = config(storage.clone());
+
+    let id = config.generate();
+
+    Session::new(&id, SessionStatus::Unchanged, config.clone())
+        .save()
+        .await?;
+
+    let first = Session::new(&id, SessionStatus::Unchanged, config.clone());
+    let second = Session::new(&id, SessionStatus::Unchanged, config.clone());
+    if let Some(data) = storage.get(&id).await? {
+        first.set_data(data.clone())?;
+        second.set_data(data)?;
+    }
+
+    first.set("theme", "dark".to_string())?;
+    second.set("locale", "en".to_string())?;
+
+    assert!(first.save_with_conflict_check().await.is_ok());
+
+    let err = second.save_with_conflict_check().await.unwrap_err();
+    assert!(err.is::<Conflict>());
+
+    config.remove(&id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn redis_lock() -> Result<()> {
+    let storage = RedisStorage::new(RedisClient::open("redis://127.0.0.1")?);
+
+    let sid = nanoid::nanoid!(32);
+
+    let guard = storage.lock(&sid, Duration::from_secs(5)).await?;
+    assert!(
+        !storage
+            .try_lock(&sid, "other", Duration::from_secs(1))
+            .await?
+    );
+
+    assert!(guard.release().await?);
+    assert!(!guard.release().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn redis_metadata() -> Result<()> {
+    let storage = Arc::new(Serialized::new(RedisStorage::new(RedisClient::open(
+        "redis://127.0.0.1",
+    )?)));
+
+    let config = config(storage.clone());
+
+    let id = config.generate();
+
+    let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+    session.save().await?;
+
+    assert_eq!(config.metadata(&id).await?, Some(session.metadata()?));
+    assert!(session.created_at().is_some());
+
+    config.remove(&id).await?;
+
+    assert_eq!(config.metadata(&id).await?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn redis_millis_expiry() -> Result<()> {
+    let storage = RedisStorage::new(RedisClient::open("redis://127.0.0.1")?);
+    let record = record::encode(&Data::new(), b"{}")?;
+
+    // A TTL under a second isn't rounded down to a rejected zero
+    storage
+        .set("millis", record.clone(), Duration::from_millis(300))
+        .await?;
+    assert!(storage.get("millis").await?.is_some());
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert!(storage.get("millis").await?.is_none());
+
+    // Nor is a TTL of 1.9s rounded down to 1s
+    storage
+        .set("millis", record, Duration::from_millis(1900))
+        .await?;
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    assert!(storage.get("millis").await?.is_some());
+    storage.remove("millis").await?;
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+#![cfg(all(feature = "rocket", feature = "memory"))]
+
+use std::sync::Arc;
+
+use rocket::{
+    get,
+    http::{Cookie, Status},
+    local::asynchronous::Client,
+    post, routes, Build, Rocket, State,
+};
+
+use sessions::*;
+
+fn config() -> Arc<Config> {
+    config_with(CookieOptions::new())
+}
+
+fn config_with(cookie: CookieOptions) -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .cookie(cookie)
+            .build()
+            .unwrap(),
+    )
+}
+
+#[get("/count")]
+fn count(session: Session) -> String {
+    let count = session.get::<u64>("count").unwrap().unwrap_or_default() + 1;
+    session.set("count", count).unwrap();
+    count.to_string()
+}
+
+#[post("/logout")]
+async fn logout(session: Session) -> Status {
+    session.destroy().await.unwrap();
+    Status::NoContent
+}
+
+#[get("/name")]
+fn name(config: &State<Arc<Config>>) -> String {
+    config.cookie().name.clone()
+}
+
+fn app(config: Arc<Config>) -> Rocket<Build> {
+    rocket::build()
+        .attach(SessionFairing::new(config))
+        .mount("/", routes![count, logout, name])
+}
+
+#[rocket::async_test]
+async fn counter() {
+    let config = config();
+    let client = Client::untracked(app(config.clone())).await.unwrap();
+    let name = &config.cookie().name;
+
+    let res = client.get("/count").dispatch().await;
+    let cookie = res.cookies().get(name).unwrap().clone();
+    assert_eq!(cookie.path(), Some("/"));
+    assert_eq!(res.into_string().await.unwrap(), "1");
+    let cookie = Cookie::new(name.clone(), cookie.value().to_string());
+
+    let res = client.get("/count").cookie(cookie.clone()).dispatch().await;
+    assert_eq!(res.into_string().await.unwrap(), "2");
+
+    let res = client
+        .post("/logout")
+        .cookie(cookie.clone())
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::NoContent);
+    let removal = res.cookies().get(name).unwrap();
+    assert_eq!(removal.value(), "");
+    assert_eq!(removal.max_age(), Some(rocket::time::Duration::ZERO));
+
+    let res = client.get("/count").cookie(cookie).dispatch().await;
+    assert_eq!(res.into_string().await.unwrap(), "1");
+
+    // The config is managed
+    let res = client.get("/name").dispatch().await;
+    assert_eq!(res.into_string().await.unwrap(), *name);
+}
+
+#[rocket::async_test]
+async fn chunked() {
+    let config = config_with(CookieOptions::new().with_chunk_size(16));
+    let client = Client::untracked(app(config.clone())).await.unwrap();
+    let name = &config.cookie().name;
+
+    let res = client.get("/count").dispatch().await;
+    let mut names = res
+        .cookies()
+        .iter()
+        .map(|cookie| cookie.name().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, [format!("{}.0", name), format!("{}.1", name)]);
+    let cookies = res
+        .cookies()
+        .iter()
+        .map(|cookie| Cookie::new(cookie.name().to_string(), cookie.value().to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(res.into_string().await.unwrap(), "1");
+
+    let res = client.get("/count").cookies(cookies).dispatch().await;
+    assert_eq!(res.into_string().await.unwrap(), "2");
+}
+
+#[rocket::async_test]
+async fn missing_fairing() {
+    let client = Client::untracked(rocket::build().mount("/", routes![count]))
+        .await
+        .unwrap();
+    let res = client.get("/count").dispatch().await;
+    assert_eq!(res.status(), Status::InternalServerError);
+}
@@ -0,0 +1,149 @@
+#![cfg(all(feature = "salvo", feature = "memory"))]
+
+use std::sync::Arc;
+
+use salvo_core::{
+    handler,
+    http::{
+        cookie::{self, Cookie},
+        header::{COOKIE, SET_COOKIE},
+        StatusCode,
+    },
+    test::{ResponseExt, TestClient},
+    Depot, Response, Router, Service,
+};
+
+use sessions::*;
+
+fn config() -> Arc<Config> {
+    config_with(CookieOptions::new())
+}
+
+fn config_with(cookie: CookieOptions) -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .cookie(cookie)
+            .build()
+            .unwrap(),
+    )
+}
+
+#[handler]
+async fn set(depot: &mut Depot, res: &mut Response) {
+    depot.session().unwrap().set("user", "fundon").unwrap();
+    res.status_code(StatusCode::NO_CONTENT);
+}
+
+#[handler]
+async fn get(depot: &mut Depot, res: &mut Response) {
+    match depot.session().unwrap().get::<String>("user").unwrap() {
+        Some(user) => res.render(user),
+        None => {
+            res.status_code(StatusCode::UNAUTHORIZED);
+        }
+    }
+}
+
+#[handler]
+async fn destroy(depot: &mut Depot, res: &mut Response) {
+    depot.session().unwrap().destroy().await.unwrap();
+    res.status_code(StatusCode::NO_CONTENT);
+}
+
+fn set_cookie(res: &salvo_core::Response) -> Option<Cookie<'static>> {
+    res.headers()
+        .get(SET_COOKIE)
+        .map(|val| Cookie::parse(val.to_str().unwrap().to_string()).unwrap())
+}
+
+/// The cookies the response sets, sorted by name
+///
+/// They're read from its jar, as the test client keeps one `Set-Cookie` header.
+fn set_cookies(res: &salvo_core::Response) -> Vec<Cookie<'static>> {
+    let mut cookies = res.cookies().delta().cloned().collect::<Vec<_>>();
+    cookies.sort_by(|a, b| a.name().cmp(b.name()));
+    cookies
+}
+
+#[tokio::test]
+async fn set_get_destroy() {
+    let config = config();
+    let service = Service::new(
+        Router::new()
+            .hoop(SalvoSessionHandler::new(config.clone()))
+            .push(Router::with_path("set").post(set))
+            .push(Router::with_path("get").get(get))
+            .push(Router::with_path("destroy").post(destroy)),
+    );
+
+    let res = TestClient::get("http://localhost/get").send(&service).await;
+    assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+    assert!(set_cookie(&res).is_none());
+
+    let res = TestClient::post("http://localhost/set")
+        .send(&service)
+        .await;
+    assert_eq!(res.status_code, Some(StatusCode::NO_CONTENT));
+    let cookie = set_cookie(&res).unwrap();
+    assert_eq!(cookie.name(), config.cookie().name);
+    assert_eq!(cookie.path(), Some("/"));
+    let cookie = format!("{}={}", cookie.name(), cookie.value());
+
+    let mut res = TestClient::get("http://localhost/get")
+        .add_header(COOKIE, &cookie, true)
+        .send(&service)
+        .await;
+    assert!(set_cookie(&res).is_none());
+    assert_eq!(res.take_string().await.unwrap(), "fundon");
+
+    let res = TestClient::post("http://localhost/destroy")
+        .add_header(COOKIE, &cookie, true)
+        .send(&service)
+        .await;
+    assert_eq!(res.status_code, Some(StatusCode::NO_CONTENT));
+    let removal = set_cookie(&res).unwrap();
+    assert_eq!(removal.name(), config.cookie().name);
+    assert_eq!(removal.value(), "");
+    assert_eq!(removal.max_age(), Some(cookie::time::Duration::ZERO));
+
+    let res = TestClient::get("http://localhost/get")
+        .add_header(COOKIE, &cookie, true)
+        .send(&service)
+        .await;
+    assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+}
+
+#[tokio::test]
+async fn chunked() {
+    let config = config_with(CookieOptions::new().with_chunk_size(16));
+    let service = Service::new(
+        Router::new()
+            .hoop(SalvoSessionHandler::new(config.clone()))
+            .push(Router::with_path("set").post(set))
+            .push(Router::with_path("get").get(get)),
+    );
+    let name = &config.cookie().name;
+
+    let res = TestClient::post("http://localhost/set")
+        .send(&service)
+        .await;
+    assert_eq!(res.status_code, Some(StatusCode::NO_CONTENT));
+    let cookies = set_cookies(&res);
+    let names = cookies.iter().map(Cookie::name).collect::<Vec<_>>();
+    assert_eq!(names, [format!("{}.0", name), format!("{}.1", name)]);
+    let cookie = cookies
+        .iter()
+        .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let mut res = TestClient::get("http://localhost/get")
+        .add_header(COOKIE, &cookie, true)
+        .send(&service)
+        .await;
+    assert!(set_cookies(&res).is_empty());
+    assert_eq!(res.take_string().await.unwrap(), "fundon");
+}
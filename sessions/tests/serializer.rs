@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use proptest::prelude::*;
+use serde_json::Value;
+
+use sessions::*;
+
+fn serializers() -> Vec<Arc<dyn Serializer>> {
+    vec![
+        Arc::new(Json),
+        Arc::new(EncryptedSerializer::new(Json, [7; 32])),
+        #[cfg(feature = "msgpack")]
+        Arc::new(MessagePack),
+        #[cfg(feature = "bincode")]
+        Arc::new(Bincode),
+        #[cfg(feature = "cbor")]
+        Arc::new(Cbor),
+        #[cfg(feature = "postcard")]
+        Arc::new(Postcard),
+    ]
+}
+
+/// Any finite float, with the edge cases drawn more often
+fn float() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        any::<f64>().prop_filter("JSON has no NaN or infinity", |f| f.is_finite()),
+        prop::sample::select(vec![
+            0.0,
+            -0.0,
+            f64::MIN,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            f64::EPSILON,
+            1e-310,
+            9007199254740993.0,
+        ]),
+    ]
+}
+
+/// Any integer, with the edges of `u64` and `i64` drawn more often
+fn integer() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<u64>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        prop::sample::select(vec![
+            Value::from(u64::MAX),
+            Value::from(i64::MAX as u64 + 1),
+            Value::from(i64::MAX),
+            Value::from(i64::MIN),
+            Value::from(-1),
+        ]),
+    ]
+}
+
+/// Keys and strings, unicode ones included
+fn string() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-z_]{0,12}",
+        "\\PC{0,12}",
+        Just("🦀 Ünïcödé ключ 鍵".to_string()),
+    ]
+}
+
+fn value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        integer(),
+        float().prop_map(Value::from),
+        string().prop_map(Value::from),
+        prop::collection::vec(any::<u8>(), 0..32).prop_map(|bytes| binary::to_value(&bytes)),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::from),
+            prop::collection::btree_map(string(), inner, 0..8)
+                .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+fn data() -> impl Strategy<Value = Data> {
+    prop::collection::btree_map(string(), value(), 0..8).prop_map(|map| map.into_iter().collect())
+}
+
+proptest! {
+    #[test]
+    fn round_trip(data in data()) {
+        for serializer in serializers() {
+            let buf = serializer.serialize(&data).unwrap();
+            prop_assert_eq!(&serializer.deserialize(&buf).unwrap(), &data, "{:?}", serializer);
+        }
+    }
+
+    #[test]
+    fn floats_are_exact(f in float()) {
+        let mut data = Data::new();
+        data.insert("f".into(), f.into());
+        for serializer in serializers() {
+            let read = serializer.deserialize(&serializer.serialize(&data).unwrap()).unwrap();
+            prop_assert_eq!(read["f"].as_f64().map(f64::to_bits), Some(f.to_bits()), "{:?}", serializer);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+#![cfg(feature = "sled")]
+
+use std::{
+    env, fs,
+    path::Path,
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use futures_executor::block_on;
+
+use sessions::*;
+
+fn config(storage: Arc<dyn Storage>, cleanup: Option<CleanupHandle>) -> Arc<Config> {
+    let mut config = Config::builder()
+        .storage(storage)
+        .generate(|| nanoid::nanoid!(32))
+        .verify(|sid: &str| sid.len() == 32)
+        .build()
+        .unwrap();
+    config.cleanup = cleanup;
+    Arc::new(config)
+}
+
+/// Opens the database again, once the background threads of the previous one released it
+fn reopen(path: &Path) -> Result<SledStorage> {
+    let mut tries = 0;
+    loop {
+        match SledStorage::open(path) {
+            Err(_) if tries < 100 => {
+                tries += 1;
+                thread::sleep(Duration::from_millis(10));
+            }
+            res => return res,
+        }
+    }
+}
+
+#[test]
+fn sled_close() -> Result<()> {
+    let path = env::temp_dir().join(format!("sessions-sled-{}", process::id()));
+    let _ = fs::remove_dir_all(&path);
+
+    let id = block_on(async {
+        let storage = Arc::new(Serialized::new(SledStorage::open(&path)?));
+        let cleanup = spawn_cleanup(storage.clone(), Duration::from_millis(5));
+        let config = config(storage, Some(cleanup));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+
+        config
+            .set("expired", Data::new(), Duration::from_secs(0))
+            .await?;
+        thread::sleep(Duration::from_millis(2));
+
+        config.shutdown().await?;
+        // Shutting down twice is harmless
+        config.shutdown().await?;
+
+        Ok::<_, anyhow::Error>(id)
+    })?;
+
+    block_on(async {
+        let storage = reopen(&path)?;
+        let config = config(Arc::new(Serialized::new(storage.clone())), None);
+
+        let data = config.get(&id).await?.unwrap();
+        assert_eq!(data.get("crate"), Some(&"sessions".into()));
+
+        assert!(config.touch(&id, 42, Duration::from_secs(60)).await?);
+        assert_eq!(
+            config.metadata(&id).await?.unwrap().last_accessed,
+            from_millis(42)
+        );
+
+        // The final cleanup on close removed the expired session
+        assert!(storage.get("expired").await?.is_none());
+        assert_eq!(storage.cleanup().await?, 0);
+
+        config.shutdown().await
+    })?;
+
+    fs::remove_dir_all(&path)?;
+
+    Ok(())
+}
+
+#[test]
+fn sled_rolling_idle_timeout() -> Result<()> {
+    const MINUTE: u64 = 60 * 1000;
+
+    let path = env::temp_dir().join(format!("sessions-sled-rolling-{}", process::id()));
+    let _ = fs::remove_dir_all(&path);
+
+    block_on(async {
+        let now = Arc::new(AtomicU64::new(to_millis(SystemTime::now())));
+        let clock = now.clone();
+        let config = Arc::new(
+            Config::builder()
+                .storage(Arc::new(Serialized::new(SledStorage::open(&path)?)))
+                .generate(|| nanoid::nanoid!(32))
+                .max_age(Duration::from_secs(3600))
+                .rolling(true)
+                .rolling_interval(Duration::from_secs(60))
+                .idle_timeout(Duration::from_millis(30 * MINUTE))
+                .clock(move || from_millis(clock.load(Ordering::SeqCst)).unwrap())
+                .build()?,
+        );
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+
+        // A request loading the session, `None` once it idled out
+        let request = |mins: u64| {
+            now.fetch_add(mins * MINUTE, Ordering::SeqCst);
+            let config = config.clone();
+            let id = id.clone();
+            async move {
+                let data = match config.get(&id).await? {
+                    Some(data) => data,
+                    None => return Ok(None),
+                };
+                let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+                session.set_data(data)?;
+                SessionGuard::new(session.clone()).finish().await?;
+                Result::<_>::Ok(Some(session))
+            }
+        };
+
+        // Every rolled request restarts the idle timeout
+        for _ in 0..3 {
+            let session = request(20).await?.unwrap();
+            assert_eq!(session.status(), SessionStatus::Saved);
+        }
+        assert_eq!(
+            config.get(&id).await?.unwrap().get("user"),
+            Some(&"fundon".into())
+        );
+
+        assert!(request(30).await?.is_none());
+
+        config.shutdown().await
+    })?;
+
+    fs::remove_dir_all(&path)?;
+
+    Ok(())
+}
+
+#[test]
+fn sled_rename() -> Result<()> {
+    let path = env::temp_dir().join(format!("sessions-sled-rename-{}", process::id()));
+    let _ = fs::remove_dir_all(&path);
+
+    block_on(async {
+        let storage = SledStorage::open(&path)?;
+        let exp = Duration::from_secs(60);
+        storage.set("old", b"old".to_vec(), exp).await?;
+        storage.set("taken", b"taken".to_vec(), exp).await?;
+
+        // Never onto an unexpired session
+        assert!(storage.rename("old", "taken").await.is_err());
+        assert_eq!(storage.get("old").await?, Some(b"old".to_vec()));
+        assert_eq!(storage.get("taken").await?, Some(b"taken".to_vec()));
+
+        // Onto an expired one
+        storage
+            .set("expired", b"expired".to_vec(), Duration::from_secs(0))
+            .await?;
+        thread::sleep(Duration::from_millis(2));
+        storage.rename("old", "expired").await?;
+        assert!(storage.get("old").await?.is_none());
+        assert_eq!(storage.get("expired").await?, Some(b"old".to_vec()));
+
+        // An expired session isn't moved
+        storage
+            .set("stale", b"stale".to_vec(), Duration::from_secs(0))
+            .await?;
+        thread::sleep(Duration::from_millis(2));
+        storage.rename("stale", "new").await?;
+        assert!(storage.get("new").await?.is_none());
+        assert_eq!(storage.cleanup().await?, 0);
+
+        storage.close().await
+    })?;
+
+    fs::remove_dir_all(&path)?;
+
+    Ok(())
+}
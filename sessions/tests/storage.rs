@@ -0,0 +1,1707 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use futures_executor::block_on;
+use futures_timer::Delay;
+
+use sessions::*;
+
+#[derive(Debug)]
+struct SlowStorage(Duration);
+
+#[async_trait]
+impl Storage for SlowStorage {
+    async fn get(&self, _key: &str) -> Result<Option<Data>> {
+        Ok(None)
+    }
+
+    async fn set(&self, _key: &str, _val: Data, _exp: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    async fn remove(&self, _key: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Delay::new(self.0).await;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct CountingStorage {
+    inner: Mutex<HashMap<String, Data>>,
+    sets: AtomicU64,
+    /// The number of gets and removes
+    reads: AtomicU64,
+    cleanups: AtomicU64,
+    /// The expiry of the last set
+    exp: Mutex<Option<Duration>>,
+}
+
+#[async_trait]
+impl Storage for CountingStorage {
+    async fn get(&self, key: &str) -> Result<Option<Data>> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        Ok(self.inner.lock().unwrap().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
+        self.sets.fetch_add(1, Ordering::SeqCst);
+        self.exp.lock().unwrap().replace(exp);
+        self.inner.lock().unwrap().insert(key.to_string(), val);
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn cleanup(&self) -> Result<u64> {
+        Ok(self.cleanups.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+}
+
+/// A byte-oriented storage without expiry, clones share the entries
+#[derive(Debug, Default, Clone)]
+struct BytesStorage {
+    inner: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+#[async_trait]
+impl Storage<Vec<u8>> for BytesStorage {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.inner.lock().unwrap().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, val: Vec<u8>, _exp: Duration) -> Result<()> {
+        self.inner.lock().unwrap().insert(key.to_string(), val);
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.inner.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn sessions_for(&self, user_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .inner
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, val)| val.user() == Some(user_id))
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
+}
+
+fn config(storage: Arc<dyn Storage>, ping_timeout: Option<Duration>) -> Config {
+    let mut config = Config::builder()
+        .storage(storage)
+        .generate(|| nanoid::nanoid!(32))
+        .verify(|sid: &str| sid.len() == 32)
+        .build()
+        .unwrap();
+    config.ping_timeout = ping_timeout;
+    config
+}
+
+#[test]
+fn ping() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(SlowStorage(Duration::from_millis(10)));
+
+        assert!(config(storage.clone(), None).ping().await.is_ok());
+
+        assert!(config(storage, Some(Duration::from_secs(1)))
+            .ping()
+            .await
+            .is_ok());
+
+        let storage = Arc::new(SlowStorage(Duration::from_secs(5)));
+
+        assert!(config(storage, Some(Duration::from_millis(10)))
+            .ping()
+            .await
+            .is_err());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn cleanup() {
+    let storage = Arc::new(CountingStorage::default());
+
+    let handle = spawn_cleanup(storage.clone(), Duration::from_millis(5));
+
+    thread::sleep(Duration::from_millis(50));
+
+    handle.stop();
+
+    let cleanups = storage.cleanups.load(Ordering::SeqCst);
+
+    assert!(cleanups > 0);
+
+    thread::sleep(Duration::from_millis(20));
+
+    assert_eq!(storage.cleanups.load(Ordering::SeqCst), cleanups);
+}
+
+#[test]
+fn bytes() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(Serialized::new(BytesStorage::default()));
+        let config = Arc::new(config(storage.clone(), None));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        session.bind_user("fundon")?;
+        session.save().await?;
+
+        let buf = storage.inner().get(&id).await?.unwrap();
+        let record = record::decode(&buf).unwrap();
+        assert_eq!(buf[0], record::FORMAT);
+        assert_eq!(record.user, Some("fundon"));
+        assert_eq!(record.metadata, session.metadata()?);
+        assert_eq!(
+            serde_json::from_slice::<Data>(record.body)?,
+            session.data()?
+        );
+
+        assert_eq!(config.get(&id).await?, Some(session.data()?));
+        assert_eq!(config.metadata(&id).await?, Some(session.metadata()?));
+        assert_eq!(config.sessions_for("fundon").await?, vec![id.clone()]);
+
+        session.set("crate", "core".to_string())?;
+        assert!(config
+            .set_if_version(&id, session.data()?, Duration::from_secs(60), 0)
+            .await
+            .unwrap_err()
+            .is::<Conflict>());
+
+        // Storages without a native touch rewrite the record
+        assert!(config.touch(&id, 42, Duration::from_secs(60)).await?);
+        assert_eq!(
+            config.metadata(&id).await?.unwrap().last_accessed,
+            from_millis(42)
+        );
+        let missing = format!("{:_<32}", "missing");
+        assert!(!config.touch(&missing, 42, Duration::from_secs(60)).await?);
+
+        // Plain JSON written before the record format is still readable
+        let legacy = format!("{:_<32}", "legacy");
+        storage
+            .inner()
+            .set(
+                &legacy,
+                br#"{"crate":"sessions"}"#.to_vec(),
+                Duration::from_secs(60),
+            )
+            .await?;
+        let data = config.get(&legacy).await?.unwrap();
+        assert_eq!(data.get("crate"), Some(&"sessions".into()));
+        assert_eq!(config.metadata(&legacy).await?, Some(Metadata::default()));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn bytes_long_user() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(Serialized::new(BytesStorage::default()));
+        let config = Arc::new(config(storage.clone(), None));
+        let id = config.generate();
+
+        // Cut at `u16::MAX` bytes, the last `é` would be split
+        let user = format!("ab{}", "é".repeat(u16::MAX as usize / 2));
+        assert!(user.len() > u16::MAX as usize);
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        session.bind_user(&user)?;
+        assert!(session.save().await.is_err());
+        assert!(storage.inner().get(&id).await?.is_none());
+
+        // Up to `u16::MAX` bytes, the user id is kept whole
+        let user = "é".repeat(u16::MAX as usize / 2);
+        session.bind_user(&user)?;
+        session.save().await?;
+        let buf = storage.inner().get(&id).await?.unwrap();
+        assert_eq!(record::decode(&buf).unwrap().user, Some(user.as_str()));
+        assert!(config.get(&id).await?.is_some());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn bytes_touch_idle_timeout() -> Result<()> {
+    const MINUTE: u64 = 60 * 1000;
+
+    block_on(async {
+        let now = Arc::new(AtomicU64::new(to_millis(SystemTime::now())));
+        let clock = now.clone();
+        let config = Arc::new(
+            Config::builder()
+                .byte_storage(BytesStorage::default())
+                .generate(|| nanoid::nanoid!(32))
+                .idle_timeout(Duration::from_millis(30 * MINUTE))
+                .clock(move || from_millis(clock.load(Ordering::SeqCst)).unwrap())
+                .build()?,
+        );
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+
+        // The touch only rewrites the header, which wins over the body
+        now.fetch_add(20 * MINUTE, Ordering::SeqCst);
+        session.touch().await?;
+        let touched_at = now.load(Ordering::SeqCst);
+        let data = config.get(&id).await?.unwrap();
+        assert_eq!(
+            data.get("_last_accessed").and_then(|val| val.as_u64()),
+            Some(touched_at)
+        );
+        assert_eq!(Metadata::from_data(&data), session.metadata()?);
+
+        // The idle timeout restarts from the touch
+        now.fetch_add(20 * MINUTE, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_some());
+        now.fetch_add(10 * MINUTE, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_none());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn blocking() -> Result<()> {
+    let store = BlockingStore::new(Serialized::new(BytesStorage::default()));
+    let mut data = Data::new();
+    data.insert("crate".into(), "sessions".into());
+
+    store.set("blocking", data.clone(), Duration::from_secs(60))?;
+    assert_eq!(store.get("blocking")?, Some(data));
+    store.remove::<Data>("blocking")?;
+    assert_eq!(store.get::<Data>("blocking")?, None);
+    assert_eq!(store.cleanup::<Data>()?, 0);
+
+    let storage = Arc::new(Serialized::new(BytesStorage::default()));
+    let config = Arc::new(config(storage.clone(), None));
+    let id = config.generate();
+
+    let session = BlockingSession::new(Session::new(&id, SessionStatus::Unchanged, config));
+    session.set("crate", "sessions".to_string())?;
+    assert_eq!(
+        session.get::<String>("crate")?,
+        Some("sessions".to_string())
+    );
+    session.save()?;
+    assert!(storage.inner().inner.lock().unwrap().contains_key(&id));
+
+    session.destroy()?;
+    assert!(!storage.inner().inner.lock().unwrap().contains_key(&id));
+
+    // Refuses to block a thread that is already driving an executor
+    block_on(async {
+        assert!(store.get::<Data>("blocking").is_err());
+    });
+    #[cfg(feature = "tokio")]
+    tokio::runtime::Runtime::new()?.block_on(async {
+        assert!(store.get::<Data>("blocking").is_err());
+    });
+
+    Ok(())
+}
+
+#[test]
+fn renew_keeping_data() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(Serialized::new(BytesStorage::default()));
+        let config = Arc::new(config(storage, None));
+        let id = config.generate();
+
+        assert!(config
+            .rename(&id, "new")
+            .await
+            .unwrap_err()
+            .is::<Unsupported>());
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        session.save().await?;
+        let state = config.get(&id).await?;
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set_data(state.clone().unwrap())?;
+        session.renew_keeping_data().await?;
+
+        let new = session.id()?;
+        assert_ne!(new, id);
+        assert_eq!(session.status(), SessionStatus::Renewed);
+        assert_eq!(config.get(&new).await?, state);
+        assert_eq!(config.get(&id).await?, None);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn save_without_changes() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(CountingStorage::default());
+        let config = Arc::new(config(storage.clone(), None));
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("crate", "sessions".to_string())?;
+        assert_eq!(session.changed_keys(), vec!["crate".to_string()]);
+        session.save().await?;
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 1);
+        assert!(session.changed_keys().is_empty());
+
+        // A read-only request
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set_data(config.get(&id).await?.unwrap())?;
+        assert_eq!(
+            session.get::<String>("crate")?,
+            Some("sessions".to_string())
+        );
+        session.save().await?;
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 1);
+
+        // An identical write
+        session.set("crate", "sessions".to_string())?;
+        assert!(!session.data_status());
+        session.save().await?;
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 1);
+
+        session.set("crate", "core".to_string())?;
+        session.set("version", 2)?;
+        let mut keys = session.changed_keys();
+        keys.sort();
+        assert_eq!(keys, vec!["crate".to_string(), "version".to_string()]);
+        session.save().await?;
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn max_data_size() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(CountingStorage::default());
+        let config = Arc::new(Config {
+            max_data_size: Some(128),
+            ..config(storage.clone(), None)
+        });
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        assert_eq!(session.approx_size(), r#"{"user":"fundon"}"#.len());
+
+        // The whole state counts, not only the new value
+        let err = session.set("token", "x".repeat(112)).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DataTooLarge>(),
+            Some(&DataTooLarge {
+                size: r#"{"token":"","user":"fundon"}"#.len() + 112,
+                limit: 128,
+            })
+        );
+        assert!(!session.contains_key("token"));
+        assert_eq!(session.changed_keys(), vec!["user".to_string()]);
+
+        // Writers without the check fail on save, the stamps count too
+        session.extend_pairs(vec![("token".to_string(), "x".repeat(64).into())])?;
+        assert!(session.save().await.unwrap_err().is::<DataTooLarge>());
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 0);
+        assert!(session.data_status());
+
+        session.remove_value("token")?;
+        session.save().await?;
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn max_data_size_boundary() -> Result<()> {
+    block_on(async {
+        let now = to_millis(SystemTime::now());
+        let mut stamped = Data::new();
+        stamped.insert("token".into(), "x".repeat(1024).into());
+        stamped.insert("_version".into(), 1.into());
+        stamped.insert("_created_at".into(), now.into());
+        stamped.insert("_last_accessed".into(), now.into());
+
+        let save = |limit: usize, serializer: Option<Arc<dyn Serializer>>| {
+            let storage = Arc::new(CountingStorage::default());
+            let config = Arc::new(Config {
+                max_data_size: Some(limit),
+                clock: Some(Box::new(move || from_millis(now).unwrap())),
+                serializer,
+                ..config(storage.clone(), None)
+            });
+            async move {
+                let session = Session::new(&config.generate(), SessionStatus::Unchanged, config);
+                session.set("token", "x".repeat(1024))?;
+                let res = session.save().await;
+                Result::<_>::Ok((res, storage.sets.load(Ordering::SeqCst)))
+            }
+        };
+
+        let size = serde_json::to_vec(&stamped)?.len();
+        let (res, sets) = save(size, None).await?;
+        assert!(res.is_ok());
+        assert_eq!(sets, 1);
+        let (res, sets) = save(size - 1, None).await?;
+        assert_eq!(
+            res.unwrap_err().downcast_ref::<DataTooLarge>(),
+            Some(&DataTooLarge {
+                size,
+                limit: size - 1
+            })
+        );
+        assert_eq!(sets, 0);
+
+        // `0` disables the check
+        let (res, sets) = save(0, None).await?;
+        assert!(res.is_ok());
+        assert_eq!(sets, 1);
+
+        // Measured with the serializer of the storage
+        for serializer in serializers() {
+            let size = serializer.serialize(&stamped)?.len();
+            let (res, _) = save(size, Some(serializer.clone())).await?;
+            assert!(res.is_ok());
+            let (res, sets) = save(size - 1, Some(serializer)).await?;
+            assert!(res.unwrap_err().is::<DataTooLarge>());
+            assert_eq!(sets, 0);
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn browser_session_cookie() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(CountingStorage::default());
+        let browser = Arc::new(Config {
+            cookie: CookieOptions::new().without_max_age(),
+            ttl: Some(Duration::from_secs(3600)),
+            ..config(storage.clone(), None)
+        });
+        assert_eq!(browser.store_ttl(), Duration::from_secs(3600));
+
+        let session = Session::new(
+            &browser.generate(),
+            SessionStatus::Unchanged,
+            browser.clone(),
+        );
+        session.set("user", "fundon")?;
+        session.save().await?;
+        assert_eq!(
+            *storage.exp.lock().unwrap(),
+            Some(Duration::from_secs(3600))
+        );
+        let cookie = session.cookie_header()?.unwrap();
+        assert!(!cookie.contains("Max-Age"));
+        assert!(!cookie.contains("Expires"));
+
+        // The ttl wins over the cookie's max_age
+        let with_ttl = Config {
+            cookie: CookieOptions::new().with_max_age(Duration::from_secs(60)),
+            ttl: Some(Duration::from_secs(3600)),
+            ..config(storage.clone(), None)
+        };
+        assert_eq!(with_ttl.store_ttl(), Duration::from_secs(3600));
+        assert_eq!(
+            with_ttl.cookie.to_set_cookie("abc")?,
+            "viz.sid=abc; Max-Age=60; Path=/; HttpOnly"
+        );
+
+        // Without either, the storage keeps a browser-session cookie for a day
+        let without = Config {
+            cookie: CookieOptions::new().without_max_age(),
+            ..config(storage, None)
+        };
+        assert_eq!(without.store_ttl(), Duration::from_secs(3600 * 24));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn remember_me() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(CountingStorage::default());
+        let remember = Arc::new(Config {
+            cookie: CookieOptions::new().with_max_age(Duration::from_secs(3600 * 24)),
+            ttl: Some(Duration::from_secs(3600 * 24 * 30)),
+            ..config(storage.clone(), None)
+        });
+        assert_eq!(remember.store_ttl(), Duration::from_secs(3600 * 24 * 30));
+        assert_eq!(
+            remember.cookie_max_age(),
+            Some(Duration::from_secs(3600 * 24))
+        );
+
+        let session = Session::new(
+            &remember.generate(),
+            SessionStatus::Unchanged,
+            remember.clone(),
+        );
+        session.set("user", "fundon")?;
+        session.save().await?;
+        assert_eq!(
+            *storage.exp.lock().unwrap(),
+            Some(Duration::from_secs(3600 * 24 * 30))
+        );
+        assert_eq!(
+            session.cookie_header()?,
+            Some(format!(
+                "viz.sid={}; Max-Age=86400; Path=/; HttpOnly",
+                session.id()?
+            ))
+        );
+
+        // The ttl defaults to the cookie's max_age
+        let short = Config {
+            cookie: CookieOptions::new().with_max_age(Duration::from_secs(60)),
+            ..config(storage, None)
+        };
+        assert_eq!(short.store_ttl(), Duration::from_secs(60));
+        assert_eq!(short.cookie_max_age(), Some(Duration::from_secs(60)));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn invalid_ids_skip_storage() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(CountingStorage::default());
+        let config = Config::builder()
+            .storage(storage.clone())
+            .generate(|| nanoid::nanoid!(32))
+            .build()?;
+
+        for id in [
+            "../../../etc/passwd",
+            "0123456789abcdef\0",
+            "01234567\089abcdef",
+            &"a".repeat(10 * 1024),
+            "",
+        ] {
+            assert_eq!(config.get(id).await?, None);
+            assert_eq!(config.metadata(id).await?, None);
+            assert!(!config.touch(id, 0, Duration::from_secs(1)).await?);
+            config.remove(id).await?;
+        }
+        assert_eq!(storage.reads.load(Ordering::SeqCst), 0);
+
+        let id = config.generate();
+        assert_eq!(config.get(&id).await?, None);
+        config.remove(&id).await?;
+        assert_eq!(storage.reads.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn max_age_override() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(CountingStorage::default());
+        let config = Arc::new(Config {
+            cookie: CookieOptions::new().with_max_age(Duration::from_secs(3600)),
+            ..config(storage.clone(), None)
+        });
+        let remember_me = Duration::from_secs(3600 * 24 * 30);
+
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.set_max_age(remember_me)?;
+        assert_eq!(session.max_age(), remember_me);
+        assert_eq!(session.cookie_max_age(), Some(remember_me));
+        session.save().await?;
+        assert_eq!(*storage.exp.lock().unwrap(), Some(remember_me));
+        assert_eq!(
+            session.cookie_header()?,
+            Some(format!(
+                "viz.sid={}; Max-Age=2592000; Path=/; HttpOnly",
+                session.id()?
+            ))
+        );
+
+        // Survives a reload
+        let id = session.id()?;
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set_data(config.get(&id).await?.unwrap())?;
+        assert_eq!(session.max_age(), remember_me);
+        assert!(!session.data_status());
+
+        // Cleared, back to the config
+        session.clear_max_age()?;
+        assert!(session.data_status());
+        assert_eq!(session.max_age(), Duration::from_secs(3600));
+        assert_eq!(session.cookie_max_age(), Some(Duration::from_secs(3600)));
+        session.save().await?;
+        assert_eq!(
+            *storage.exp.lock().unwrap(),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            session.cookie_header()?,
+            Some(format!("viz.sid={}; Max-Age=3600; Path=/; HttpOnly", id))
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn rolling() -> Result<()> {
+    block_on(async {
+        let now = Arc::new(AtomicU64::new(to_millis(SystemTime::now())));
+        let clock = now.clone();
+        let storage = Arc::new(CountingStorage::default());
+        let config = Arc::new(
+            Config::builder()
+                .storage(storage.clone())
+                .generate(|| nanoid::nanoid!(32))
+                .max_age(Duration::from_secs(3600))
+                .rolling(true)
+                .rolling_interval(Duration::from_secs(60))
+                .absolute_timeout(Duration::from_secs(2 * 3600))
+                .clock(move || from_millis(clock.load(Ordering::SeqCst)).unwrap())
+                .build()?,
+        );
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 1);
+
+        // A request loading the session
+        let request = |secs: u64| {
+            now.fetch_add(secs * 1000, Ordering::SeqCst);
+            let config = config.clone();
+            let id = id.clone();
+            async move {
+                let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+                session.set_data(config.get(&id).await?.unwrap())?;
+                SessionGuard::new(session.clone()).finish().await?;
+                Result::<_>::Ok(session)
+            }
+        };
+
+        // Throttled by the interval
+        let session = request(30).await?;
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        assert_eq!(session.cookie_header()?, None);
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 1);
+
+        // Refreshed, with a new cookie
+        let session = request(31).await?;
+        assert_eq!(session.status(), SessionStatus::Saved);
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            *storage.exp.lock().unwrap(),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            session.cookie_header()?,
+            Some(format!("viz.sid={}; Max-Age=3600; Path=/; HttpOnly", id))
+        );
+        let session = request(59).await?;
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 2);
+        request(1).await?;
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 3);
+
+        // Never past the absolute timeout
+        let session = request(3600 + 1800).await?;
+        assert_eq!(session.status(), SessionStatus::Saved);
+        assert_eq!(
+            *storage.exp.lock().unwrap(),
+            Some(Duration::from_secs(2 * 3600 - 3600 - 1800 - 121))
+        );
+
+        // Changed sessions are saved rather than touched
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set_data(config.get(&id).await?.unwrap())?;
+        now.fetch_add(120_000, Ordering::SeqCst);
+        session.set("cart", 1)?;
+        session.save().await?;
+        assert!(!session.roll().await?);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn save_uninitialized() -> Result<()> {
+    block_on(async {
+        let storage = Arc::new(CountingStorage::default());
+        let lazy = Arc::new(config(storage.clone(), None));
+        assert!(!lazy.save_uninitialized);
+
+        // A read-only request
+        let session = Session::new(&lazy.generate(), SessionStatus::Unchanged, lazy.clone());
+        assert!(session.is_fresh());
+        assert_eq!(session.get::<String>("user")?, None);
+        session.save().await?;
+        assert!(session.is_fresh());
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        assert_eq!(session.cookie_header()?, None);
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 0);
+
+        // The first write needs a save and a cookie
+        session.set("user", "fundon")?;
+        assert!(!session.is_fresh());
+        session.save().await?;
+        assert!(!session.is_fresh());
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 1);
+        assert!(session.cookie_header()?.is_some());
+
+        // Saving uninitialized sessions, every visitor gets one
+        let eager = Arc::new(Config {
+            save_uninitialized: true,
+            ..config(storage.clone(), None)
+        });
+        let session = Session::new(&eager.generate(), SessionStatus::Unchanged, eager.clone());
+        assert!(session.is_fresh());
+        session.save().await?;
+        assert!(!session.is_fresh());
+        assert_eq!(storage.sets.load(Ordering::SeqCst), 2);
+        assert!(session.cookie_header()?.is_some());
+
+        Ok(())
+    })
+}
+
+/// Logs the store calls and the hooks in the order they ran
+#[derive(Debug, Default, Clone)]
+struct Log(Arc<Mutex<Vec<String>>>);
+
+impl Log {
+    fn push(&self, event: String) {
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+#[derive(Debug, Default)]
+struct LoggedStorage {
+    inner: CountingStorage,
+    log: Log,
+}
+
+#[async_trait]
+impl Storage for LoggedStorage {
+    async fn get(&self, key: &str) -> Result<Option<Data>> {
+        self.inner.get(key).await
+    }
+
+    async fn set(&self, key: &str, val: Data, exp: Duration) -> Result<()> {
+        self.log.push(format!("set {}", key));
+        self.inner.set(key, val, exp).await
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.log.push(format!("remove {}", key));
+        self.inner.remove(key).await
+    }
+}
+
+struct LoggedHooks {
+    log: Log,
+    fail: bool,
+}
+
+#[async_trait]
+impl SessionHooks for LoggedHooks {
+    async fn on_create(&self, sid: &str) -> Result<()> {
+        self.log.push(format!("on_create {}", sid));
+        if self.fail {
+            anyhow::bail!("audit log is down");
+        }
+        Ok(())
+    }
+
+    async fn on_save(&self, sid: &str) -> Result<()> {
+        self.log.push(format!("on_save {}", sid));
+        Ok(())
+    }
+
+    async fn on_destroy(&self, sid: &str) -> Result<()> {
+        self.log.push(format!("on_destroy {}", sid));
+        Ok(())
+    }
+
+    async fn on_renew(&self, old_sid: &str, new_sid: &str) -> Result<()> {
+        self.log.push(format!("on_renew {} {}", old_sid, new_sid));
+        Ok(())
+    }
+
+    async fn on_expire(&self, sid: &str) -> Result<()> {
+        self.log.push(format!("on_expire {}", sid));
+        Ok(())
+    }
+}
+
+#[test]
+fn hooks() -> Result<()> {
+    block_on(async {
+        let log = Log::default();
+        let storage = Arc::new(LoggedStorage {
+            log: log.clone(),
+            ..Default::default()
+        });
+        let config = Arc::new(Config {
+            hooks: Some(Arc::new(LoggedHooks {
+                log: log.clone(),
+                fail: false,
+            })),
+            ..config(storage, None)
+        });
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        session.set("theme", "dark")?;
+        session.save().await?;
+        assert_eq!(
+            log.take(),
+            vec![
+                format!("set {}", id),
+                format!("on_create {}", id),
+                format!("set {}", id),
+                format!("on_save {}", id),
+            ]
+        );
+
+        // A save without changes runs no hook
+        session.save().await?;
+        assert!(log.take().is_empty());
+
+        session.renew_keeping_data().await?;
+        let new = session.id()?;
+        assert_eq!(
+            log.take(),
+            vec![
+                format!("set {}", new),
+                format!("remove {}", id),
+                format!("on_renew {} {}", id, new),
+            ]
+        );
+
+        session.destroy().await?;
+        assert_eq!(
+            log.take(),
+            vec![format!("remove {}", new), format!("on_destroy {}", new)]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn expire_hook() -> Result<()> {
+    block_on(async {
+        let log = Log::default();
+        let storage = Arc::new(LoggedStorage {
+            log: log.clone(),
+            ..Default::default()
+        });
+        let now = Arc::new(AtomicU64::new(to_millis(SystemTime::now())));
+        let clock = now.clone();
+        let config = Arc::new(Config {
+            hooks: Some(Arc::new(LoggedHooks {
+                log: log.clone(),
+                fail: false,
+            })),
+            absolute_timeout: Some(Duration::from_secs(60)),
+            clock: Some(Box::new(move || {
+                from_millis(clock.load(Ordering::SeqCst)).unwrap()
+            })),
+            ..config(storage.clone(), None)
+        });
+        let id = config.generate();
+
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+        assert!(config.get(&id).await?.is_some());
+        log.take();
+
+        // The timed out session is removed as the hook runs, later reads miss it
+        now.fetch_add(61 * 1000, Ordering::SeqCst);
+        assert!(config.get(&id).await?.is_none());
+        assert!(config.get(&id).await?.is_none());
+        assert!(config.load_session(&id).await?.is_new());
+        assert_eq!(
+            log.take(),
+            vec![format!("remove {}", id), format!("on_expire {}", id)]
+        );
+        assert!(storage.inner.get(&id).await?.is_none());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn strict_hooks() -> Result<()> {
+    block_on(async {
+        let log = Log::default();
+        let storage = Arc::new(LoggedStorage {
+            log: log.clone(),
+            ..Default::default()
+        });
+        let hooks = || {
+            Some(Arc::new(LoggedHooks {
+                log: log.clone(),
+                fail: true,
+            }) as Arc<dyn SessionHooks>)
+        };
+
+        // The error is logged, the save succeeds
+        let lenient = Arc::new(Config {
+            hooks: hooks(),
+            ..config(storage.clone(), None)
+        });
+        let session = Session::new(&lenient.generate(), SessionStatus::Unchanged, lenient);
+        session.set("user", "fundon")?;
+        assert!(session.save().await.is_ok());
+        assert_eq!(session.status(), SessionStatus::Saved);
+
+        // The error fails the save, after the store call
+        let strict = Arc::new(Config {
+            hooks: hooks(),
+            strict_hooks: true,
+            ..config(storage.clone(), None)
+        });
+        let id = strict.generate();
+        log.take();
+        let session = Session::new(&id, SessionStatus::Unchanged, strict.clone());
+        session.set("user", "fundon")?;
+        assert!(session.save().await.is_err());
+        assert_eq!(
+            log.take(),
+            vec![format!("set {}", id), format!("on_create {}", id)]
+        );
+        assert!(strict.get(&id).await?.is_some());
+
+        Ok(())
+    })
+}
+
+fn serializers() -> Vec<Arc<dyn Serializer>> {
+    vec![
+        Arc::new(Json),
+        #[cfg(feature = "msgpack")]
+        Arc::new(MessagePack),
+        #[cfg(feature = "bincode")]
+        Arc::new(Bincode),
+        #[cfg(feature = "cbor")]
+        Arc::new(Cbor),
+        #[cfg(feature = "postcard")]
+        Arc::new(Postcard),
+    ]
+}
+
+#[test]
+fn serializer_round_trip() -> Result<()> {
+    block_on(async {
+        let data: Data = serde_json::from_value(serde_json::json!({
+            "user": "fundon",
+            "null": null,
+            "flag": true,
+            "count": u64::MAX,
+            "delta": -42,
+            "ratio": 0.5,
+            "tags": ["a", 1, [], {}],
+            "nested": { "cart": { "items": [1, 2, 3] } },
+            "_user": "fundon",
+            "_version": 3,
+        }))?;
+
+        for serializer in serializers() {
+            assert_eq!(serializer.deserialize(&serializer.serialize(&data)?)?, data);
+
+            let storage = Serialized::with_serializer(BytesStorage::default(), serializer.clone());
+            storage
+                .set("sid", data.clone(), Duration::from_secs(60))
+                .await?;
+            assert_eq!(storage.get("sid").await?, Some(data.clone()));
+            assert_eq!(storage.metadata("sid").await?.unwrap().version, 3);
+            assert_eq!(storage.sessions_for("fundon").await?, vec!["sid"]);
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn serializer_mismatch() -> Result<()> {
+    block_on(async {
+        let mut data = Data::new();
+        data.insert("user".into(), "fundon".into());
+        data.insert("cart".into(), serde_json::json!([1, 2, 3]));
+
+        for writer in serializers() {
+            let bytes = BytesStorage::default();
+            Serialized::with_serializer(bytes.clone(), writer.clone())
+                .set("sid", data.clone(), Duration::from_secs(60))
+                .await?;
+            for reader in serializers() {
+                let storage = Serialized::with_serializer(bytes.clone(), reader.clone());
+                let read = storage.get("sid").await?;
+                let names = [format!("{:?}", writer), format!("{:?}", reader)];
+                // Bincode and postcard write the same mirror of the values alike
+                let shared = names
+                    .iter()
+                    .all(|name| name == "Bincode" || name == "Postcard");
+                if names[0] == names[1] || shared {
+                    assert_eq!(read, Some(data.clone()));
+                } else {
+                    // Missing rather than an error or garbage
+                    assert_eq!(read, None, "{:?} read by {:?}", writer, reader);
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn config_serializer() -> Result<()> {
+    block_on(async {
+        let bytes = BytesStorage::default();
+        let config = Arc::new(
+            Config::builder()
+                .byte_storage(bytes.clone())
+                .serializer(MessagePack)
+                .generate(|| nanoid::nanoid!(32))
+                .build()?,
+        );
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+
+        let id = session.id()?;
+        let buf = bytes.get(&id).await?.unwrap();
+        let record = record::decode(&buf).unwrap();
+        assert!(MessagePack.deserialize(record.body).is_ok());
+        assert!(Json.deserialize(record.body).is_err());
+        assert_eq!(
+            config.get(&id).await?.unwrap().get("user"),
+            Some(&"fundon".into())
+        );
+
+        // JSON by default
+        let config = Config::builder()
+            .byte_storage(bytes)
+            .generate(|| nanoid::nanoid!(32))
+            .build()?;
+        assert_eq!(config.get(&id).await?, None);
+
+        Ok(())
+    })
+}
+
+#[cfg(feature = "msgpack")]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Cart {
+    owner: String,
+    items: Vec<Item>,
+    coupon: Option<String>,
+    total: f64,
+}
+
+#[cfg(feature = "msgpack")]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Item {
+    sku: u64,
+    quantity: i32,
+    delta: i64,
+    tags: HashMap<String, bool>,
+}
+
+#[cfg(feature = "msgpack")]
+fn cart() -> Cart {
+    Cart {
+        owner: "fundon".to_string(),
+        items: (0..8)
+            .map(|i| Item {
+                sku: u64::MAX - i,
+                quantity: i as i32,
+                delta: -(i as i64) * 1_000_000,
+                tags: vec![("gift".to_string(), i % 2 == 0)].into_iter().collect(),
+            })
+            .collect(),
+        coupon: None,
+        total: 1.0,
+    }
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn msgpack_numbers() -> Result<()> {
+    let data: Data = serde_json::from_value(serde_json::json!({
+        "zero": 0,
+        "small": 7,
+        "negative": -1,
+        "u64_max": u64::MAX,
+        "i64_max": i64::MAX,
+        "i64_min": i64::MIN,
+        "float": 0.25,
+        "whole_float": 1.0,
+        "large_float": 1e300,
+        "nested": [[-128, 255, 65536], { "n": -2147483649i64 }],
+    }))?;
+
+    // The same kinds of numbers as the JSON path
+    let json = Json.deserialize(&Json.serialize(&data)?)?;
+    let msgpack = MessagePack.deserialize(&MessagePack.serialize(&data)?)?;
+    assert_eq!(msgpack, json);
+    assert_eq!(msgpack, data);
+    assert!(msgpack["u64_max"].is_u64());
+    assert!(msgpack["i64_max"].is_u64());
+    assert!(msgpack["i64_min"].is_i64());
+    assert!(msgpack["negative"].is_i64());
+    assert!(msgpack["whole_float"].is_f64());
+    assert_eq!(msgpack["whole_float"].as_f64(), Some(1.0));
+
+    Ok(())
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn msgpack_size() -> Result<()> {
+    let mut data = Data::new();
+    data.insert("cart".into(), serde_json::to_value(cart())?);
+    data.insert("user".into(), "fundon".into());
+    data.insert("_version".into(), 12.into());
+    data.insert("_last_accessed".into(), 1_700_000_000_000u64.into());
+
+    let json = Json.serialize(&data)?;
+    let msgpack = MessagePack.serialize(&data)?;
+    assert!(
+        msgpack.len() * 4 < json.len() * 3,
+        "msgpack {} bytes, json {} bytes",
+        msgpack.len(),
+        json.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn msgpack_session_round_trip() -> Result<()> {
+    block_on(async {
+        let bytes = BytesStorage::default();
+        let config = Arc::new(
+            Config::builder()
+                .byte_storage(bytes.clone())
+                .serializer(MessagePack)
+                .generate(|| nanoid::nanoid!(32))
+                .build()?,
+        );
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.set("cart", cart())?;
+        session.set("visits", 3u8)?;
+        session.set("balance", -12i64)?;
+        session.set("ratio", 0.5f32)?;
+        session.save().await?;
+
+        let id = session.id()?;
+        let buf = bytes.get(&id).await?.unwrap();
+        assert!(Json
+            .deserialize(record::decode(&buf).unwrap().body)
+            .is_err());
+
+        let loaded = config.load_session(&id).await?;
+        assert!(!loaded.is_new());
+        assert_eq!(loaded.get::<Cart>("cart")?, Some(cart()));
+        assert_eq!(loaded.get::<u8>("visits")?, Some(3));
+        assert_eq!(loaded.get::<i64>("balance")?, Some(-12));
+        assert_eq!(loaded.get::<f32>("ratio")?, Some(0.5));
+
+        // Saved again, the data reads the same
+        loaded.set("visits", 4u8)?;
+        loaded.save().await?;
+        let reloaded = config.load_session(&id).await?;
+        assert_eq!(reloaded.get::<Cart>("cart")?, Some(cart()));
+        assert_eq!(reloaded.get::<u8>("visits")?, Some(4));
+
+        Ok(())
+    })
+}
+
+/// Saves a session through the serializer, reloads and exports it, with the size of
+/// its serialized data
+#[cfg(feature = "cbor")]
+async fn export_through(
+    serializer: impl Serializer,
+    thumbnail: &[u8],
+) -> Result<(SessionExport, usize)> {
+    let bytes = BytesStorage::default();
+    let config = Arc::new(
+        Config::builder()
+            .byte_storage(bytes.clone())
+            .serializer(serializer)
+            .generate(|| nanoid::nanoid!(32))
+            .build()?,
+    );
+    let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+    session.set("user", "fundon")?;
+    session.set("thumbnail", binary::to_value(thumbnail))?;
+    session.set(
+        "prefs",
+        serde_json::json!({ "theme": "dark", "zoom": 1.25, "limits": [u64::MAX, -7] }),
+    )?;
+    session.save().await?;
+
+    let id = session.id()?;
+    let buf = bytes.get(&id).await?.unwrap();
+    let size = record::decode(&buf).unwrap().body.len();
+    let loaded = config.load_session(&id).await?;
+    Ok((loaded.export()?, size))
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_export_matches_json() -> Result<()> {
+    block_on(async {
+        let thumbnail = (0..=255).collect::<Vec<u8>>();
+        let (json, json_size) = export_through(Json, &thumbnail).await?;
+        let (cbor, cbor_size) = export_through(Cbor, &thumbnail).await?;
+
+        assert_eq!(cbor.data, json.data);
+        assert_eq!(cbor.version, json.version);
+        assert_eq!(
+            binary::from_value(&cbor.data["thumbnail"]),
+            Some(thumbnail.clone())
+        );
+        // The bytes themselves, without the base64
+        assert!(
+            cbor_size + 80 < json_size,
+            "cbor {} bytes, json {} bytes",
+            cbor_size,
+            json_size
+        );
+
+        Ok(())
+    })
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_foreign_data() -> Result<()> {
+    // { "at": 1(1600000000), "raw": h'0102' }, a tagged epoch time and a byte string
+    let data = Cbor.deserialize(&[
+        0xa2, 0x62, b'a', b't', 0xc1, 0x1a, 0x5f, 0x5e, 0x10, 0x00, 0x63, b'r', b'a', b'w', 0x42,
+        0x01, 0x02,
+    ])?;
+    assert_eq!(data["at"], 1_600_000_000u64);
+    assert_eq!(binary::from_value(&data["raw"]), Some(vec![1, 2]));
+    let buf = Cbor.serialize(&data)?;
+    assert_eq!(
+        buf.windows(3).filter(|w| *w == [0x42, 0x01, 0x02]).count(),
+        1
+    );
+
+    // { 1: 2 }, map keys must be text
+    assert!(Cbor.deserialize(&[0xa1, 0x01, 0x02]).is_err());
+    // [], the data is a map
+    assert!(Cbor.deserialize(&[0x80]).unwrap_err().is::<DataError>());
+    // -2^64, below i64
+    assert!(Cbor
+        .deserialize(&[0xa1, 0x61, b'n', 0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff])
+        .is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "postcard")]
+#[test]
+fn postcard_size() -> Result<()> {
+    let data: Data = serde_json::from_value(serde_json::json!({
+        "_user": "42",
+        "_version": 7,
+        "_created_at": 1_700_000_000_000u64,
+        "_last_accessed": 1_700_000_360_000u64,
+        "_csrf": "c3Jm5pLq0xG2zT8Vb1nY4wKe",
+        "_flash": [{ "level": "info", "message": "Welcome back" }],
+        "cart": {
+            "items": [
+                { "sku": 1001, "quantity": 2, "price": 9.99 },
+                { "sku": 1002, "quantity": 1, "price": 24.5 },
+            ],
+            "coupon": null,
+        },
+        "locale": "en-US",
+        "avatar": binary::to_value(&[0x89, b'P', b'N', b'G', 0, 0, 0, 13]),
+    }))?;
+
+    let json = Json.serialize(&data)?;
+    let postcard = Postcard.serialize(&data)?;
+    assert!(
+        postcard.len() * 5 < json.len() * 4,
+        "postcard {} bytes, json {} bytes",
+        postcard.len(),
+        json.len()
+    );
+    assert_eq!(Postcard.deserialize(&postcard)?, data);
+
+    // Truncated or trailing bytes aren't read
+    assert!(Postcard
+        .deserialize(&postcard[..postcard.len() - 1])
+        .is_err());
+    let mut trailing = postcard.clone();
+    trailing.push(0);
+    assert!(Postcard.deserialize(&trailing).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "postcard")]
+#[test]
+fn postcard_session_round_trip() -> Result<()> {
+    block_on(async {
+        let config = Arc::new(
+            Config::builder()
+                .byte_storage(BytesStorage::default())
+                .serializer(Postcard)
+                .generate(|| nanoid::nanoid!(32))
+                .build()?,
+        );
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.set("visits", 3u8)?;
+        session.set("balance", -12i64)?;
+        session.set("ratio", 0.1f64)?;
+        session.set("tags", vec!["a", "ü"])?;
+        session.set("raw", binary::to_value(&[0, 255]))?;
+        session.save().await?;
+
+        let loaded = config.load_session(&session.id()?).await?;
+        assert!(!loaded.is_new());
+        assert_eq!(loaded.get::<u8>("visits")?, Some(3));
+        assert_eq!(loaded.get::<i64>("balance")?, Some(-12));
+        assert_eq!(loaded.get::<f64>("ratio")?, Some(0.1));
+        assert_eq!(
+            loaded.get::<Vec<String>>("tags")?,
+            Some(vec!["a".into(), "ü".into()])
+        );
+        assert_eq!(
+            binary::from_value(&loaded.get_value("raw").unwrap()),
+            Some(vec![0, 255])
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn binary_values() -> Result<()> {
+    block_on(async {
+        let avatar = (0..=255).cycle().take(600).collect::<Vec<u8>>();
+        let encoded = binary::to_value(&avatar)[binary::KEY]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        for serializer in serializers() {
+            let bytes = BytesStorage::default();
+            let storage: Arc<dyn Storage> = Arc::new(Serialized::with_serializer(
+                bytes.clone(),
+                serializer.clone(),
+            ));
+            let config = Arc::new(
+                Config::builder()
+                    .storage(storage)
+                    .generate(|| nanoid::nanoid!(32))
+                    .build()?,
+            );
+            let session =
+                Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+            session.set("user", "fundon")?;
+            // A string which happens to be base64 stays a string
+            session.set("token", &encoded)?;
+            session.set_bytes("avatar", &avatar)?;
+            session.set_bytes("empty", Vec::new())?;
+            session.save().await?;
+
+            let buf = bytes.get(&session.id()?).await?.unwrap();
+            let body = record::decode(&buf).unwrap().body;
+            let native = body.windows(avatar.len()).any(|w| w == &avatar[..]);
+            assert_eq!(
+                native,
+                format!("{:?}", serializer) != "Json",
+                "{:?}",
+                serializer
+            );
+
+            let loaded = config.load_session(&session.id()?).await?;
+            assert_eq!(loaded.get_bytes("avatar"), Some(avatar.clone()));
+            assert_eq!(loaded.get_bytes("empty"), Some(Vec::new()));
+            assert_eq!(loaded.get_bytes("token"), None);
+            assert_eq!(loaded.get_bytes("missing"), None);
+            assert_eq!(loaded.get::<String>("token")?, Some(encoded.clone()));
+            assert!(loaded.get::<String>("avatar").is_err());
+
+            assert_eq!(
+                loaded.get_raw("avatar"),
+                Some(binary::RawValue::Bytes(avatar.clone()))
+            );
+            assert_eq!(
+                loaded.get_raw("user"),
+                Some(binary::RawValue::Value("fundon".into()))
+            );
+            let mut raw = loaded.iter_raw().collect::<Vec<_>>();
+            raw.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                raw,
+                vec![
+                    (
+                        "avatar".to_string(),
+                        binary::RawValue::Bytes(avatar.clone())
+                    ),
+                    ("empty".to_string(), binary::RawValue::Bytes(Vec::new())),
+                    (
+                        "token".to_string(),
+                        binary::RawValue::Value(encoded.clone().into())
+                    ),
+                    ("user".to_string(), binary::RawValue::Value("fundon".into())),
+                ]
+            );
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn canonical_json() -> Result<()> {
+    block_on(async {
+        let bytes = BytesStorage::default();
+        let config = Arc::new(
+            Config::builder()
+                .byte_storage(bytes.clone())
+                .serializer(Json::canonical())
+                .generate(|| nanoid::nanoid!(32))
+                .clock(|| SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+                .build()?,
+        );
+
+        let first = Session::new("first", SessionStatus::Unchanged, config.clone());
+        first.set("user", "fundon")?;
+        first.set("prefs", serde_json::json!({ "zoom": 1.5, "theme": "dark", "nested": { "b": -0.0, "a": [3, 1] } }))?;
+        first.set("cart", vec![1u64, 2])?;
+        first.save().await?;
+
+        let mut prefs = serde_json::Map::new();
+        prefs.insert(
+            "nested".into(),
+            serde_json::json!({ "a": [3, 1], "b": 0.0 }),
+        );
+        prefs.insert("theme".into(), "dark".into());
+        prefs.insert("zoom".into(), 1.5.into());
+        let second = Session::new("second", SessionStatus::Unchanged, config.clone());
+        second.set("cart", vec![1u64, 2])?;
+        second.set("prefs", prefs)?;
+        second.set("user", "fundon")?;
+        second.save().await?;
+
+        let first = bytes.get("first").await?.unwrap();
+        let second = bytes.get("second").await?.unwrap();
+        let body = record::decode(&first).unwrap().body;
+        assert_eq!(body, record::decode(&second).unwrap().body);
+        assert_eq!(
+            std::str::from_utf8(body)?,
+            r#"{"_created_at":1700000000000,"_last_accessed":1700000000000,"_version":1,"cart":[1,2],"prefs":{"nested":{"a":[3,1],"b":0.0},"theme":"dark","zoom":1.5},"user":"fundon"}"#
+        );
+        assert_eq!(config.get("first").await?, config.get("second").await?);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn save_semantically_unchanged() -> Result<()> {
+    block_on(async {
+        let bytes = BytesStorage::default();
+        let config = Arc::new(
+            Config::builder()
+                .byte_storage(bytes.clone())
+                .generate(|| nanoid::nanoid!(32))
+                .build()?,
+        );
+        let session = Session::new(&config.generate(), SessionStatus::Unchanged, config.clone());
+        session.set("visits", 1)?;
+        session.set("prefs", serde_json::json!({ "theme": "dark", "zoom": 1.5 }))?;
+        session.save().await?;
+        let id = session.id()?;
+
+        // Written back to the stored values, nothing to save
+        let loaded = config.load_session(&id).await?;
+        loaded.set("visits", 2)?;
+        loaded.set("visits", 1)?;
+        loaded.modify("prefs", |prefs: &mut serde_json::Value| {
+            prefs["zoom"] = 1.5.into();
+        })?;
+        assert!(loaded.data_status());
+        loaded.save().await?;
+        assert_eq!(config.get(&id).await?.unwrap()[keys::VERSION], 1);
+
+        loaded.set("visits", 2)?;
+        loaded.save().await?;
+        assert_eq!(config.get(&id).await?.unwrap()[keys::VERSION], 2);
+
+        // Then back to the first values, which aren't the stored ones anymore
+        loaded.set("visits", 1)?;
+        loaded.save().await?;
+        assert_eq!(config.get(&id).await?.unwrap()[keys::VERSION], 3);
+
+        Ok(())
+    })
+}
+
+fn encrypted(bytes: &BytesStorage, serializer: EncryptedSerializer<Json>) -> Result<Arc<Config>> {
+    Ok(Arc::new(
+        Config::builder()
+            .byte_storage(bytes.clone())
+            .serializer(serializer)
+            .generate(|| nanoid::nanoid!(32))
+            .build()?,
+    ))
+}
+
+#[test]
+fn encrypted_serializer() -> Result<()> {
+    block_on(async {
+        let bytes = BytesStorage::default();
+        let key = Key::generate()?;
+        let config = encrypted(&bytes, EncryptedSerializer::new(Json, key.clone()))?;
+
+        let session = config.load_session("").await?;
+        session.set("user", "fundon")?;
+        session.save().await?;
+        let id = session.id()?;
+        let stored = bytes.get(&id).await?.unwrap();
+        let body = record::decode(&stored).unwrap().body;
+        assert!(!body.windows(6).any(|w| w == b"fundon"));
+
+        let loaded = config.load_session(&id).await?;
+        assert!(!loaded.is_new());
+        assert_eq!(loaded.get::<String>("user")?, Some("fundon".to_string()));
+
+        // Encrypting again takes a fresh nonce, unchanged data isn't written
+        let serializer = EncryptedSerializer::new(Json, key.clone());
+        let data = config.get(&id).await?.unwrap();
+        assert_ne!(serializer.serialize(&data)?, serializer.serialize(&data)?);
+        loaded.set("user", "fundon")?;
+        loaded.save().await?;
+        assert_eq!(bytes.get(&id).await?.unwrap(), stored);
+
+        // Tampered data is a missing session
+        let mut tampered = stored.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        bytes.set(&id, tampered, Duration::from_secs(60)).await?;
+        assert!(config.get(&id).await?.is_none());
+        assert!(config.load_session(&id).await?.is_new());
+        bytes
+            .set(&id, stored.clone(), Duration::from_secs(60))
+            .await?;
+
+        // So is data encrypted under another key
+        let other = encrypted(&bytes, EncryptedSerializer::new(Json, Key::generate()?))?;
+        assert!(other.get(&id).await?.is_none());
+        assert!(other.load_session(&id).await?.is_new());
+        assert!(serializer.deserialize(b"{}").is_err());
+
+        // After a rotation, the previous key still decrypts, the next save uses the active one
+        let active = Key::generate()?;
+        let rotated = encrypted(
+            &bytes,
+            EncryptedSerializer::with_keys(Json, active.clone(), vec![key]),
+        )?;
+        let loaded = rotated.load_session(&id).await?;
+        assert_eq!(loaded.get::<String>("user")?, Some("fundon".to_string()));
+        loaded.set("user", "fundon2")?;
+        loaded.save().await?;
+        assert!(config.get(&id).await?.is_none());
+        let renewed = encrypted(&bytes, EncryptedSerializer::new(Json, active))?;
+        assert_eq!(renewed.get(&id).await?.unwrap()["user"], "fundon2");
+
+        Ok(())
+    })
+}
+
+/// The application state of a web framework, `Config` and `Session` name no closure
+/// or storage types
+struct AppState {
+    config: Arc<Config>,
+}
+
+#[test]
+fn erased_types() -> Result<()> {
+    fn assert_shareable<T: Send + Sync + 'static>() {}
+    assert_shareable::<Config>();
+    assert_shareable::<Session>();
+    assert_shareable::<AppState>();
+
+    let state = AppState {
+        config: Arc::new(config(Arc::new(CountingStorage::default()), None)),
+    };
+    let swapped = AppState {
+        config: Arc::new(config(
+            Arc::new(Serialized::new(BytesStorage::default())),
+            None,
+        )),
+    };
+    for state in [state, swapped] {
+        let session = Session::new(
+            &state.config.generate(),
+            SessionStatus::Unchanged,
+            state.config.clone(),
+        );
+        assert!(session.is_new());
+        assert_eq!(session.name(), state.config.cookie().name);
+        assert!(Arc::ptr_eq(&session.store(), &state.config.storage()));
+        session.set("user", "fundon")?;
+        block_on(session.save())?;
+        assert!(!session.is_new());
+    }
+
+    Ok(())
+}
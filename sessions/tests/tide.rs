@@ -0,0 +1,78 @@
+#![cfg(all(feature = "tide", feature = "memory"))]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures_executor::block_on;
+use tide::{
+    http::{
+        headers::{COOKIE, SET_COOKIE},
+        Method, Request, Response, Url,
+    },
+    StatusCode,
+};
+
+use sessions::*;
+
+fn app(config: Arc<Config>) -> tide::Server<()> {
+    let mut app = tide::new();
+    app.with(TideSessionMiddleware::new(config));
+    app.at("/count").get(|req: tide::Request<()>| async move {
+        let session = req.ext::<Session>().unwrap();
+        let count = session.get::<u64>("count")?.unwrap_or_default() + 1;
+        session.set("count", count)?;
+        Ok(count.to_string())
+    });
+    app.at("/logout").post(|req: tide::Request<()>| async move {
+        req.ext::<Session>().unwrap().destroy().await?;
+        Ok(StatusCode::NoContent)
+    });
+    app
+}
+
+async fn send(
+    app: &tide::Server<()>,
+    method: Method,
+    path: &str,
+    cookie: Option<&str>,
+) -> Result<(StatusCode, String, Option<String>)> {
+    let mut req = Request::new(method, Url::parse("http://localhost")?.join(path)?);
+    if let Some(cookie) = cookie {
+        req.insert_header(COOKIE, cookie);
+    }
+    let mut res: Response = app.respond(req).await.map_err(|e| e.into_inner())?;
+    let set_cookie = res.header(SET_COOKIE).map(|val| val.as_str().to_string());
+    let body = res.body_string().await.map_err(|e| e.into_inner())?;
+    Ok((res.status(), body, set_cookie))
+}
+
+#[test]
+fn counter() -> Result<()> {
+    block_on(async {
+        let config = Arc::new(
+            Config::builder()
+                .storage(Arc::new(MemoryStorage::new()))
+                .generate(|| nanoid::nanoid!(32))
+                .verify(|sid: &str| sid.len() == 32)
+                .build()?,
+        );
+        let app = app(config.clone());
+
+        let (_, body, set_cookie) = send(&app, Method::Get, "/count", None).await?;
+        assert_eq!(body, "1");
+        let set_cookie = set_cookie.unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        let (_, body, _) = send(&app, Method::Get, "/count", Some(&cookie)).await?;
+        assert_eq!(body, "2");
+
+        let (status, _, set_cookie) = send(&app, Method::Post, "/logout", Some(&cookie)).await?;
+        assert_eq!(status, StatusCode::NoContent);
+        assert_eq!(set_cookie, Some(config.cookie().to_removal_cookie()));
+
+        let (_, body, _) = send(&app, Method::Get, "/count", Some(&cookie)).await?;
+        assert_eq!(body, "1");
+
+        Ok(())
+    })
+}
@@ -0,0 +1,272 @@
+#![cfg(all(feature = "tonic", feature = "memory"))]
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{header::HeaderName, uri::PathAndQuery, Uri};
+use hyper_util::rt::TokioIo;
+use tonic::{
+    body::Body,
+    server::{Grpc, NamedService, UnaryService},
+    transport::{Channel, Endpoint, Server},
+    Code, Request, Response, Status,
+};
+use tonic_prost::ProstCodec;
+use tower::{service_fn, Service};
+
+use sessions::*;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Empty {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Visits {
+    #[prost(uint64, tag = "1")]
+    count: u64,
+}
+
+/// A `sessions.Counter` service, as tonic would generate it, with a `Visit`,
+/// a read-only `Peek` and a `Logout` method
+#[derive(Clone)]
+struct Counter;
+
+impl NamedService for Counter {
+    const NAME: &'static str = "sessions.Counter";
+}
+
+impl Service<http::Request<Body>> for Counter {
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        Box::pin(async move {
+            let method = match req.uri().path() {
+                "/sessions.Counter/Visit" => Method::Visit,
+                "/sessions.Counter/Peek" => Method::Peek,
+                "/sessions.Counter/Logout" => Method::Logout,
+                _ => return Ok(Status::unimplemented("").into_http()),
+            };
+            let mut grpc = Grpc::new(ProstCodec::<Visits, Empty>::default());
+            Ok(grpc.unary(method, req).await)
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Method {
+    Visit,
+    Peek,
+    Logout,
+}
+
+impl UnaryService<Empty> for Method {
+    type Response = Visits;
+    type Future = BoxFuture<Result<Response<Visits>, Status>>;
+
+    fn call(&mut self, req: Request<Empty>) -> Self::Future {
+        let method = *self;
+        Box::pin(async move {
+            let session = req
+                .extensions()
+                .get::<Session>()
+                .cloned()
+                .ok_or_else(|| Status::internal("no session"))?;
+            let count = match method {
+                Method::Visit => {
+                    let count = session.get::<u64>("count").unwrap().unwrap_or_default() + 1;
+                    session.set("count", count).unwrap();
+                    count
+                }
+                Method::Peek => session.get::<u64>("count").unwrap().unwrap_or_default(),
+                Method::Logout => {
+                    session.destroy().await.unwrap();
+                    0
+                }
+            };
+            Ok(Response::new(Visits { count }))
+        })
+    }
+}
+
+fn config() -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
+/// Serves the counter behind the layer over an in-process duplex stream
+async fn channel(layer: TonicSessionLayer) -> Channel {
+    let (client, server) = tokio::io::duplex(4096);
+    tokio::spawn(
+        Server::builder()
+            .layer(layer)
+            .add_service(Counter)
+            .serve_with_incoming(futures_util::stream::iter(vec![Ok::<_, std::io::Error>(
+                server,
+            )])),
+    );
+    let mut client = Some(client);
+    Endpoint::from_static("http://[::]:50051")
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let client = client.take();
+            async move {
+                client
+                    .map(TokioIo::new)
+                    .ok_or_else(|| std::io::Error::other("a single connection"))
+            }
+        }))
+        .await
+        .unwrap()
+}
+
+async fn call(
+    channel: &Channel,
+    method: &str,
+    metadata: Option<(&'static str, &str)>,
+) -> Result<Response<Visits>, Status> {
+    let mut grpc = tonic::client::Grpc::new(channel.clone());
+    grpc.ready().await.unwrap();
+    let mut req = Request::new(Empty {});
+    if let Some((key, value)) = metadata {
+        req.metadata_mut().insert(key, value.parse().unwrap());
+    }
+    let path = format!("/sessions.Counter/{}", method)
+        .parse::<PathAndQuery>()
+        .unwrap();
+    grpc.unary(req, path, ProstCodec::default()).await
+}
+
+fn metadata(res: &Response<Visits>, key: &str) -> Option<String> {
+    res.metadata()
+        .get(key)
+        .map(|val| val.to_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn cookie_metadata() {
+    let config = config();
+    let channel = channel(TonicSessionLayer::new(config.clone())).await;
+
+    let res = call(&channel, "Visit", None).await.unwrap();
+    assert_eq!(res.get_ref().count, 1);
+    let set_cookie = metadata(&res, "set-cookie").unwrap();
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+    let res = call(&channel, "Visit", Some(("cookie", &cookie)))
+        .await
+        .unwrap();
+    assert_eq!(res.get_ref().count, 2);
+
+    let res = call(&channel, "Logout", Some(("cookie", &cookie)))
+        .await
+        .unwrap();
+    assert_eq!(
+        metadata(&res, "set-cookie"),
+        Some(config.cookie().to_removal_cookie())
+    );
+
+    let res = call(&channel, "Visit", Some(("cookie", &cookie)))
+        .await
+        .unwrap();
+    assert_eq!(res.get_ref().count, 1);
+}
+
+#[tokio::test]
+async fn required_session_id() {
+    let config = config();
+    let layer = TonicSessionLayer::new(config.clone())
+        .with_key(HeaderName::from_static("x-session-id"))
+        .with_required(true);
+    let channel = channel(layer).await;
+
+    let status = call(&channel, "Visit", None).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let unknown = nanoid::nanoid!(32);
+    let status = call(&channel, "Visit", Some(("x-session-id", &unknown)))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    // A session logged in elsewhere
+    let session = config.load_session("").await.unwrap();
+    session.set("user", "fundon").unwrap();
+    session.save().await.unwrap();
+    let sid = session.id().unwrap();
+
+    let res = call(&channel, "Visit", Some(("x-session-id", &sid)))
+        .await
+        .unwrap();
+    assert_eq!(res.get_ref().count, 1);
+    assert_eq!(metadata(&res, "x-session-id"), Some(sid.clone()));
+    assert_eq!(metadata(&res, "set-cookie"), None);
+
+    let res = call(&channel, "Logout", Some(("x-session-id", &sid)))
+        .await
+        .unwrap();
+    assert_eq!(metadata(&res, "x-session-id"), Some(String::new()));
+
+    let status = call(&channel, "Visit", Some(("x-session-id", &sid)))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn reissue_opaque_ids() {
+    let storage = Arc::new(MemoryStorage::new());
+    let config = |ids: OpaqueIds| {
+        Arc::new(
+            Config::builder()
+                .storage(storage.clone())
+                .generate(|| nanoid::nanoid!(32))
+                .verify(|sid: &str| sid.len() == 32)
+                .opaque_ids(ids)
+                .build()
+                .unwrap(),
+        )
+    };
+    let layer = |config: Arc<Config>| {
+        TonicSessionLayer::new(config).with_key(HeaderName::from_static("x-session-id"))
+    };
+    let a = Key::from([1; 32]);
+    let b = Key::from([2; 32]);
+
+    let old = config(OpaqueIds::new(a.clone()));
+    let session = old.load_session("").await.unwrap();
+    session.set("count", 1).unwrap();
+    session.save().await.unwrap();
+    let sid = session.id().unwrap();
+
+    // Rotated to the key B, the unchanged session gets a token under B
+    let rotated = channel(layer(config(OpaqueIds::with_keys(b.clone(), vec![a])))).await;
+    let res = call(&rotated, "Peek", Some(("x-session-id", &sid)))
+        .await
+        .unwrap();
+    assert_eq!(res.get_ref().count, 1);
+    let reissued = metadata(&res, "x-session-id").unwrap();
+    assert_ne!(reissued, sid);
+
+    let only_b = channel(layer(config(OpaqueIds::new(b)))).await;
+    let res = call(&only_b, "Peek", Some(("x-session-id", &reissued)))
+        .await
+        .unwrap();
+    assert_eq!(res.get_ref().count, 1);
+    assert_eq!(metadata(&res, "x-session-id"), None);
+}
@@ -0,0 +1,118 @@
+#![cfg(all(feature = "tower", feature = "memory"))]
+
+use std::{convert::Infallible, sync::Arc};
+
+use anyhow::Result;
+use futures_executor::block_on;
+use http::{
+    header::{COOKIE, SET_COOKIE},
+    Request, Response,
+};
+use tower::{service_fn, Layer, ServiceExt};
+
+use sessions::*;
+
+fn config() -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn handler(req: Request<()>) -> Result<Response<String>, Infallible> {
+    let session = req.extensions().get::<Session>().unwrap();
+    let count = session.get::<i32>("count").unwrap().unwrap_or_default();
+    let count = match req.uri().path() {
+        "/increment" => {
+            session.set("count", count + 1).unwrap();
+            count + 1
+        }
+        "/logout" => {
+            session.destroy().await.unwrap();
+            0
+        }
+        _ => count,
+    };
+    Ok(Response::new(count.to_string()))
+}
+
+async fn send(
+    config: &Arc<Config>,
+    path: &str,
+    cookie: Option<&str>,
+) -> Result<(String, Option<String>)> {
+    let service = SessionLayer::new(config.clone()).layer(service_fn(handler));
+    let mut req = Request::get(path);
+    if let Some(cookie) = cookie {
+        req = req.header(COOKIE, cookie);
+    }
+    let res = service.oneshot(req.body(())?).await?;
+    let set_cookie = res
+        .headers()
+        .get(SET_COOKIE)
+        .map(|val| val.to_str().unwrap().to_string());
+    Ok((res.into_body(), set_cookie))
+}
+
+#[test]
+fn counter() -> Result<()> {
+    block_on(async {
+        let config = config();
+
+        // A fresh session is neither saved nor sent
+        let (body, set_cookie) = send(&config, "/", None).await?;
+        assert_eq!(body, "0");
+        assert_eq!(set_cookie, None);
+
+        let (body, set_cookie) = send(&config, "/increment", None).await?;
+        assert_eq!(body, "1");
+        let set_cookie = set_cookie.unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        let (body, _) = send(&config, "/increment", Some(&cookie)).await?;
+        assert_eq!(body, "2");
+
+        // A read-only request doesn't reissue the cookie
+        let (body, set_cookie) = send(&config, "/", Some(&cookie)).await?;
+        assert_eq!(body, "2");
+        assert_eq!(set_cookie, None);
+
+        let (body, set_cookie) = send(&config, "/logout", Some(&cookie)).await?;
+        assert_eq!(body, "0");
+        assert_eq!(set_cookie, Some(config.cookie().to_removal_cookie()));
+
+        let (body, _) = send(&config, "/", Some(&cookie)).await?;
+        assert_eq!(body, "0");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn rolling() -> Result<()> {
+    block_on(async {
+        let config = Arc::new(
+            Config::builder()
+                .storage(Arc::new(MemoryStorage::new()))
+                .generate(|| nanoid::nanoid!(32))
+                .verify(|sid: &str| sid.len() == 32)
+                .rolling(true)
+                .build()?,
+        );
+
+        let (_, set_cookie) = send(&config, "/increment", None).await?;
+        let set_cookie = set_cookie.unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        // A rolling session gets its cookie reissued on every request
+        let (body, set_cookie) = send(&config, "/", Some(&cookie)).await?;
+        assert_eq!(body, "1");
+        assert!(set_cookie.unwrap().starts_with(&cookie));
+
+        Ok(())
+    })
+}
@@ -0,0 +1,201 @@
+#![cfg(all(feature = "tower-sessions", feature = "memory"))]
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use futures_executor::block_on;
+use futures_timer::Delay;
+use serde_json::json;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    SessionStore,
+};
+use tower_sessions_memory_store::MemoryStore;
+
+use sessions::*;
+
+fn record(expiry: Duration) -> Record {
+    let mut data = HashMap::new();
+    data.insert("user".to_string(), json!("fundon"));
+    data.insert("visits".to_string(), json!(3));
+    Record {
+        id: Id::default(),
+        data,
+        expiry_date: OffsetDateTime::now_utc() + expiry,
+    }
+}
+
+fn data() -> Data {
+    let mut data = Data::new();
+    data.insert("user".into(), json!("fundon"));
+    data.insert("roles".into(), json!(["admin"]));
+    data
+}
+
+fn millis(date: OffsetDateTime) -> i128 {
+    date.unix_timestamp_nanos() / 1_000_000
+}
+
+fn assert_expires_in(date: OffsetDateTime, exp: Duration) {
+    let diff = date - (OffsetDateTime::now_utc() + exp);
+    assert!(diff.abs() < time::Duration::seconds(1), "{}", diff);
+}
+
+#[test]
+fn tower_store_over_storage() -> Result<()> {
+    block_on(async {
+        let storage = MemoryStorage::new();
+        let store = TowerSessionStoreAdapter::new(storage.clone());
+
+        let mut saved = record(Duration::from_secs(3600));
+        store.create(&mut saved).await?;
+        let loaded = store.load(&saved.id).await?.unwrap();
+        assert_eq!(loaded.id, saved.id);
+        assert_eq!(loaded.data, saved.data);
+        assert_eq!(millis(loaded.expiry_date), millis(saved.expiry_date));
+
+        // The storage holds the data and the expiry date under the id
+        let stored = storage.get(&saved.id.to_string()).await?.unwrap();
+        assert_eq!(stored["user"], "fundon");
+        assert_eq!(stored[keys::EXPIRY_DATE], millis(saved.expiry_date) as u64);
+
+        // Creating the same id again picks another one
+        let mut other = saved.clone();
+        store.create(&mut other).await?;
+        assert_ne!(other.id, saved.id);
+
+        store.delete(&saved.id).await?;
+        assert!(store.load(&saved.id).await?.is_none());
+        assert!(storage.get(&saved.id.to_string()).await?.is_none());
+
+        // Data saved without an expiry date expires like a new tower-sessions session
+        let id = Id::default();
+        storage
+            .set(&id.to_string(), data(), Duration::from_secs(60))
+            .await?;
+        let loaded = store.load(&id).await?.unwrap();
+        assert_eq!(loaded.data.len(), 2);
+        assert_expires_in(loaded.expiry_date, Duration::from_secs(14 * 24 * 60 * 60));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn tower_store_expiry() -> Result<()> {
+    block_on(async {
+        let storage = MemoryStorage::new();
+        let store = TowerSessionStoreAdapter::new(storage.clone());
+
+        // The storage expires the record with it
+        let short = record(Duration::from_millis(50));
+        store.save(&short).await?;
+        assert!(storage.get(&short.id.to_string()).await?.is_some());
+        Delay::new(Duration::from_millis(100)).await;
+        assert!(storage.get(&short.id.to_string()).await?.is_none());
+        assert!(store.load(&short.id).await?.is_none());
+
+        // An expired record is removed
+        let mut expired = record(Duration::from_secs(60));
+        store.save(&expired).await?;
+        expired.expiry_date = OffsetDateTime::now_utc() - time::Duration::seconds(1);
+        store.save(&expired).await?;
+        assert!(storage.get(&expired.id.to_string()).await?.is_none());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn storage_over_tower_store() -> Result<()> {
+    block_on(async {
+        let store = MemoryStore::default();
+        let storage = StorageAdapter::new(store.clone());
+        let exp = Duration::from_secs(3600);
+
+        // The id of a tower-sessions cookie is the id of the record
+        let id = Id::default();
+        storage.set(&id.to_string(), data(), exp).await?;
+        assert_eq!(storage.get(&id.to_string()).await?, Some(data()));
+        let record = store.load(&id).await?.unwrap();
+        assert_eq!(record.data.len(), 2);
+        assert_eq!(record.data["roles"], json!(["admin"]));
+        assert_expires_in(record.expiry_date, exp);
+
+        // Other keys are hashed into ids
+        let key = nanoid::nanoid!(32);
+        storage.set(&key, data(), exp).await?;
+        assert_eq!(storage.get(&key).await?, Some(data()));
+        storage.remove(&key).await?;
+        assert!(storage.get(&key).await?.is_none());
+
+        storage
+            .set("short", data(), Duration::from_millis(50))
+            .await?;
+        Delay::new(Duration::from_millis(100)).await;
+        assert!(storage.get("short").await?.is_none());
+
+        // Records of tower-sessions are readable
+        let mut created = self::record(exp);
+        store.create(&mut created).await?;
+        let data = storage.get(&created.id.to_string()).await?.unwrap();
+        assert_eq!(data["visits"], 3);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn sessions_over_tower_store() -> Result<()> {
+    block_on(async {
+        let store = MemoryStore::default();
+        let config = Arc::new(
+            Config::builder()
+                .storage(Arc::new(StorageAdapter::new(store.clone())))
+                .generate(|| nanoid::nanoid!(32))
+                .verify(|sid: &str| sid.len() == 32)
+                .build()?,
+        );
+
+        let id = config.generate();
+        let session = Session::new(&id, SessionStatus::Unchanged, config.clone());
+        session.set("user", "fundon")?;
+        session.save().await?;
+
+        let loaded = config.load_session(&id).await?;
+        assert!(!loaded.is_new());
+        assert_eq!(loaded.get::<String>("user")?, Some("fundon".to_string()));
+
+        loaded.destroy().await?;
+        assert!(config.load_session(&id).await?.is_new());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn both_ways() -> Result<()> {
+    block_on(async {
+        let memory = MemoryStorage::new();
+        let storage = StorageAdapter::new(TowerSessionStoreAdapter::new(memory.clone()));
+        let exp = Duration::from_secs(600);
+
+        let id = Id::default().to_string();
+        storage.set(&id, data(), exp).await?;
+        assert_eq!(storage.get(&id).await?, Some(data()));
+
+        let stored = memory.get(&id).await?.unwrap();
+        let expiry_date = stored[keys::EXPIRY_DATE].as_u64().unwrap();
+        assert_expires_in(OffsetDateTime::from(from_millis(expiry_date).unwrap()), exp);
+
+        let store = TowerSessionStoreAdapter::new(StorageAdapter::new(MemoryStore::default()));
+        let saved = record(exp);
+        store.save(&saved).await?;
+        let loaded = store.load(&saved.id).await?.unwrap();
+        assert_eq!(loaded.data, saved.data);
+        assert_eq!(millis(loaded.expiry_date), millis(saved.expiry_date));
+
+        Ok(())
+    })
+}
@@ -0,0 +1,8 @@
+use sessions::SessionData;
+
+#[derive(SessionData)]
+enum Login {
+    Guest,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use sessions::SessionData;
+
+#[derive(Default, SessionData)]
+struct Login {
+    #[session(key = 1)]
+    user_id: u64,
+}
+
+fn main() {}
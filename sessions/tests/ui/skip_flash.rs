@@ -0,0 +1,9 @@
+use sessions::SessionData;
+
+#[derive(Default, SessionData)]
+struct Login {
+    #[session(skip, flash)]
+    message: String,
+}
+
+fn main() {}
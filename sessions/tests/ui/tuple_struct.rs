@@ -0,0 +1,6 @@
+use sessions::SessionData;
+
+#[derive(Default, SessionData)]
+struct Login(u64);
+
+fn main() {}
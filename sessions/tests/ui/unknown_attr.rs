@@ -0,0 +1,9 @@
+use sessions::SessionData;
+
+#[derive(Default, SessionData)]
+struct Login {
+    #[session(rename = "user")]
+    user_id: u64,
+}
+
+fn main() {}
@@ -0,0 +1,103 @@
+#![cfg(all(feature = "viz", feature = "memory"))]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures_executor::block_on;
+use http::{
+    header::{COOKIE, SET_COOKIE},
+    StatusCode,
+};
+use http_body_util::BodyExt;
+use viz::{Body, Error, Handler, HandlerExt, IntoHandler, IntoResponse, Request, Response};
+
+use sessions::*;
+
+fn config() -> Arc<Config> {
+    Arc::new(
+        Config::builder()
+            .storage(Arc::new(MemoryStorage::new()))
+            .generate(|| nanoid::nanoid!(32))
+            .verify(|sid: &str| sid.len() == 32)
+            .build()
+            .unwrap(),
+    )
+}
+
+async fn count(session: Session) -> viz::Result<String> {
+    let count = session
+        .get::<u64>("count")
+        .map_err(Error::boxed)?
+        .unwrap_or_default()
+        + 1;
+    session.set("count", count).map_err(Error::boxed)?;
+    Ok(count.to_string())
+}
+
+async fn logout(session: Session) -> viz::Result<StatusCode> {
+    session.destroy().await.map_err(Error::boxed)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn send<H>(handler: &H, cookie: Option<&str>) -> Result<(StatusCode, String, Option<String>)>
+where
+    H: Handler<Request, Output = viz::Result<Response>>,
+{
+    let mut req = Request::builder().uri("/");
+    if let Some(cookie) = cookie {
+        req = req.header(COOKIE, cookie);
+    }
+    let res = match handler.call(req.body(Body::Empty)?).await {
+        Ok(res) => res,
+        Err(e) => e.into_response(),
+    };
+    let status = res.status();
+    let set_cookie = res
+        .headers()
+        .get(SET_COOKIE)
+        .map(|val| val.to_str().unwrap().to_string());
+    let body = res.into_body().collect().await?.to_bytes();
+    Ok((status, String::from_utf8(body.to_vec())?, set_cookie))
+}
+
+#[test]
+fn counter() -> Result<()> {
+    block_on(async {
+        let config = config();
+        let count = count
+            .into_handler()
+            .with(SessionTransform::new(config.clone()));
+        let logout = logout
+            .into_handler()
+            .with(SessionTransform::new(config.clone()));
+
+        let (status, body, set_cookie) = send(&count, None).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "1");
+        let set_cookie = set_cookie.unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        let (_, body, _) = send(&count, Some(&cookie)).await?;
+        assert_eq!(body, "2");
+
+        let (status, _, set_cookie) = send(&logout, Some(&cookie)).await?;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(set_cookie, Some(config.cookie().to_removal_cookie()));
+
+        let (_, body, _) = send(&count, Some(&cookie)).await?;
+        assert_eq!(body, "1");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn missing_transform() -> Result<()> {
+    block_on(async {
+        let (status, body, _) = send(&count.into_handler().map_into_response(), None).await?;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("`SessionTransform`"));
+
+        Ok(())
+    })
+}